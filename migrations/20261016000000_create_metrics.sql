@@ -0,0 +1,31 @@
+-- Latest aggregates of each endpoint, one row per endpoint
+CREATE TABLE endpoint_aggregates (
+    endpoint TEXT PRIMARY KEY NOT NULL,
+    total_checks INTEGER NOT NULL,
+    successful_checks INTEGER NOT NULL,
+    failed_checks INTEGER NOT NULL,
+    total_downtime INTEGER NOT NULL,
+    last_check TEXT,
+    last_status TEXT,
+    last_status_code INTEGER,
+    average_response_time REAL NOT NULL,
+    uptime_percentage REAL NOT NULL,
+    request_bytes INTEGER NOT NULL,
+    response_bytes INTEGER NOT NULL,
+    -- Every field, as in the metrics file
+    metrics TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- One row per check
+CREATE TABLE check_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    endpoint TEXT NOT NULL,
+    checked_at TEXT NOT NULL,
+    success INTEGER NOT NULL,
+    response_time_ms INTEGER NOT NULL,
+    status_code INTEGER,
+    failure_reason TEXT
+);
+
+CREATE INDEX check_history_endpoint_time ON check_history (endpoint, checked_at);
@@ -0,0 +1,50 @@
+-- The database replaces the metrics file, so every field of an endpoint's
+-- metrics gets a column of its own instead of a JSON copy of the entry. Rows
+-- written by the previous schema are re-imported from the metrics file, which
+-- was still written alongside them.
+DROP TABLE endpoint_aggregates;
+
+-- Latest aggregates of each endpoint, one row per endpoint
+CREATE TABLE endpoint_aggregates (
+    -- Key the endpoint is stored under: its identity, or its URL without one
+    endpoint TEXT PRIMARY KEY NOT NULL,
+    url TEXT NOT NULL,
+    total_checks INTEGER NOT NULL,
+    successful_checks INTEGER NOT NULL,
+    failed_checks INTEGER NOT NULL,
+    total_downtime INTEGER NOT NULL,
+    last_check TEXT,
+    last_status TEXT,
+    average_response_time REAL NOT NULL,
+    request_bytes INTEGER NOT NULL,
+    response_bytes INTEGER NOT NULL,
+    transport_retries INTEGER NOT NULL,
+    ipv4_connect_time REAL,
+    ipv6_connect_time REAL,
+    last_outcome TEXT,
+    last_status_code INTEGER,
+    draining_checks INTEGER NOT NULL,
+    last_response_time REAL,
+    recent_error_rate REAL NOT NULL,
+    health_score REAL,
+    deploy_downtime INTEGER NOT NULL,
+    maintenance_checks INTEGER NOT NULL,
+    maintenance_downtime INTEGER NOT NULL,
+    bytes_today INTEGER NOT NULL,
+    traffic_day TEXT,
+    range_supported INTEGER,
+    priority TEXT NOT NULL,
+    consecutive_failures INTEGER NOT NULL,
+    consecutive_successes INTEGER NOT NULL,
+    clock_skew REAL,
+    cert_expiry TEXT,
+    scheduling_delay REAL,
+    -- Structured fields, as JSON
+    latency_budget TEXT,
+    uptime_windows TEXT NOT NULL,
+    response_quantiles TEXT NOT NULL,
+    cluster_health TEXT,
+    -- Derived from the counters, for queries
+    uptime_percentage REAL NOT NULL,
+    updated_at TEXT NOT NULL
+);
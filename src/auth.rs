@@ -0,0 +1,127 @@
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Client,
+};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// A "log in, then check" flow: a POST whose JSON response carries a token
+/// that is injected into a header on the checks that need it.
+#[derive(Debug)]
+pub struct AuthFlow {
+    pub login_url: String,
+    pub login_body: Option<Value>,
+    /// JSONPath to the token in the login response, e.g. `$.data.access_token`
+    pub token_path: String,
+    pub header_name: HeaderName,
+    /// Header value with `{token}` replaced by the token, e.g. `Bearer {token}`
+    pub header_template: String,
+    /// Overrides the login response's `expires_in`
+    pub ttl: Option<Duration>,
+    /// Endpoints that use the token; every HTTP endpoint when empty
+    pub endpoints: Vec<String>,
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: Option<Instant>,
+}
+
+impl AuthFlow {
+    pub fn applies_to(&self, endpoint: &str) -> bool {
+        self.endpoints.is_empty() || self.endpoints.iter().any(|e| e == endpoint)
+    }
+
+    pub fn header_value(&self, token: &str) -> Result<HeaderValue, String> {
+        HeaderValue::from_str(&self.header_template.replace("{token}", token))
+            .map_err(|e| format!("token is not a valid header value: {}", e))
+    }
+
+    async fn login(&self, client: &Client) -> Result<CachedToken, String> {
+        let mut request = client.post(&self.login_url);
+        if let Some(body) = &self.login_body {
+            request = request.json(body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("login request failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("login returned HTTP {}", status));
+        }
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("login response is not JSON: {}", e))?;
+
+        let token = json_path(&json, &self.token_path)
+            .and_then(|value| match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| format!("no token at {} in login response", self.token_path))?;
+        let ttl = self.ttl.or_else(|| {
+            json.get("expires_in")
+                .and_then(Value::as_u64)
+                .map(Duration::from_secs)
+        });
+
+        info!("Fetched auth token from {}", self.login_url);
+        Ok(CachedToken {
+            token,
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        })
+    }
+}
+
+/// Caches the token between checks so the login only runs when it is missing,
+/// expired or rejected.
+#[derive(Default)]
+pub struct TokenCache(Mutex<Option<CachedToken>>);
+
+impl TokenCache {
+    pub async fn token(
+        &self,
+        flow: &AuthFlow,
+        client: &Client,
+        refresh: bool,
+    ) -> Result<String, String> {
+        let mut cached = self.0.lock().await;
+        let fresh = cached
+            .as_ref()
+            .is_some_and(|c| c.expires_at.is_none_or(|at| at > Instant::now()));
+        if refresh || !fresh {
+            *cached = Some(flow.login(client).await?);
+        }
+        Ok(cached.as_ref().map(|c| c.token.clone()).unwrap_or_default())
+    }
+}
+
+/// Evaluates the dotted subset of JSONPath: `$.a.b[0].c` or `$['a']['b']`.
+pub fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.trim_start_matches('$')
+        .replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .map(|segment| segment.trim_matches(|c| c == '\'' || c == '"'))
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => current.get(segment),
+        })
+}
+
+/// Parses `Name: value template`, e.g. `Authorization: Bearer {token}`.
+pub fn parse_header_template(value: &str) -> Result<(HeaderName, String), String> {
+    let (name, template) = value.split_once(':').ok_or("expected NAME: TEMPLATE")?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|e| format!("invalid header name '{}': {}", name.trim(), e))?;
+    if !template.contains("{token}") {
+        return Err("template must contain {token}".into());
+    }
+    Ok((name, template.trim().to_string()))
+}
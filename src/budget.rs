@@ -0,0 +1,175 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Months of final consumption kept for reports.
+const HISTORY_MONTHS: usize = 12;
+
+/// Alert thresholds in percent of the budget consumed.
+const THRESHOLDS: [u8; 2] = [80, 100];
+
+/// One `--latency-slo URL=MS[@PERCENTILE]` entry, e.g. "p95 under 400ms".
+#[derive(Debug, Clone)]
+pub struct LatencySlo {
+    pub endpoint: String,
+    pub target_ms: f64,
+    pub percentile: f64,
+}
+
+pub fn parse_latency_slo(value: &str) -> Result<LatencySlo, String> {
+    let (endpoint, rule) = value
+        .rsplit_once('=')
+        .ok_or("expected URL=MS[@PERCENTILE]")?;
+    let (target, percentile) = rule.split_once('@').unwrap_or((rule, "95"));
+    let target_ms: f64 = target
+        .trim_end_matches("ms")
+        .parse()
+        .ok()
+        .filter(|ms| *ms > 0.0)
+        .ok_or_else(|| format!("invalid latency target '{}'", target))?;
+    let percentile: f64 = percentile
+        .trim_start_matches('p')
+        .parse()
+        .ok()
+        .filter(|p| *p > 0.0 && *p < 100.0)
+        .ok_or_else(|| format!("percentile must be between 0 and 100, got '{}'", percentile))?;
+    Ok(LatencySlo {
+        endpoint: endpoint.to_string(),
+        target_ms,
+        percentile,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthlyBudget {
+    pub month: String,
+    pub consumed: f64,
+}
+
+/// How much of a month's latency budget an endpoint has used. The budget is
+/// the number of checks allowed over the target in the whole month, so the
+/// meter only grows until the month rolls over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBudget {
+    /// `YYYY-MM` in the configured timezone
+    pub month: String,
+    pub target_ms: f64,
+    pub percentile: f64,
+    pub checks: u64,
+    /// Checks slower than the target, or failed
+    pub slow_checks: u64,
+    /// Slow checks allowed this month at the current check interval
+    pub allowed_slow_checks: f64,
+    /// Highest threshold already alerted on this month
+    pub alerted: u8,
+    /// Final consumption of previous months, oldest first
+    #[serde(default)]
+    pub history: Vec<MonthlyBudget>,
+}
+
+/// A budget crossing one of [`THRESHOLDS`].
+pub struct BudgetAlert {
+    pub endpoint: String,
+    pub threshold: u8,
+    pub consumed: f64,
+    pub target_ms: f64,
+    pub percentile: f64,
+}
+
+impl BudgetAlert {
+    pub fn message(&self) -> String {
+        format!(
+            "⏳ Latency budget for {} is {:.0}% consumed this month (p{} < {}ms), past the {}% mark",
+            self.endpoint, self.consumed, self.percentile, self.target_ms, self.threshold
+        )
+    }
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let (year, month) = (date.year(), date.month());
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    next.and_then(|next| next.pred_opt())
+        .map_or(30, |last| last.day())
+}
+
+impl LatencyBudget {
+    /// Percentage of this month's budget used so far; over 100 once blown.
+    pub fn consumed(&self) -> f64 {
+        if self.allowed_slow_checks <= 0.0 {
+            return 0.0;
+        }
+        self.slow_checks as f64 / self.allowed_slow_checks * 100.0
+    }
+
+    /// Records one check against the endpoint's budget, creating or rolling it
+    /// over as needed, and returns an alert when a threshold is crossed.
+    pub fn record(
+        budget: &mut Option<LatencyBudget>,
+        slo: &LatencySlo,
+        at: DateTime<Utc>,
+        timezone: Tz,
+        interval: Duration,
+        response_time: f64,
+        success: bool,
+    ) -> Option<BudgetAlert> {
+        let local = at.with_timezone(&timezone).date_naive();
+        let month = local.format("%Y-%m").to_string();
+        let expected_checks =
+            days_in_month(local) as f64 * 86400.0 / interval.as_secs_f64().max(1.0);
+
+        let budget = budget.get_or_insert_with(|| LatencyBudget {
+            month: month.clone(),
+            target_ms: slo.target_ms,
+            percentile: slo.percentile,
+            checks: 0,
+            slow_checks: 0,
+            allowed_slow_checks: 0.0,
+            alerted: 0,
+            history: Vec::new(),
+        });
+        if budget.month != month {
+            if budget.checks > 0 {
+                budget.history.push(MonthlyBudget {
+                    month: budget.month.clone(),
+                    consumed: budget.consumed(),
+                });
+                if budget.history.len() > HISTORY_MONTHS {
+                    budget.history.remove(0);
+                }
+            }
+            budget.month = month;
+            budget.checks = 0;
+            budget.slow_checks = 0;
+            budget.alerted = 0;
+        }
+
+        // The SLO may have changed since the budget was saved
+        budget.target_ms = slo.target_ms;
+        budget.percentile = slo.percentile;
+        budget.allowed_slow_checks = expected_checks * (1.0 - slo.percentile / 100.0);
+        budget.checks += 1;
+        if !success || response_time * 1000.0 > slo.target_ms {
+            budget.slow_checks += 1;
+        }
+
+        let consumed = budget.consumed();
+        let threshold = THRESHOLDS
+            .iter()
+            .rev()
+            .copied()
+            .find(|&t| consumed >= t as f64 && t > budget.alerted)?;
+        budget.alerted = threshold;
+        Some(BudgetAlert {
+            endpoint: slo.endpoint.clone(),
+            threshold,
+            consumed,
+            target_ms: slo.target_ms,
+            percentile: slo.percentile,
+        })
+    }
+}
@@ -0,0 +1,1122 @@
+use crate::outcome::Outcome;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive};
+use tracing::info;
+
+pub struct CheckResult {
+    pub(crate) success: bool,
+    pub(crate) response_time: f64,
+    pub(crate) request_bytes: u64,
+    pub(crate) response_bytes: u64,
+    pub(crate) failure_reason: Option<String>,
+    /// Whether the request was retried after a transport error
+    pub(crate) retried: bool,
+    /// Per-address-family TCP connect times, when `--ip-family-timing` is on
+    pub(crate) family_timings: Option<FamilyTimings>,
+    /// Raw result category, kept even when an outcome mapping overrides `success`
+    pub(crate) outcome: Outcome,
+    /// Whether a `--range-request` endpoint served the range correctly
+    pub(crate) range_supported: Option<bool>,
+    /// Seconds the server's `Date` header is ahead of the monitor, for `--max-clock-skew` endpoints
+    pub(crate) clock_skew: Option<f64>,
+    /// Parsed cluster health of an `--elasticsearch` endpoint
+    pub(crate) cluster_health: Option<ClusterHealth>,
+    /// Value of the endpoint's `warmup_version_header`, when it has one
+    pub(crate) version: Option<String>,
+    /// HTTP status of the response, when there was one
+    pub(crate) status_code: Option<u16>,
+    /// The response carried the endpoint's `drain_header`
+    pub(crate) draining: bool,
+    /// When the leaf TLS certificate expires, for HTTPS endpoints with `--cert-expiry-warn-days`
+    pub(crate) cert_expiry: Option<DateTime<Utc>>,
+    /// Response headers on the endpoint's `capture_headers` allowlist
+    pub(crate) captured_headers: BTreeMap<String, String>,
+}
+
+impl CheckResult {
+    pub(crate) fn failed(reason: String, request_bytes: u64) -> Self {
+        Self {
+            success: false,
+            response_time: 0.0,
+            request_bytes,
+            response_bytes: 0,
+            failure_reason: Some(reason),
+            retried: false,
+            family_timings: None,
+            range_supported: None,
+            clock_skew: None,
+            cluster_health: None,
+            version: None,
+            status_code: None,
+            draining: false,
+            cert_expiry: None,
+            captured_headers: BTreeMap::new(),
+            outcome: Outcome::Other,
+        }
+    }
+}
+
+/// HTTP statuses that count as up, e.g. `200,204,301-399`. Empty means any 2xx.
+#[derive(Debug, Clone, Default)]
+pub struct StatusSet(Vec<RangeInclusive<u16>>);
+
+impl StatusSet {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn accepts(&self, status: StatusCode) -> bool {
+        if self.0.is_empty() {
+            return status.is_success();
+        }
+        self.0.iter().any(|range| range.contains(&status.as_u16()))
+    }
+}
+
+impl fmt::Display for StatusSet {
+    /// The list as written on the command line, e.g. `200,204,301-399`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|range| match (range.start(), range.end()) {
+                (start, end) if start == end => start.to_string(),
+                (start, end) => format!("{}-{}", start, end),
+            })
+            .collect();
+        f.write_str(&parts.join(","))
+    }
+}
+
+/// Parses a comma-separated list of statuses and inclusive ranges.
+pub fn parse_status_set(value: &str) -> Result<StatusSet, String> {
+    let parse_status = |text: &str| {
+        text.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|status| (100..=599).contains(status))
+            .ok_or_else(|| format!("'{}' is not an HTTP status", text.trim()))
+    };
+    let ranges = value
+        .split(',')
+        .map(|part| match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_status(start)?, parse_status(end)?);
+                if start > end {
+                    return Err(format!(
+                        "range {}-{} is reversed; write it as {}-{}",
+                        start, end, end, start
+                    ));
+                }
+                Ok(start..=end)
+            }
+            None => parse_status(part).map(|status| status..=status),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(StatusSet(ranges))
+}
+
+/// Rules that mark an otherwise successful response as a failure.
+#[derive(Debug, Default)]
+pub struct ResponseRules {
+    /// Fail when the `Server` header contains any of these (case-insensitive),
+    /// e.g. a CDN edge serving its own error page
+    pub reject_server_headers: Vec<String>,
+    /// Fail when the body contains any of these, e.g. a proxy's block page
+    pub reject_body_contains: Vec<String>,
+    /// Fail unless the body contains all of these, e.g. text only the real
+    /// page has; an endpoint's `must_contain` in the config file replaces them
+    pub must_contain: Vec<String>,
+    /// Fail unless the body matches all of these
+    pub must_match: Vec<Regex>,
+    /// Headers every successful response must carry
+    pub require_headers: Vec<HeaderRequirement>,
+    /// Minimum `Strict-Transport-Security` max-age in seconds
+    pub hsts_min_age: Option<u64>,
+}
+
+/// A response header that must be present, optionally containing a value
+/// (case-insensitive), e.g. `X-Content-Type-Options: nosniff`.
+#[derive(Debug, Clone)]
+pub struct HeaderRequirement {
+    pub name: String,
+    pub contains: Option<String>,
+}
+
+pub fn parse_header_requirement(value: &str) -> Result<HeaderRequirement, String> {
+    let (name, contains) = match value.split_once('=') {
+        Some((name, contains)) => (name, Some(contains.to_string())),
+        None => (value, None),
+    };
+    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| format!("invalid header name '{}': {}", name, e))?;
+    Ok(HeaderRequirement {
+        name: name.to_string(),
+        contains,
+    })
+}
+
+impl HeaderRequirement {
+    /// The baseline enforced by `--security-headers`.
+    pub fn security_baseline() -> Vec<Self> {
+        let require = |name: &str, contains: Option<&str>| Self {
+            name: name.to_string(),
+            contains: contains.map(str::to_string),
+        };
+        vec![
+            require("Strict-Transport-Security", Some("max-age")),
+            require("Content-Security-Policy", None),
+            require("X-Content-Type-Options", Some("nosniff")),
+        ]
+    }
+}
+
+fn hsts_max_age(value: &str) -> Option<u64> {
+    value.split(';').find_map(|directive| {
+        let (name, age) = directive.trim().split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("max-age")
+            .then(|| age.trim().trim_matches('"').parse().ok())?
+    })
+}
+
+fn check_security_headers(headers: &HeaderMap, rules: &ResponseRules) -> Result<(), String> {
+    for requirement in &rules.require_headers {
+        let Some(value) = headers.get(requirement.name.as_str()) else {
+            return Err(format!("missing header {}", requirement.name));
+        };
+        let value = value.to_str().unwrap_or_default();
+        if let Some(expected) = &requirement.contains {
+            if !value.to_lowercase().contains(&expected.to_lowercase()) {
+                return Err(format!(
+                    "weak header {}: '{}' does not contain '{}'",
+                    requirement.name, value, expected
+                ));
+            }
+        }
+    }
+
+    if let Some(min_age) = rules.hsts_min_age {
+        let value = headers
+            .get(reqwest::header::STRICT_TRANSPORT_SECURITY)
+            .and_then(|value| value.to_str().ok())
+            .ok_or("missing header Strict-Transport-Security")?;
+        match hsts_max_age(value) {
+            Some(age) if age >= min_age => {}
+            _ => {
+                return Err(format!(
+                    "weak header Strict-Transport-Security: '{}' is below max-age={}",
+                    value, min_age
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl ResponseRules {
+    pub fn needs_body(&self) -> bool {
+        !self.reject_body_contains.is_empty()
+            || !self.must_contain.is_empty()
+            || !self.must_match.is_empty()
+    }
+}
+
+/// True for hyper's "connection closed before message completed", which usually
+/// means a pooled connection was closed by the server just as it was reused.
+pub fn is_connection_closed(error: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(err) = source {
+        if err
+            .to_string()
+            .contains("connection closed before message completed")
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+// Approximate on-the-wire size of an HTTP/1.1 request or response head.
+pub fn header_bytes(headers: &HeaderMap) -> u64 {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().len() + value.len() + 4) as u64)
+        .sum::<u64>()
+        + 2
+}
+
+/// Decides whether a response counts as up, returning the failure reason if not.
+///
+/// Intermediary rules are evaluated before the status code so that a CDN's
+/// branded 503 is reported as an intermediary error rather than an origin failure.
+/// Any 2xx is up unless `expected_status` lists the accepted statuses, and
+/// then only if the body contains each of `must_contain` and matches each of
+/// the rules' `must_match`.
+pub fn evaluate_response(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+    rules: &ResponseRules,
+    expected_status: &StatusSet,
+    must_contain: &[String],
+) -> Result<(), String> {
+    if let Some(server) = headers
+        .get(reqwest::header::SERVER)
+        .and_then(|value| value.to_str().ok())
+    {
+        let server_lower = server.to_lowercase();
+        if let Some(rule) = rules
+            .reject_server_headers
+            .iter()
+            .find(|rule| server_lower.contains(&rule.to_lowercase()))
+        {
+            return Err(format!(
+                "intermediary error page: Server header '{}' matched rule '{}'",
+                server, rule
+            ));
+        }
+    }
+
+    if let Some(body) = body {
+        let text = String::from_utf8_lossy(body);
+        if let Some(rule) = rules
+            .reject_body_contains
+            .iter()
+            .find(|rule| text.contains(rule.as_str()))
+        {
+            return Err(format!("intermediary error page: body contains '{}'", rule));
+        }
+    }
+
+    if !expected_status.accepts(status) {
+        return Err(format!("HTTP {}", status));
+    }
+
+    let text = String::from_utf8_lossy(body.unwrap_or_default());
+    if let Some(keyword) = must_contain
+        .iter()
+        .find(|keyword| !text.contains(keyword.as_str()))
+    {
+        return Err(format!(
+            "status {} but keyword '{}' missing",
+            status.as_u16(),
+            keyword
+        ));
+    }
+    if let Some(pattern) = rules.must_match.iter().find(|re| !re.is_match(&text)) {
+        return Err(format!(
+            "status {} but body doesn't match /{}/",
+            status.as_u16(),
+            pattern
+        ));
+    }
+
+    check_security_headers(headers, rules)
+}
+
+/// Seconds the server's clock is ahead of ours (negative when behind), from the
+/// response's `Date` header. The server stamped it somewhere during the round
+/// trip, so it is compared against the midpoint between sending the request at
+/// `sent_at` and receiving the headers `round_trip` seconds later. `Date` only
+/// has one-second resolution, so skews under a second are noise.
+pub fn clock_skew(headers: &HeaderMap, sent_at: DateTime<Utc>, round_trip: f64) -> Option<f64> {
+    let date = headers
+        .get(reqwest::header::DATE)?
+        .to_str()
+        .ok()
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())?;
+    let midpoint = sent_at + chrono::Duration::microseconds((round_trip * 500_000.0) as i64);
+    Some((date.with_timezone(&Utc) - midpoint).num_milliseconds() as f64 / 1000.0)
+}
+
+/// Longest captured header value kept, in characters.
+const CAPTURED_HEADER_CHARS: usize = 128;
+
+/// The allowlisted response headers, keyed by lowercase name. Long values are
+/// truncated, and values whose header name looks secret or that match one of
+/// `redact` are replaced, so a careless allowlist can't leak credentials.
+pub fn capture_headers(
+    headers: &HeaderMap,
+    allowlist: &[String],
+    redact: &[Regex],
+) -> BTreeMap<String, String> {
+    allowlist
+        .iter()
+        .filter_map(|name| {
+            let values: Vec<String> = headers
+                .get_all(name.as_str())
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect();
+            if values.is_empty() {
+                return None;
+            }
+            let value = values.join(", ");
+            let value = if crate::recording::is_secret_key(name)
+                || redact.iter().any(|pattern| pattern.is_match(&value))
+            {
+                "[redacted]".to_string()
+            } else if value.chars().count() > CAPTURED_HEADER_CHARS {
+                let kept: String = value.chars().take(CAPTURED_HEADER_CHARS).collect();
+                format!("{}…", kept)
+            } else {
+                value
+            };
+            Some((name.to_lowercase(), value))
+        })
+        .collect()
+}
+
+/// Verifies the answer to a `Range: bytes=0-(requested - 1)` request: a 206 whose
+/// `Content-Range` starts at byte 0 and matches the body actually received.
+pub fn check_range(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body_bytes: u64,
+    requested: u64,
+) -> Result<(), String> {
+    if status != StatusCode::PARTIAL_CONTENT {
+        return Err(format!(
+            "range not supported: HTTP {} instead of 206 Partial Content",
+            status
+        ));
+    }
+    let content_range = headers
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("206 response without a Content-Range header")?;
+    let (start, end) = content_range
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('/'))
+        .and_then(|(span, _total)| span.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<u64>().ok()?, end.parse::<u64>().ok()?)))
+        .filter(|(start, end)| start <= end)
+        .ok_or_else(|| format!("malformed Content-Range '{}'", content_range))?;
+    if start != 0 || end >= requested {
+        return Err(format!(
+            "Content-Range '{}' does not match requested bytes 0-{}",
+            content_range,
+            requested - 1
+        ));
+    }
+    if end - start + 1 != body_bytes {
+        return Err(format!(
+            "partial content mismatch: Content-Range '{}' but {} bytes received",
+            content_range, body_bytes
+        ));
+    }
+    Ok(())
+}
+
+/// The parts of an Elasticsearch `GET /_cluster/health` answer kept in the metrics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterHealth {
+    /// `green`, `yellow` (all primaries assigned, some replicas not) or `red`
+    pub status: String,
+    pub number_of_nodes: u64,
+    #[serde(alias = "active_shards_percent_as_number")]
+    pub active_shards_percent: f64,
+    pub active_primary_shards: u64,
+}
+
+impl ClusterHealth {
+    /// Yellow still serves every index, so it counts as up.
+    pub fn is_degraded(&self) -> bool {
+        self.status == "yellow"
+    }
+}
+
+/// Parses an Elasticsearch cluster health body: green and yellow are up, red
+/// is down.
+pub fn check_cluster_health(body: &[u8]) -> Result<ClusterHealth, String> {
+    let health: ClusterHealth = serde_json::from_slice(body)
+        .map_err(|e| format!("not an Elasticsearch cluster health response: {}", e))?;
+    match health.status.as_str() {
+        "green" | "yellow" => Ok(health),
+        "red" => Err(format!(
+            "Elasticsearch cluster is red: {:.1}% of shards active, {} nodes",
+            health.active_shards_percent, health.number_of_nodes
+        )),
+        other => Err(format!("unknown Elasticsearch cluster status '{}'", other)),
+    }
+}
+
+/// Streaming endpoints (Server-Sent Events, long-lived chunked responses)
+/// checked by reading only the start of the stream, since their body never ends.
+#[derive(Debug, Default)]
+pub struct StreamRules {
+    pub endpoints: Vec<String>,
+    /// The first event, or the first bytes of other streams, must match this
+    pub expect: Option<Regex>,
+    /// Bytes read from a stream that isn't `text/event-stream` before judging it
+    pub min_bytes: usize,
+}
+
+impl StreamRules {
+    pub fn applies_to(&self, endpoint: &str) -> bool {
+        self.endpoints.iter().any(|e| e == endpoint)
+    }
+}
+
+/// Reads a streaming response until it has sent valid data: the first event
+/// with a `data` field for `text/event-stream`, otherwise at least
+/// `min_bytes`. Returns what was read, for `expect` to be matched against;
+/// dropping the response afterwards disconnects. The request's timeout bounds
+/// the wait, and `limit` how much is read looking for a complete event.
+pub async fn read_stream_start(
+    response: &mut reqwest::Response,
+    rules: &StreamRules,
+    limit: usize,
+) -> Result<Vec<u8>, String> {
+    let event_stream = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim_start().starts_with("text/event-stream"));
+    let mut received = Vec::new();
+    let mut total = 0;
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => {
+                return Err(format!(
+                    "stream ended after {} bytes without {}",
+                    total,
+                    if event_stream {
+                        "an event"
+                    } else {
+                        "enough data"
+                    }
+                ))
+            }
+            Err(e) if e.is_timeout() => {
+                return Err(format!(
+                    "no {} within the timeout ({} bytes received)",
+                    if event_stream { "event" } else { "stream data" },
+                    total
+                ))
+            }
+            Err(e) => return Err(format!("failed to read stream: {}", e)),
+        };
+        total += chunk.len();
+        received.extend_from_slice(&chunk);
+        if !event_stream {
+            if received.len() >= rules.min_bytes.max(1) {
+                return Ok(received);
+            }
+            continue;
+        }
+        // Events end at a blank line; blocks of only comments or keep-alives are skipped
+        let text = String::from_utf8_lossy(&received).replace("\r\n", "\n");
+        let mut consumed = 0;
+        for block in text.split_inclusive("\n\n") {
+            if !block.ends_with("\n\n") {
+                break;
+            }
+            consumed += block.len();
+            let has_data = block
+                .lines()
+                .any(|line| line == "data" || line.starts_with("data:"));
+            if has_data {
+                return Ok(block.trim_end().as_bytes().to_vec());
+            }
+        }
+        received = text.as_bytes()[consumed..].to_vec();
+        if total >= limit {
+            return Err(format!("no complete event in the first {} bytes", total));
+        }
+    }
+}
+
+/// Checks the start of a stream against `expect`.
+pub fn check_stream_data(data: &[u8], rules: &StreamRules) -> Result<(), String> {
+    let text = String::from_utf8_lossy(data);
+    match &rules.expect {
+        Some(pattern) if !pattern.is_match(&text) => Err(format!(
+            "stream data does not match /{}/: {:?}",
+            pattern,
+            text.chars().take(80).collect::<String>()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Application-level round trip performed after a WebSocket handshake.
+#[derive(Debug, Default)]
+pub struct WebSocketRules {
+    /// Text message sent once the connection is open (e.g. a subscribe frame)
+    pub send: Option<String>,
+    /// The reply must contain this substring
+    pub expect: Option<String>,
+    /// The reply must be JSON containing every field of this JSON value
+    pub expect_json: Option<serde_json::Value>,
+}
+
+impl WebSocketRules {
+    fn expects_reply(&self) -> bool {
+        self.expect.is_some() || self.expect_json.is_some()
+    }
+
+    fn matches(&self, message: &str) -> bool {
+        if let Some(expect) = &self.expect {
+            if !message.contains(expect.as_str()) {
+                return false;
+            }
+        }
+        if let Some(expected) = &self.expect_json {
+            match serde_json::from_str::<serde_json::Value>(message) {
+                Ok(actual) => return json_contains(&actual, expected),
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+/// True when every field in `expected` is present with the same value in `actual`.
+/// Arrays must match element for element.
+pub fn json_contains(actual: &serde_json::Value, expected: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .is_some_and(|actual| json_contains(actual, value))
+        }),
+        (Value::Array(actual), Value::Array(expected)) => {
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(actual, expected)| json_contains(actual, expected))
+        }
+        _ => actual == expected,
+    }
+}
+
+pub fn is_websocket(endpoint: &str) -> bool {
+    endpoint.starts_with("ws://") || endpoint.starts_with("wss://")
+}
+
+/// Completes a WebSocket handshake and, when configured, sends a message and
+/// waits for a matching reply.
+pub async fn check_websocket(
+    endpoint: &str,
+    rules: &WebSocketRules,
+    timeout: std::time::Duration,
+) -> CheckResult {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let start = std::time::Instant::now();
+    let exchange = async {
+        let (mut socket, _) = tokio_tungstenite::connect_async(endpoint)
+            .await
+            .map_err(|e| format!("websocket handshake failed: {}", e))?;
+        let mut request_bytes = 0u64;
+        let mut response_bytes = 0u64;
+
+        if let Some(payload) = &rules.send {
+            request_bytes += payload.len() as u64;
+            socket
+                .send(Message::text(payload.as_str()))
+                .await
+                .map_err(|e| format!("websocket send failed: {}", e))?;
+        }
+
+        if rules.expects_reply() {
+            loop {
+                let message = socket
+                    .next()
+                    .await
+                    .ok_or_else(|| "websocket closed before a matching reply".to_string())?
+                    .map_err(|e| format!("websocket read failed: {}", e))?;
+                response_bytes += message.len() as u64;
+                if let Message::Text(text) = &message {
+                    if rules.matches(text.as_str()) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let _ = socket.close(None).await;
+        Ok::<_, String>((request_bytes, response_bytes))
+    };
+
+    match tokio::time::timeout(timeout, exchange).await {
+        Ok(Ok((request_bytes, response_bytes))) => CheckResult {
+            success: true,
+            response_time: start.elapsed().as_secs_f64(),
+            request_bytes,
+            response_bytes,
+            failure_reason: None,
+            retried: false,
+            family_timings: None,
+            range_supported: None,
+            clock_skew: None,
+            cluster_health: None,
+            version: None,
+            status_code: None,
+            draining: false,
+            cert_expiry: None,
+            captured_headers: BTreeMap::new(),
+            outcome: Outcome::Ok2xx,
+        },
+        Ok(Err(reason)) => CheckResult::failed(reason, 0),
+        Err(_) => CheckResult {
+            outcome: Outcome::Timeout,
+            ..CheckResult::failed("websocket reply timed out".into(), 0)
+        },
+    }
+}
+
+pub fn is_tcp(endpoint: &str) -> bool {
+    endpoint.starts_with("tcp://")
+}
+
+/// Checks a `tcp://host:port` endpoint by opening a connection and closing it
+/// again, for services that don't speak HTTP. Nothing is sent.
+pub async fn check_tcp(endpoint: &str, timeout: std::time::Duration) -> CheckResult {
+    let url = match reqwest::Url::parse(endpoint) {
+        Ok(url) => url,
+        Err(e) => return CheckResult::failed(format!("invalid TCP endpoint: {}", e), 0),
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port()) else {
+        return CheckResult::failed("TCP endpoint needs a host and a port".into(), 0);
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let start = std::time::Instant::now();
+    let connect = async {
+        let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| {
+                (
+                    Outcome::DnsError,
+                    format!("TCP lookup of {} failed: {}", host, e),
+                )
+            })?
+            .collect();
+        tokio::net::TcpStream::connect(&addrs[..])
+            .await
+            .map_err(|e| {
+                (
+                    Outcome::Other,
+                    format!("TCP connect to {}:{} failed: {}", host, port, e),
+                )
+            })
+    };
+    match tokio::time::timeout(timeout, connect).await {
+        Ok(Ok(_)) => CheckResult {
+            success: true,
+            response_time: start.elapsed().as_secs_f64(),
+            request_bytes: 0,
+            response_bytes: 0,
+            failure_reason: None,
+            retried: false,
+            family_timings: None,
+            range_supported: None,
+            clock_skew: None,
+            cluster_health: None,
+            version: None,
+            status_code: None,
+            draining: false,
+            cert_expiry: None,
+            captured_headers: BTreeMap::new(),
+            outcome: Outcome::Ok2xx,
+        },
+        Ok(Err((outcome, reason))) => CheckResult {
+            outcome,
+            ..CheckResult::failed(reason, 0)
+        },
+        Err(_) => CheckResult {
+            outcome: Outcome::Timeout,
+            ..CheckResult::failed(format!("TCP connect to {}:{} timed out", host, port), 0)
+        },
+    }
+}
+
+pub fn is_mqtt(endpoint: &str) -> bool {
+    endpoint.starts_with("mqtt://")
+}
+
+/// Connects to an MQTT broker given as `mqtt://host[:port][?client_id=ID&subscribe=TOPIC]`
+/// and, when a topic is given, waits for the first message on it before
+/// disconnecting.
+pub async fn check_mqtt(endpoint: &str, timeout: std::time::Duration) -> CheckResult {
+    use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+
+    let url = match reqwest::Url::parse(endpoint) {
+        Ok(url) => url,
+        Err(e) => return CheckResult::failed(format!("invalid MQTT endpoint: {}", e), 0),
+    };
+    let Some(host) = url.host_str() else {
+        return CheckResult::failed("MQTT endpoint has no host".into(), 0);
+    };
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    let client_id = query("client_id").unwrap_or_else(|| "uptime-monitor".to_string());
+    let topic = query("subscribe");
+
+    let mut options = MqttOptions::new(client_id, host, url.port().unwrap_or(1883));
+    options.set_keep_alive(timeout.max(std::time::Duration::from_secs(5)));
+    let (client, mut event_loop) = AsyncClient::new(options, 10);
+
+    let start = std::time::Instant::now();
+    let exchange = async {
+        let mut connect_time = None;
+        loop {
+            let event = event_loop
+                .poll()
+                .await
+                .map_err(|e| format!("MQTT connection failed: {}", e))?;
+            match event {
+                Event::Incoming(Packet::ConnAck(_)) => {
+                    connect_time = Some(start.elapsed().as_secs_f64());
+                    match &topic {
+                        Some(topic) => client
+                            .subscribe(topic.as_str(), QoS::AtMostOnce)
+                            .await
+                            .map_err(|e| format!("MQTT subscribe failed: {}", e))?,
+                        None => break,
+                    }
+                }
+                Event::Incoming(Packet::Publish(_)) if connect_time.is_some() => break,
+                _ => {}
+            }
+        }
+        Ok::<_, String>(connect_time.unwrap_or_default())
+    };
+
+    let result = match tokio::time::timeout(timeout, exchange).await {
+        Ok(Ok(connect_time)) => {
+            let elapsed = start.elapsed().as_secs_f64();
+            match &topic {
+                Some(topic) => info!(
+                    "MQTT {} connected in {:.3}s, first message on {} after {:.3}s",
+                    endpoint, connect_time, topic, elapsed
+                ),
+                None => info!("MQTT {} connected in {:.3}s", endpoint, connect_time),
+            }
+            CheckResult {
+                success: true,
+                response_time: elapsed,
+                request_bytes: 0,
+                response_bytes: 0,
+                failure_reason: None,
+                retried: false,
+                family_timings: None,
+                range_supported: None,
+                clock_skew: None,
+                cluster_health: None,
+                version: None,
+                status_code: None,
+                draining: false,
+                cert_expiry: None,
+                captured_headers: BTreeMap::new(),
+                outcome: Outcome::Ok2xx,
+            }
+        }
+        Ok(Err(reason)) => CheckResult::failed(reason, 0),
+        Err(_) => CheckResult {
+            outcome: Outcome::Timeout,
+            ..CheckResult::failed(
+                match topic {
+                    Some(topic) => format!("no MQTT message on {} within the timeout", topic),
+                    None => "MQTT connection timed out".into(),
+                },
+                0,
+            )
+        },
+    };
+
+    // Best effort: flush the DISCONNECT so the broker doesn't log an unclean drop
+    if client.try_disconnect().is_ok() {
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(1), event_loop.poll()).await;
+    }
+    result
+}
+
+/// Resolves the endpoint's host, returning it on failure along with the reason.
+pub async fn resolve_endpoint(endpoint: &str) -> Result<(), (String, String)> {
+    let url = reqwest::Url::parse(endpoint)
+        .map_err(|e| (endpoint.to_string(), format!("invalid URL: {}", e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| (endpoint.to_string(), "no host".to_string()))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    // The port only matters for the lookup API, not for resolution
+    let port = url.port_or_known_default().unwrap_or(0);
+    let resolved = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map(|mut addrs| addrs.next().is_some());
+    match resolved {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((host, "no addresses".to_string())),
+        Err(e) => Err((host, e.to_string())),
+    }
+}
+
+/// TCP connect time in seconds to the first IPv4 and first IPv6 address of a
+/// host; `None` when the family has no address or the connection failed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FamilyTimings {
+    pub ipv4: Option<f64>,
+    pub ipv6: Option<f64>,
+}
+
+impl std::fmt::Display for FamilyTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show =
+            |time: Option<f64>| time.map_or("-".to_string(), |t| format!("{:.0}ms", t * 1000.0));
+        write!(f, "IPv4 {} / IPv6 {}", show(self.ipv4), show(self.ipv6))
+    }
+}
+
+async fn connect_time(
+    addr: Option<std::net::SocketAddr>,
+    timeout: std::time::Duration,
+) -> Option<f64> {
+    let addr = addr?;
+    let start = std::time::Instant::now();
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64()),
+        _ => None,
+    }
+}
+
+/// Connects to the endpoint's host over IPv4 and IPv6 in parallel to expose
+/// how much a broken family would cost. The HTTP client itself already races
+/// both families (RFC 6555), so this is measurement only.
+pub async fn measure_family_connect(
+    endpoint: &str,
+    timeout: std::time::Duration,
+) -> Option<FamilyTimings> {
+    let url = reqwest::Url::parse(endpoint).ok()?;
+    let host = url
+        .host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default()?;
+    let addrs: Vec<std::net::SocketAddr> =
+        tokio::net::lookup_host((host, port)).await.ok()?.collect();
+
+    let ipv4 = addrs.iter().find(|addr| addr.is_ipv4()).copied();
+    let ipv6 = addrs.iter().find(|addr| addr.is_ipv6()).copied();
+    let (ipv4, ipv6) = tokio::join!(connect_time(ipv4, timeout), connect_time(ipv6, timeout));
+    Some(FamilyTimings { ipv4, ipv6 })
+}
+
+/// When the leaf certificate an HTTPS endpoint presents expires. The
+/// handshake accepts any certificate, so expired or otherwise invalid ones
+/// can still be read; validating them is left to the HTTP check.
+pub async fn leaf_cert_expiry(
+    endpoint: &str,
+    timeout: std::time::Duration,
+) -> Result<DateTime<Utc>, String> {
+    let url = reqwest::Url::parse(endpoint).map_err(|e| format!("invalid URL: {}", e))?;
+    let host = url
+        .host_str()
+        .ok_or("no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+    let connector = tokio_native_tls::native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let handshake = async {
+        let stream = tokio::net::TcpStream::connect((host, port))
+            .await
+            .map_err(|e| format!("connect failed: {}", e))?;
+        tokio_native_tls::TlsConnector::from(connector)
+            .connect(host, stream)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {}", e))
+    };
+    let tls = tokio::time::timeout(timeout, handshake)
+        .await
+        .map_err(|_| "timed out".to_string())??;
+    let der = tls
+        .get_ref()
+        .peer_certificate()
+        .map_err(|e| e.to_string())?
+        .ok_or("no certificate presented")?
+        .to_der()
+        .map_err(|e| e.to_string())?;
+    let cert = openssl::x509::X509::from_der(&der).map_err(|e| e.to_string())?;
+    // ASN.1 times only compare and subtract, so measure from the epoch
+    let since_epoch = openssl::asn1::Asn1Time::from_unix(0)
+        .and_then(|epoch| epoch.diff(cert.not_after()))
+        .map_err(|e| e.to_string())?;
+    DateTime::from_timestamp(
+        i64::from(since_epoch.days) * 86400 + i64::from(since_epoch.secs),
+        0,
+    )
+    .ok_or_else(|| "expiry out of range".to_string())
+}
+
+/// Whether this host can open a TCP connection to any of the reference
+/// `host:port` probes. An empty probe list always counts as connected.
+pub async fn has_connectivity(probes: &[String], timeout: std::time::Duration) -> bool {
+    if probes.is_empty() {
+        return true;
+    }
+    let attempts = probes.iter().map(|probe| async move {
+        matches!(
+            tokio::time::timeout(timeout, tokio::net::TcpStream::connect(probe.as_str())).await,
+            Ok(Ok(_))
+        )
+    });
+    futures_util::future::join_all(attempts)
+        .await
+        .into_iter()
+        .any(|ok| ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(
+        status: u16,
+        body: Option<&str>,
+        rules: &ResponseRules,
+        must_contain: &[&str],
+    ) -> Result<(), String> {
+        let must_contain: Vec<String> = must_contain.iter().map(|k| k.to_string()).collect();
+        evaluate_response(
+            StatusCode::from_u16(status).unwrap(),
+            &HeaderMap::new(),
+            body.map(str::as_bytes),
+            rules,
+            &StatusSet::default(),
+            &must_contain,
+        )
+    }
+
+    #[test]
+    fn must_contain_requires_every_keyword() {
+        let rules = ResponseRules::default();
+        let keywords = ["\"status\":\"ok\"", "db"];
+        assert_eq!(
+            evaluate(200, Some(r#"{"status":"ok","db":true}"#), &rules, &keywords),
+            Ok(())
+        );
+        assert_eq!(
+            evaluate(200, Some(r#"{"status":"ok"}"#), &rules, &keywords),
+            Err("status 200 but keyword 'db' missing".to_string())
+        );
+        // The first missing keyword is the one reported
+        assert_eq!(
+            evaluate(200, Some("<h1>Maintenance</h1>"), &rules, &keywords),
+            Err("status 200 but keyword '\"status\":\"ok\"' missing".to_string())
+        );
+    }
+
+    #[test]
+    fn must_contain_fails_an_empty_or_missing_body() {
+        let rules = ResponseRules::default();
+        assert_eq!(
+            evaluate(204, Some(""), &rules, &["ok"]),
+            Err("status 204 but keyword 'ok' missing".to_string())
+        );
+        assert_eq!(
+            evaluate(200, None, &rules, &["ok"]),
+            Err("status 200 but keyword 'ok' missing".to_string())
+        );
+        assert_eq!(evaluate(200, None, &rules, &[]), Ok(()));
+    }
+
+    #[test]
+    fn status_and_intermediary_rules_come_before_keywords() {
+        let rules = ResponseRules {
+            reject_body_contains: vec!["Attention Required".to_string()],
+            ..ResponseRules::default()
+        };
+        assert_eq!(
+            evaluate(503, Some("down"), &rules, &["ok"]),
+            Err("HTTP 503 Service Unavailable".to_string())
+        );
+        assert_eq!(
+            evaluate(200, Some("Attention Required"), &rules, &["ok"]),
+            Err("intermediary error page: body contains 'Attention Required'".to_string())
+        );
+    }
+
+    #[test]
+    fn keywords_need_the_body() {
+        assert!(!ResponseRules::default().needs_body());
+        let rules = ResponseRules {
+            must_contain: vec!["ok".to_string()],
+            ..ResponseRules::default()
+        };
+        assert!(rules.needs_body());
+    }
+
+    fn must_match(patterns: &[&str]) -> ResponseRules {
+        ResponseRules {
+            must_match: patterns.iter().map(|p| Regex::new(p).unwrap()).collect(),
+            ..ResponseRules::default()
+        }
+    }
+
+    #[test]
+    fn body_must_match_every_pattern() {
+        let rules = must_match(&[r#""status":\s*"(ok|degraded)""#, r#""version":\s*"\d+\."#]);
+        assert!(rules.needs_body());
+        assert_eq!(
+            evaluate(
+                200,
+                Some(r#"{"status": "ok", "version": "2.4.1"}"#),
+                &rules,
+                &[]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            evaluate(
+                200,
+                Some(r#"{"status": "down", "version": "2.4.1"}"#),
+                &rules,
+                &[]
+            ),
+            Err(r#"status 200 but body doesn't match /"status":\s*"(ok|degraded)"/"#.to_string())
+        );
+        assert_eq!(
+            evaluate(200, Some(r#"{"status": "ok"}"#), &rules, &[]),
+            Err(r#"status 200 but body doesn't match /"version":\s*"\d+\./"#.to_string())
+        );
+        assert_eq!(
+            evaluate(200, None, &rules, &[]),
+            Err(r#"status 200 but body doesn't match /"status":\s*"(ok|degraded)"/"#.to_string())
+        );
+    }
+
+    #[test]
+    fn keywords_are_checked_before_patterns() {
+        let rules = must_match(&["^OK$"]);
+        assert_eq!(
+            evaluate(200, Some("error"), &rules, &["OK"]),
+            Err("status 200 but keyword 'OK' missing".to_string())
+        );
+        assert_eq!(
+            evaluate(200, Some("OK then"), &rules, &["OK"]),
+            Err("status 200 but body doesn't match /^OK$/".to_string())
+        );
+        assert_eq!(evaluate(200, Some("OK"), &rules, &["OK"]), Ok(()));
+    }
+}
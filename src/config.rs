@@ -0,0 +1,446 @@
+use crate::{
+    check::{self, StatusSet},
+    hours::AfterHours,
+};
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Method,
+};
+use serde::{Deserialize, Deserializer};
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+/// Contents of a `--config` file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "endpoint")]
+    pub endpoints: Vec<EndpointConfig>,
+}
+
+/// One `[[endpoint]]` section; anything left out falls back to the command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+    pub url: String,
+    /// Label shown alongside the URL in logs and notifications
+    #[serde(default)]
+    pub name: Option<String>,
+    /// HTTP method of the check, `GET` unless set
+    #[serde(default, deserialize_with = "deserialize_method")]
+    pub method: Option<Method>,
+    /// Overrides `--interval`
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub interval: Option<Duration>,
+    /// Overrides `--timeout`
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Statuses that count as up instead of `--expect-status`, e.g. `[200, 204]`,
+    /// `401` or `"200,301-399"`
+    #[serde(default, deserialize_with = "deserialize_statuses")]
+    pub expected_status: StatusSet,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Requests sent and discarded before the measured check, to prime caches
+    #[serde(default)]
+    pub warmup: u32,
+    /// Only warm up when this response header changes, i.e. after a restart
+    /// or deploy, instead of before every check
+    #[serde(default)]
+    pub warmup_version_header: Option<String>,
+    /// What the endpoint's metrics and check history are stored under
+    #[serde(default)]
+    pub identity: Identity,
+    /// Response header set by the load balancer or app while the instance is
+    /// draining; failed checks whose response carries it count as up
+    #[serde(default)]
+    pub drain_header: Option<String>,
+    /// Text the body must contain for the check to count as up, instead of
+    /// `--must-contain`
+    #[serde(default)]
+    pub must_contain: Vec<String>,
+    /// Whether alerts page or wait for the digest outside `--business-hours`,
+    /// instead of deciding by priority
+    #[serde(default)]
+    pub after_hours: Option<AfterHours>,
+    /// Response headers recorded with every check, e.g. `["x-cache", "cf-ray"]`
+    #[serde(default)]
+    pub capture_headers: Vec<String>,
+}
+
+/// How an endpoint is identified in the metrics and history files, so its
+/// numbers carry over when its URL is edited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Identity {
+    /// The full URL
+    #[default]
+    Url,
+    /// The URL without its query string and fragment, for cache-busting or
+    /// version parameters that change between edits
+    UrlWithoutQuery,
+    /// The endpoint's `name`
+    Name,
+}
+
+impl EndpointConfig {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            name: None,
+            method: None,
+            interval: None,
+            timeout: None,
+            expected_status: StatusSet::default(),
+            headers: BTreeMap::new(),
+            warmup: 0,
+            warmup_version_header: None,
+            identity: Identity::Url,
+            drain_header: None,
+            must_contain: Vec::new(),
+            after_hours: None,
+            capture_headers: Vec::new(),
+        }
+    }
+
+    /// Key of the endpoint's stored metrics and history under its `identity`.
+    pub fn key(&self) -> String {
+        match self.identity {
+            Identity::Url => self.url.clone(),
+            Identity::UrlWithoutQuery => match self.url.find(['?', '#']) {
+                Some(end) => self.url[..end].to_string(),
+                None => self.url.clone(),
+            },
+            Identity::Name => self.name.clone().unwrap_or_else(|| self.url.clone()),
+        }
+    }
+
+    /// Each setting as shown by `config-diff`, `default` for ones left out.
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        let or_default = |value: Option<String>| value.unwrap_or_else(|| "default".to_string());
+        let duration = |value: Option<Duration>| {
+            or_default(value.map(|d| humantime::format_duration(d).to_string()))
+        };
+        vec![
+            ("name", or_default(self.name.clone())),
+            (
+                "method",
+                or_default(self.method.as_ref().map(Method::to_string)),
+            ),
+            ("interval", duration(self.interval)),
+            ("timeout", duration(self.timeout)),
+            (
+                "expected_status",
+                or_default(
+                    (!self.expected_status.is_empty()).then(|| self.expected_status.to_string()),
+                ),
+            ),
+            ("headers", format!("{:?}", self.headers)),
+            ("warmup", self.warmup.to_string()),
+            (
+                "warmup_version_header",
+                or_default(self.warmup_version_header.clone()),
+            ),
+            ("identity", format!("{:?}", self.identity)),
+            ("drain_header", or_default(self.drain_header.clone())),
+            ("must_contain", format!("{:?}", self.must_contain)),
+            (
+                "after_hours",
+                or_default(self.after_hours.map(|a| format!("{:?}", a).to_lowercase())),
+            ),
+            ("capture_headers", format!("{:?}", self.capture_headers)),
+        ]
+    }
+
+    /// Headers as sent on each request, checked when the config is loaded.
+    pub fn header_pairs(&self) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+        self.headers
+            .iter()
+            .map(|(name, value)| {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| format!("invalid header name '{}': {}", name, e))?;
+                let value = HeaderValue::from_str(value)
+                    .map_err(|e| format!("invalid value for header {}: {}", name, e))?;
+                Ok((name, value))
+            })
+            .collect()
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let mut config: Config =
+            toml::from_str(&text).map_err(|e| format!("invalid {}: {}", path.display(), e))?;
+        for endpoint in &mut config.endpoints {
+            if endpoint.interval.is_some_and(|interval| interval.is_zero()) {
+                return Err(format!(
+                    "{}: interval must be greater than zero",
+                    endpoint.url
+                ));
+            }
+            endpoint
+                .header_pairs()
+                .map_err(|e| format!("{}: {}", endpoint.url, e))?;
+            if let Some(header) = &endpoint.warmup_version_header {
+                if endpoint.warmup == 0 {
+                    return Err(format!(
+                        "{}: warmup_version_header needs warmup to be at least 1",
+                        endpoint.url
+                    ));
+                }
+                HeaderName::from_bytes(header.as_bytes()).map_err(|e| {
+                    format!(
+                        "{}: invalid warmup_version_header '{}': {}",
+                        endpoint.url, header, e
+                    )
+                })?;
+            }
+            if let Some(header) = &endpoint.drain_header {
+                HeaderName::from_bytes(header.as_bytes()).map_err(|e| {
+                    format!("{}: invalid drain_header '{}': {}", endpoint.url, header, e)
+                })?;
+            }
+            for header in &mut endpoint.capture_headers {
+                HeaderName::from_bytes(header.as_bytes()).map_err(|e| {
+                    format!(
+                        "{}: invalid capture_headers entry '{}': {}",
+                        endpoint.url, header, e
+                    )
+                })?;
+                *header = header.to_lowercase();
+            }
+            if endpoint.identity == Identity::Name && endpoint.name.is_none() {
+                return Err(format!(
+                    "{}: identity = \"name\" needs a name",
+                    endpoint.url
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Rejects endpoints that would share stored metrics, e.g. after switching
+    /// to `url_without_query` with two endpoints differing only in their query.
+    pub fn check_identities(&self) -> Result<(), String> {
+        let mut keys: BTreeMap<String, &str> = BTreeMap::new();
+        for endpoint in &self.endpoints {
+            if let Some(other) = keys.insert(endpoint.key(), &endpoint.url) {
+                return Err(format!(
+                    "{} and {} would both store their metrics as '{}'; give one of them \
+                     identity = \"url\" or a distinct name, or monitor only one of them",
+                    other,
+                    endpoint.url,
+                    endpoint.key()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn endpoint(&self, url: &str) -> Option<&EndpointConfig> {
+        self.endpoints.iter().find(|endpoint| endpoint.url == url)
+    }
+
+    /// Endpoint by endpoint differences from `old` to `self`, one line each:
+    /// added, then changed with the settings that differ, then removed.
+    pub fn diff(&self, old: &Config) -> Vec<String> {
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for endpoint in &self.endpoints {
+            let Some(previous) = old.endpoint(&endpoint.url) else {
+                added.push(format!("+ added: {}", endpoint.url));
+                continue;
+            };
+            let changes: Vec<String> = previous
+                .settings()
+                .into_iter()
+                .zip(endpoint.settings())
+                .filter(|((_, before), (_, after))| before != after)
+                .map(|((setting, before), (_, after))| {
+                    format!("{}: {} → {}", setting, before, after)
+                })
+                .collect();
+            if !changes.is_empty() {
+                changed.push(format!(
+                    "~ changed: {} ({})",
+                    endpoint.url,
+                    changes.join(", ")
+                ));
+            }
+        }
+        let removed = old
+            .endpoints
+            .iter()
+            .filter(|endpoint| self.endpoint(&endpoint.url).is_none())
+            .map(|endpoint| format!("- removed: {}", endpoint.url));
+        added.into_iter().chain(changed).chain(removed).collect()
+    }
+
+    /// Adds an endpoint given on the command line. One already in the file
+    /// keeps its settings, except for an interval given with `URL=DURATION`.
+    pub fn add_endpoint(&mut self, url: String, interval: Option<Duration>) {
+        match self.endpoints.iter_mut().find(|e| e.url == url) {
+            Some(existing) => existing.interval = interval.or(existing.interval),
+            None => self.endpoints.push(EndpointConfig {
+                interval,
+                ..EndpointConfig::new(url)
+            }),
+        }
+    }
+}
+
+/// Durations are written like on the command line: bare seconds or a string
+/// with units, e.g. `interval = 30` or `interval = "1m30s"`.
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(f64),
+        Text(String),
+    }
+    let text = match Raw::deserialize(deserializer)? {
+        Raw::Seconds(secs) => secs.to_string(),
+        Raw::Text(text) => text,
+    };
+    crate::parse_duration(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn deserialize_method<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Method>, D::Error> {
+    let method = String::deserialize(deserializer)?;
+    Method::from_bytes(method.to_uppercase().as_bytes())
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid HTTP method '{}'", method)))
+}
+
+fn deserialize_statuses<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StatusSet, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        One(u16),
+        Many(Vec<u16>),
+        Text(String),
+    }
+    let text = match Raw::deserialize(deserializer)? {
+        Raw::One(status) => status.to_string(),
+        Raw::Many(statuses) => statuses
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        Raw::Text(text) => text,
+    };
+    check::parse_status_set(&text).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn diff_of_equal_configs_is_empty() {
+        let text = r#"
+            [[endpoint]]
+            url = "https://a.example"
+            interval = "30s"
+            expected_status = [200, 204]
+        "#;
+        assert!(config(text).diff(&config(text)).is_empty());
+        // The same settings written differently are no change
+        let seconds = config(
+            r#"
+            [[endpoint]]
+            url = "https://a.example"
+            interval = 30
+            expected_status = "200,204"
+        "#,
+        );
+        assert!(seconds.diff(&config(text)).is_empty());
+    }
+
+    #[test]
+    fn diff_lists_added_then_changed_then_removed() {
+        let old = config(
+            r#"
+            [[endpoint]]
+            url = "https://gone.example"
+
+            [[endpoint]]
+            url = "https://kept.example"
+            timeout = "5s"
+            must_contain = ["ok"]
+
+            [[endpoint]]
+            url = "https://same.example"
+        "#,
+        );
+        let new = config(
+            r#"
+            [[endpoint]]
+            url = "https://same.example"
+
+            [[endpoint]]
+            url = "https://kept.example"
+            name = "Kept"
+            timeout = "10s"
+            must_contain = ["ok"]
+            after_hours = "defer"
+
+            [[endpoint]]
+            url = "https://new.example"
+        "#,
+        );
+
+        assert_eq!(
+            new.diff(&old),
+            [
+                "+ added: https://new.example",
+                "~ changed: https://kept.example (name: default → Kept, timeout: 5s → 10s, \
+                 after_hours: default → defer)",
+                "- removed: https://gone.example",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_shows_settings_reverting_to_their_default() {
+        let old = config(
+            r#"
+            [[endpoint]]
+            url = "https://a.example"
+            method = "head"
+            expected_status = "200-299,304"
+            headers = { "x-probe" = "1" }
+            warmup = 2
+            warmup_version_header = "x-version"
+            identity = "url_without_query"
+        "#,
+        );
+        let new = config(
+            r#"
+            [[endpoint]]
+            url = "https://a.example"
+        "#,
+        );
+
+        assert_eq!(
+            new.diff(&old),
+            [
+                "~ changed: https://a.example (method: HEAD → default, expected_status: \
+                 200-299,304 → default, headers: {\"x-probe\": \"1\"} → {}, warmup: 2 → 0, \
+                 warmup_version_header: x-version → default, identity: UrlWithoutQuery → Url)"
+            ]
+        );
+    }
+}
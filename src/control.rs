@@ -0,0 +1,598 @@
+use crate::{
+    history::{CheckHistory, HISTORY_PATH},
+    incident::{self, Incident, Reclassification, INCIDENTS_PATH},
+    monitor::LEGACY_METRICS_PATH,
+    recent::RECENT_CHECKS_PATH,
+    snapshot, store,
+    trend::TREND_PATH,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+/// Endpoints added at runtime with `ctl watch`. The running monitor picks this
+/// file up every round, so it doubles as persistence across restarts.
+pub const TEMPORARY_ENDPOINTS_PATH: &str = "metrics/temporary_endpoints.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemporaryEndpoint {
+    pub url: String,
+    pub name: Option<String>,
+    pub added_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TemporaryEndpoint {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+pub fn load_temporary_endpoints(path: &Path) -> io::Result<Vec<TemporaryEndpoint>> {
+    match fs::read_to_string(path) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_temporary_endpoints(path: &Path, endpoints: &[TemporaryEndpoint]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(endpoints)?;
+    crate::monitor::write_atomic(path, &json)
+}
+
+/// Adds a temporary endpoint, or extends it if it is already being watched.
+pub fn watch(
+    path: &Path,
+    url: &str,
+    duration: Duration,
+    name: Option<String>,
+) -> io::Result<TemporaryEndpoint> {
+    let now = Utc::now();
+    let mut endpoints = load_temporary_endpoints(path)?;
+    endpoints.retain(|e| !e.is_expired(now) && e.url != url);
+
+    let endpoint = TemporaryEndpoint {
+        url: url.to_string(),
+        name,
+        added_at: now,
+        expires_at: now
+            + chrono::Duration::from_std(duration)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+    };
+    endpoints.push(endpoint.clone());
+    save_temporary_endpoints(path, &endpoints)?;
+    Ok(endpoint)
+}
+
+/// Ad-hoc maintenance windows opened by a deploy pipeline through
+/// `ctl deploy-start` or `POST /api/deploy-window`. Like temporary endpoints,
+/// the running monitor re-reads this file every round.
+pub const DEPLOY_WINDOWS_PATH: &str = "metrics/deploy_windows.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployWindow {
+    pub id: String,
+    pub endpoint: String,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl DeployWindow {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+pub fn load_deploy_windows(path: &Path) -> io::Result<Vec<DeployWindow>> {
+    match fs::read_to_string(path) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_deploy_windows(path: &Path, windows: &[DeployWindow]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(windows)?;
+    crate::monitor::write_atomic(path, &json)
+}
+
+/// Holds an exclusive lock next to the deploy windows file while it is read
+/// and rewritten, so a `ctl` invocation and the HTTP server (or two concurrent
+/// requests) can't each save over the other's change. Released on drop.
+fn lock_deploy_windows(path: &Path) -> io::Result<fs::File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Opens a deploy window for `endpoint` lasting at most `max_duration`. Every
+/// call gets a window of its own, so when two pipelines deploy the same
+/// endpoint at once, alerts stay suppressed until both have ended theirs.
+pub fn start_deploy(
+    path: &Path,
+    endpoint: &str,
+    max_duration: Duration,
+) -> io::Result<DeployWindow> {
+    let now = Utc::now();
+    let expires_at = now
+        + chrono::Duration::from_std(max_duration)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let _lock = lock_deploy_windows(path)?;
+    let mut windows = load_deploy_windows(path)?;
+    windows.retain(|w| !w.is_expired(now));
+
+    // IDs are the opening time, bumped past any window opened in the same millisecond
+    let mut millis = now.timestamp_millis();
+    while windows.iter().any(|w| w.id == format!("deploy-{}", millis)) {
+        millis += 1;
+    }
+    let window = DeployWindow {
+        id: format!("deploy-{}", millis),
+        endpoint: endpoint.to_string(),
+        started_at: now,
+        expires_at,
+    };
+    windows.push(window.clone());
+    save_deploy_windows(path, &windows)?;
+    Ok(window)
+}
+
+/// Closes a deploy window early, returning it if it was still open. Other
+/// windows open for the same endpoint keep its alerts suppressed.
+pub fn end_deploy(path: &Path, id: &str) -> io::Result<Option<DeployWindow>> {
+    let now = Utc::now();
+    let _lock = lock_deploy_windows(path)?;
+    let mut windows = load_deploy_windows(path)?;
+    windows.retain(|w| !w.is_expired(now));
+    let ended = windows
+        .iter()
+        .position(|w| w.id == id)
+        .map(|index| windows.remove(index));
+    save_deploy_windows(path, &windows)?;
+    Ok(ended)
+}
+
+/// Moves an endpoint's stored metrics and checks in the database at `db`, and
+/// its check history, recent checks and trend, from `old` to `new`, e.g. after
+/// editing its URL or `identity`. Returns the database and files that had it.
+/// Nothing is changed when `new` already has data in any of them, so two
+/// endpoints are never merged. The monitor should be stopped meanwhile, or its
+/// next save puts the old key back.
+pub fn rename_endpoint(db: &Path, old: &str, new: &str) -> io::Result<Vec<String>> {
+    let already_stored = |place: &dyn std::fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already has data for {}; rename that endpoint out of the way first",
+                place, new
+            ),
+        )
+    };
+    if store::with_database(db, async |pool| store::has_endpoint(pool, new).await)? == Some(true) {
+        return Err(already_stored(&db.display()));
+    }
+
+    let mut renamed = Vec::new();
+    // The metrics file of an earlier version, if the database hasn't imported it yet
+    for path in [
+        LEGACY_METRICS_PATH,
+        HISTORY_PATH,
+        RECENT_CHECKS_PATH,
+        TREND_PATH,
+    ] {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut document: Value = serde_json::from_str(&json)?;
+        if path == LEGACY_METRICS_PATH {
+            document = snapshot::migrate(document)?;
+        }
+        let endpoints = match document.get_mut("endpoints") {
+            Some(endpoints) if path == LEGACY_METRICS_PATH => endpoints,
+            _ => &mut document,
+        };
+        let Some(endpoints) = endpoints.as_object_mut() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not keyed by endpoint", path),
+            ));
+        };
+        if endpoints.contains_key(new) {
+            return Err(already_stored(&path));
+        }
+        if let Some(data) = endpoints.remove(old) {
+            endpoints.insert(new.to_string(), data);
+            renamed.push((path, document));
+        }
+    }
+    let in_database = store::with_database(db, async |pool| {
+        store::rename_endpoint(pool, old, new).await
+    })? == Some(true);
+    if renamed.is_empty() && !in_database {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no stored data for {}", old),
+        ));
+    }
+
+    for (path, document) in &renamed {
+        let json = if *path == LEGACY_METRICS_PATH {
+            serde_json::to_string_pretty(document)?
+        } else {
+            serde_json::to_string(document)?
+        };
+        crate::monitor::write_atomic(Path::new(path), &json)?;
+    }
+    Ok(in_database
+        .then(|| db.display().to_string())
+        .into_iter()
+        .chain(renamed.into_iter().map(|(path, _)| path.to_string()))
+        .collect())
+}
+
+/// What `ctl reclassify` changed.
+pub struct ReclassifyReport {
+    /// IDs of the incidents annotated
+    pub incidents: Vec<String>,
+    /// Downtime moved out of `total_downtime`
+    pub downtime: Duration,
+    /// Failed checks now counted as maintenance
+    pub checks: u64,
+    /// Oldest check in the database when it is newer than the start of the
+    /// reclassified downtime, so earlier failures couldn't be excused check by
+    /// check
+    pub checks_since: Option<DateTime<Utc>>,
+}
+
+/// Re-tags an endpoint's downtime between `from` and `to` as `kind`, e.g.
+/// maintenance nobody opened a window for. Overlapping incidents are
+/// annotated rather than changed, and the failed checks in the period stop
+/// counting against uptime. Like renaming, this rewrites metrics the monitor
+/// keeps in memory, so it must be stopped.
+pub fn reclassify(
+    db: &Path,
+    endpoint: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    kind: &str,
+    reason: &str,
+    by: &str,
+) -> io::Result<ReclassifyReport> {
+    let now = Utc::now();
+    let mut incidents = incident::load(Path::new(INCIDENTS_PATH))?;
+    let request = Reclassification {
+        kind: kind.to_string(),
+        from,
+        to,
+        reason: reason.to_string(),
+        by: by.to_string(),
+        at: now,
+    };
+    let (overlapping, downtime, downtime_from) =
+        reclassify_incidents(&mut incidents, endpoint, &request)?;
+
+    let saved = store::with_database(db, async |pool| {
+        let Some(metrics) = store::load_endpoint(pool, endpoint).await? else {
+            return Ok(None);
+        };
+        let failed = store::failed_checks(pool, endpoint, from, to).await?;
+        let oldest = store::oldest_check(pool, endpoint).await?;
+        Ok(Some((metrics, failed, oldest)))
+    })?;
+    if saved.is_none() && Path::new(LEGACY_METRICS_PATH).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} has not been imported into {} yet; start the monitor once first",
+                LEGACY_METRICS_PATH,
+                db.display()
+            ),
+        ));
+    }
+    let mut history = CheckHistory::open(Path::new(HISTORY_PATH));
+    let mut checks = 0;
+    let mut checks_since = None;
+    let mut metrics = None;
+    if let Some((mut saved, failed, oldest)) = saved.flatten() {
+        // Failed checks per hour, as the check history buckets them
+        let mut failures: BTreeMap<i64, u64> = BTreeMap::new();
+        for checked_at in failed {
+            *failures
+                .entry(checked_at.timestamp().div_euclid(3600))
+                .or_default() += 1;
+        }
+        checks_since = oldest.filter(|&oldest| oldest > downtime_from);
+
+        history.excuse(endpoint, &failures);
+        let unexcused = saved.failed_checks.saturating_sub(saved.maintenance_checks);
+        checks = failures.values().sum::<u64>().min(unexcused);
+        saved.maintenance_checks += checks;
+        let seconds = downtime.as_secs().min(saved.total_downtime);
+        saved.total_downtime -= seconds;
+        saved.maintenance_downtime += seconds;
+        saved.uptime_windows = history.uptime_windows(endpoint, now);
+        metrics = Some(saved);
+    }
+
+    history.save()?;
+    crate::monitor::write_atomic(
+        Path::new(INCIDENTS_PATH),
+        &serde_json::to_string_pretty(&incidents)?,
+    )?;
+    if let Some(metrics) = &metrics {
+        store::with_database(db, async |pool| {
+            store::save_endpoint(pool, endpoint, metrics).await
+        })?;
+    }
+    Ok(ReclassifyReport {
+        incidents: overlapping
+            .into_iter()
+            .map(|index| incidents[index].id.clone())
+            .collect(),
+        downtime,
+        checks,
+        checks_since,
+    })
+}
+
+/// The part of [`reclassify`] that doesn't touch the disk: checks the period
+/// against `endpoint`'s incidents and annotates the ones it overlaps with
+/// `request`, clamped to each incident. Returns their indexes, the downtime
+/// they had in the period and when the earliest of it started.
+fn reclassify_incidents(
+    incidents: &mut [Incident],
+    endpoint: &str,
+    request: &Reclassification,
+) -> io::Result<(Vec<usize>, Duration, DateTime<Utc>)> {
+    let Reclassification {
+        from, to, at: now, ..
+    } = *request;
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if from >= to {
+        return Err(invalid(format!(
+            "--from {} is not before --to {}",
+            from, to
+        )));
+    }
+    let overlapping: Vec<usize> = incidents
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.endpoint == endpoint && !i.overlap(from, to, now).is_zero())
+        .map(|(index, _)| index)
+        .collect();
+    if overlapping.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "no recorded downtime for {} between {} and {}",
+                endpoint, from, to
+            ),
+        ));
+    }
+    for incident in overlapping.iter().map(|&index| &incidents[index]) {
+        if incident.closed_at.is_none() {
+            return Err(invalid(format!(
+                "incident {} is still open; reclassify it once {} has recovered",
+                incident.id, endpoint
+            )));
+        }
+        if let Some(earlier) = incident
+            .reclassified
+            .iter()
+            .find(|r| r.from < to && from < r.to)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "incident {} was already reclassified as {} from {} to {}",
+                    incident.id, earlier.kind, earlier.from, earlier.to
+                ),
+            ));
+        }
+    }
+
+    let mut downtime = Duration::ZERO;
+    let mut downtime_from = to;
+    for &index in &overlapping {
+        let incident = &mut incidents[index];
+        downtime_from = downtime_from.min(incident.opened_at.max(from));
+        let overlap = incident.overlap(from, to, now);
+        downtime += overlap;
+        incident.reclassified.push(Reclassification {
+            from: from.max(incident.opened_at),
+            to: incident.closed_at.map_or(to, |closed| closed.min(to)),
+            ..request.clone()
+        });
+    }
+    Ok((overlapping, downtime, downtime_from))
+}
+
+/// Human-friendly time left until `expires_at`, rounded down to the minute.
+pub fn format_remaining(expires_at: DateTime<Utc>) -> String {
+    let secs = (expires_at - Utc::now()).num_seconds().max(0) as u64;
+    if secs < 60 {
+        return format!("{}s", secs);
+    }
+    humantime::format_duration(Duration::from_secs(secs / 60 * 60)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn concurrent_starts_all_keep_their_window() {
+        let dir = tempfile::tempdir().unwrap();
+        // The directory is created on first use, as by `ctl deploy-start` in a fresh checkout
+        let path = dir.path().join("metrics").join("deploy_windows.json");
+
+        let ids: Vec<String> = thread::scope(|scope| {
+            let starts: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| start_deploy(&path, "https://a.example", HOUR).unwrap().id))
+                .collect();
+            starts.into_iter().map(|s| s.join().unwrap()).collect()
+        });
+
+        let saved = load_deploy_windows(&path).unwrap();
+        assert_eq!(saved.len(), 8);
+        for id in &ids {
+            assert_eq!(saved.iter().filter(|w| &w.id == id).count(), 1, "{}", id);
+        }
+    }
+
+    #[test]
+    fn ending_one_deploy_leaves_an_overlapping_one_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deploy_windows.json");
+
+        let first = start_deploy(&path, "https://a.example", HOUR).unwrap();
+        let second = start_deploy(&path, "https://a.example", HOUR).unwrap();
+        assert_ne!(first.id, second.id);
+
+        assert_eq!(end_deploy(&path, &first.id).unwrap().unwrap().id, first.id);
+        let open = load_deploy_windows(&path).unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].id, second.id);
+
+        assert!(end_deploy(&path, &first.id).unwrap().is_none());
+        assert!(end_deploy(&path, &second.id).unwrap().is_some());
+        assert!(load_deploy_windows(&path).unwrap().is_empty());
+    }
+
+    fn at(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    fn outage(id: &str, opened_at: &str, closed_at: Option<&str>) -> Incident {
+        Incident {
+            id: id.to_string(),
+            endpoint: "https://a.example".to_string(),
+            opened_at: at(opened_at),
+            closed_at: closed_at.map(at),
+            failed_checks: 3,
+            last_reason: None,
+            reclassified: Vec::new(),
+        }
+    }
+
+    fn maintenance(from: &str, to: &str) -> Reclassification {
+        Reclassification {
+            kind: "maintenance".to_string(),
+            from: at(from),
+            to: at(to),
+            reason: "db upgrade".to_string(),
+            by: "ops".to_string(),
+            at: at("2026-05-02T00:00:00Z"),
+        }
+    }
+
+    #[test]
+    fn reclassify_annotates_overlapping_incidents_clamped_to_each() {
+        let mut incidents = vec![
+            outage("a", "2026-05-01T10:00:00Z", Some("2026-05-01T10:30:00Z")),
+            outage("b", "2026-05-01T11:00:00Z", Some("2026-05-01T11:10:00Z")),
+            outage("c", "2026-05-01T13:00:00Z", Some("2026-05-01T13:05:00Z")),
+        ];
+        let request = maintenance("2026-05-01T10:15:00Z", "2026-05-01T12:00:00Z");
+
+        let (annotated, downtime, downtime_from) =
+            reclassify_incidents(&mut incidents, "https://a.example", &request).unwrap();
+
+        assert_eq!(annotated, [0, 1]);
+        assert_eq!(downtime, Duration::from_secs(25 * 60));
+        assert_eq!(downtime_from, at("2026-05-01T10:15:00Z"));
+        let a = &incidents[0].reclassified[0];
+        assert_eq!(
+            (a.from, a.to),
+            (at("2026-05-01T10:15:00Z"), at("2026-05-01T10:30:00Z"))
+        );
+        let b = &incidents[1].reclassified[0];
+        assert_eq!(
+            (b.from, b.to),
+            (at("2026-05-01T11:00:00Z"), at("2026-05-01T11:10:00Z"))
+        );
+        assert_eq!(b.reason, "db upgrade");
+        assert!(incidents[2].reclassified.is_empty());
+    }
+
+    #[test]
+    fn reclassify_rejects_a_period_without_downtime() {
+        let mut incidents = vec![
+            outage("a", "2026-05-01T10:00:00Z", Some("2026-05-01T10:30:00Z")),
+            Incident {
+                endpoint: "https://b.example".to_string(),
+                ..outage("b", "2026-05-01T11:00:00Z", Some("2026-05-01T11:30:00Z"))
+            },
+        ];
+        // Right after the first incident closed, and during another endpoint's
+        let request = maintenance("2026-05-01T10:30:00Z", "2026-05-01T11:30:00Z");
+
+        let error =
+            reclassify_incidents(&mut incidents, "https://a.example", &request).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            error.to_string(),
+            "no recorded downtime for https://a.example between 2026-05-01 10:30:00 UTC and \
+             2026-05-01 11:30:00 UTC"
+        );
+    }
+
+    #[test]
+    fn reclassify_rejects_an_overlap_with_an_earlier_reclassification() {
+        let mut incidents = vec![outage(
+            "a",
+            "2026-05-01T10:00:00Z",
+            Some("2026-05-01T11:00:00Z"),
+        )];
+        let first = maintenance("2026-05-01T10:00:00Z", "2026-05-01T10:20:00Z");
+        reclassify_incidents(&mut incidents, "https://a.example", &first).unwrap();
+
+        let overlapping = maintenance("2026-05-01T10:10:00Z", "2026-05-01T10:40:00Z");
+        let error =
+            reclassify_incidents(&mut incidents, "https://a.example", &overlapping).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            error.to_string(),
+            "incident a was already reclassified as maintenance from 2026-05-01 10:00:00 UTC to \
+             2026-05-01 10:20:00 UTC"
+        );
+        assert_eq!(incidents[0].reclassified.len(), 1);
+
+        // The rest of the incident, right after the first period, is still free
+        let adjacent = maintenance("2026-05-01T10:20:00Z", "2026-05-01T10:40:00Z");
+        reclassify_incidents(&mut incidents, "https://a.example", &adjacent).unwrap();
+        assert_eq!(incidents[0].reclassified.len(), 2);
+    }
+
+    #[test]
+    fn reclassify_rejects_open_incidents_and_reversed_periods() {
+        let mut incidents = vec![outage("a", "2026-05-01T10:00:00Z", None)];
+
+        let request = maintenance("2026-05-01T10:00:00Z", "2026-05-01T10:20:00Z");
+        let error =
+            reclassify_incidents(&mut incidents, "https://a.example", &request).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(error.to_string().starts_with("incident a is still open"));
+
+        let reversed = maintenance("2026-05-01T10:20:00Z", "2026-05-01T10:00:00Z");
+        let error =
+            reclassify_incidents(&mut incidents, "https://a.example", &reversed).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(incidents[0].reclassified.is_empty());
+    }
+}
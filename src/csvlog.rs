@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+use tracing::{error, info, warn};
+
+const HEADER: &str = "timestamp,endpoint,success,response_time,cumulative_success_rate";
+
+enum Op {
+    Row(String),
+    /// Answered once every row queued before it was written and flushed
+    Flush(oneshot::Sender<()>),
+}
+
+/// Append-only CSV of every check for `--csv-path`, one row per check, for
+/// analysis in a spreadsheet. Runs alongside the metrics database. Rows are
+/// appended by a task of their own, like the [`Writer`](crate::writer::Writer)'s,
+/// so the check loop never waits on the file; it writes whatever rows queued
+/// up meanwhile in one go and flushes once per batch.
+#[derive(Debug)]
+pub struct CsvLog {
+    queue: mpsc::Sender<Op>,
+    /// Rows dropped since the queue last had room
+    dropped: AtomicU64,
+}
+
+impl CsvLog {
+    /// Opens `path` for appending, writing the header row only when the file
+    /// is new or empty, and starts the task appending to it with room for
+    /// `capacity` pending rows.
+    pub fn open(path: &Path, capacity: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+        let mut file = BufWriter::new(file);
+        if empty {
+            writeln!(file, "{}", HEADER)?;
+            file.flush()?;
+        }
+        let (queue, ops) = mpsc::channel(capacity);
+        tokio::spawn(run(path.to_path_buf(), file, ops));
+        Ok(Self {
+            queue,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Queues one check; `success_rate` is the endpoint's cumulative
+    /// percentage of successful checks. Each row is queued whole, so rows
+    /// never interleave.
+    pub fn append(
+        &self,
+        at: DateTime<Utc>,
+        endpoint: &str,
+        success: bool,
+        response_time: f64,
+        success_rate: f64,
+    ) {
+        let row = format!(
+            "{},{},{},{:.3},{:.2}\n",
+            at.to_rfc3339(),
+            crate::monitor::csv_escape(endpoint),
+            success,
+            response_time,
+            success_rate
+        );
+        match self.queue.try_send(Op::Row(row)) {
+            Ok(()) => {
+                let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    info!("CSV log caught up after {} rows were dropped", dropped);
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("CSV log is falling behind; dropping rows until it catches up");
+                }
+            }
+            // The task logged why it stopped
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Waits until every row queued so far has been written, e.g. before
+    /// exiting.
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.queue.send(Op::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+}
+
+async fn run(path: PathBuf, mut file: BufWriter<File>, mut ops: mpsc::Receiver<Op>) {
+    while let Some(op) = ops.recv().await {
+        let mut rows = Vec::new();
+        let mut flushes = Vec::new();
+        let mut collect = |op| match op {
+            Op::Row(row) => rows.push(row),
+            Op::Flush(done) => flushes.push(done),
+        };
+        collect(op);
+        // Whatever queued up meanwhile is written with a single flush
+        while let Ok(op) = ops.try_recv() {
+            collect(op);
+        }
+
+        // Writing blocks, so it runs off the async workers
+        let written = tokio::task::spawn_blocking(move || {
+            let result = rows
+                .iter()
+                .try_for_each(|row| file.write_all(row.as_bytes()))
+                .and_then(|()| file.flush());
+            (file, result)
+        })
+        .await;
+        file = match written {
+            Ok((file, result)) => {
+                if let Err(e) = result {
+                    error!("Failed to append to {}: {}", path.display(), e);
+                }
+                file
+            }
+            Err(e) => {
+                error!("CSV log stopped; no more rows will be written: {}", e);
+                break;
+            }
+        };
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Why a monitor couldn't be set up.
+#[derive(Debug)]
+pub enum UptimeError {
+    /// The metrics snapshot to resume from is missing or unreadable
+    Snapshot { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for UptimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UptimeError::Snapshot { path, source } => {
+                write!(f, "failed to read snapshot {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UptimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UptimeError::Snapshot { source, .. } => Some(source),
+        }
+    }
+}
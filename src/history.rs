@@ -0,0 +1,184 @@
+use crate::writer::{WriteKind, Writer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::error;
+
+pub const HISTORY_PATH: &str = "metrics/check_history.json";
+
+/// Enough for the longest window plus the hour it starts in.
+const HISTORY_HOURS: i64 = 30 * 24 + 1;
+
+/// Windows cached in each endpoint's metrics after every check.
+pub const UPTIME_WINDOWS: [(&str, Duration); 4] = [
+    ("1h", Duration::from_secs(3600)),
+    ("24h", Duration::from_secs(24 * 3600)),
+    ("7d", Duration::from_secs(7 * 24 * 3600)),
+    ("30d", Duration::from_secs(30 * 24 * 3600)),
+];
+
+/// Check counts for one UTC hour.
+#[derive(Debug, Serialize, Deserialize)]
+struct Bucket {
+    /// Hours since the Unix epoch
+    hour: i64,
+    checks: u64,
+    successes: u64,
+    /// Failed checks reclassified as maintenance, which count as up
+    #[serde(default, skip_serializing_if = "is_zero")]
+    maintenance: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// Hourly check counts per endpoint over the last 30 days, for uptime over a
+/// sliding window rather than since the metrics were first created.
+/// Persisted so the windows survive restarts.
+pub struct CheckHistory {
+    path: PathBuf,
+    endpoints: HashMap<String, VecDeque<Bucket>>,
+    dirty: bool,
+}
+
+impl CheckHistory {
+    pub fn open(path: &Path) -> Self {
+        let endpoints = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                error!(
+                    "Ignoring unreadable check history {}: {}",
+                    path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                error!("Failed to read check history {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            endpoints,
+            dirty: false,
+        }
+    }
+
+    pub fn record(&mut self, endpoint: &str, at: DateTime<Utc>, success: bool) {
+        let hour = at.timestamp().div_euclid(3600);
+        let buckets = self.endpoints.entry(endpoint.to_string()).or_default();
+        match buckets.back_mut() {
+            Some(bucket) if bucket.hour == hour => {
+                bucket.checks += 1;
+                bucket.successes += u64::from(success);
+            }
+            _ => {
+                buckets.push_back(Bucket {
+                    hour,
+                    checks: 1,
+                    successes: u64::from(success),
+                    maintenance: 0,
+                });
+                while buckets
+                    .front()
+                    .is_some_and(|b| b.hour <= hour - HISTORY_HOURS)
+                {
+                    buckets.pop_front();
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Uptime percentage over `[now - window, now]`, or `None` without checks
+    /// in it. The hour the window starts in only partly overlaps it, so its
+    /// counts are weighted by the overlap, assuming checks spread evenly.
+    pub fn uptime_in_window(
+        &self,
+        endpoint: &str,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<f64> {
+        let end = now.timestamp() as f64;
+        let start = end - window.as_secs_f64();
+        let (checks, successes) = self
+            .endpoints
+            .get(endpoint)?
+            .iter()
+            .filter_map(|bucket| {
+                let bucket_start = (bucket.hour * 3600) as f64;
+                let bucket_end = bucket_start + 3600.0;
+                let overlap = (bucket_end.min(end) - bucket_start.max(start)) / 3600.0;
+                (overlap > 0.0).then(|| {
+                    let weight = if bucket_start >= start { 1.0 } else { overlap };
+                    (
+                        bucket.checks as f64 * weight,
+                        (bucket.successes + bucket.maintenance) as f64 * weight,
+                    )
+                })
+            })
+            .fold((0.0, 0.0), |(c, s), (checks, successes)| {
+                (c + checks, s + successes)
+            });
+        (checks > 0.0).then(|| successes / checks * 100.0)
+    }
+
+    /// Uptime over each of [`UPTIME_WINDOWS`] that has checks.
+    pub fn uptime_windows(&self, endpoint: &str, now: DateTime<Utc>) -> BTreeMap<String, f64> {
+        UPTIME_WINDOWS
+            .iter()
+            .filter_map(|(name, window)| {
+                let uptime = self.uptime_in_window(endpoint, *window, now)?;
+                Some((name.to_string(), uptime))
+            })
+            .collect()
+    }
+
+    /// Counts failed checks as maintenance, given as the number per hour since
+    /// the epoch. Returns how many were excused; an hour can't excuse more
+    /// failures than it recorded.
+    pub fn excuse(&mut self, endpoint: &str, failures: &BTreeMap<i64, u64>) -> u64 {
+        let Some(buckets) = self.endpoints.get_mut(endpoint) else {
+            return 0;
+        };
+        let mut excused = 0;
+        for bucket in buckets.iter_mut() {
+            if let Some(count) = failures.get(&bucket.hour) {
+                let failed = bucket
+                    .checks
+                    .saturating_sub(bucket.successes + bucket.maintenance);
+                let count = (*count).min(failed);
+                bucket.maintenance += count;
+                excused += count;
+            }
+        }
+        self.dirty |= excused > 0;
+        excused
+    }
+
+    /// Writes the history straight to disk, for `ctl` commands that run
+    /// while the monitor is stopped.
+    pub fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string(&self.endpoints)?;
+        crate::monitor::write_atomic(&self.path, &json)
+    }
+
+    /// Writes the history if it changed since the last save.
+    pub fn flush(&mut self, writer: &Writer) {
+        if !self.dirty {
+            return;
+        }
+        match serde_json::to_string(&self.endpoints) {
+            // Stays dirty when the write is dropped, so the next round retries it
+            Ok(json) => self.dirty = !writer.write(&self.path, json, WriteKind::Routine),
+            Err(e) => error!("Failed to save check history: {}", e),
+        }
+    }
+}
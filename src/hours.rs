@@ -0,0 +1,363 @@
+use chrono::{
+    DateTime, Datelike, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use tracing::warn;
+
+/// Whether an endpoint's alerts interrupt someone outside business hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AfterHours {
+    /// Sent immediately, as during business hours
+    Page,
+    /// Held for the after-hours digest
+    Defer,
+}
+
+/// Days and local times when alerts page immediately, e.g. `Mon-Fri 09:00-18:00`.
+#[derive(Debug, Clone)]
+pub struct BusinessHours {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    /// Before `start` for hours spanning midnight
+    end: NaiveTime,
+}
+
+/// Parses `[DAYS ]HH:MM-HH:MM`, where DAYS is a range like `Mon-Fri` or a
+/// list like `Mon,Wed,Fri`; without DAYS every day has business hours.
+pub fn parse_business_hours(value: &str) -> Result<BusinessHours, String> {
+    let (days, times) = match value.trim().rsplit_once(' ') {
+        Some((days, times)) => (parse_days(days.trim())?, times),
+        None => (ALL_DAYS.to_vec(), value.trim()),
+    };
+    let (start, end) = times
+        .split_once('-')
+        .ok_or_else(|| format!("'{}' is not a time range like 09:00-18:00", times))?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == end {
+        return Err(format!("business hours '{}' are empty", times));
+    }
+    Ok(BusinessHours { days, start, end })
+}
+
+const ALL_DAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+fn parse_days(value: &str) -> Result<Vec<Weekday>, String> {
+    let day = |text: &str| {
+        text.trim()
+            .parse::<Weekday>()
+            .map_err(|_| format!("'{}' is not a day of the week", text.trim()))
+    };
+    let mut days = Vec::new();
+    for part in value.split(',') {
+        match part.split_once('-') {
+            // Ranges may wrap around the weekend, e.g. Sun-Thu
+            Some((first, last)) => {
+                let (mut current, last) = (day(first)?, day(last)?);
+                days.push(current);
+                while current != last {
+                    current = current.succ();
+                    days.push(current);
+                }
+            }
+            None => days.push(day(part)?),
+        }
+    }
+    Ok(days)
+}
+
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("'{}' is not a time like 09:00", value.trim()))
+}
+
+impl BusinessHours {
+    pub fn start(&self) -> NaiveTime {
+        self.start
+    }
+
+    /// Whether `at` falls in business hours in `timezone`. Hours spanning
+    /// midnight belong to the day they start on.
+    pub fn contains(&self, at: DateTime<Utc>, timezone: Tz) -> bool {
+        let local = at.with_timezone(&timezone).naive_local();
+        let (date, time) = (local.date(), local.time());
+        if self.start < self.end {
+            self.days.contains(&date.weekday()) && self.start <= time && time < self.end
+        } else if time >= self.start {
+            self.days.contains(&date.weekday())
+        } else {
+            time < self.end && self.days.contains(&date.weekday().pred())
+        }
+    }
+}
+
+/// First moment after `after` when the clock in `timezone` reads `time`, or
+/// `None` past the end of the calendar.
+///
+/// Daylight saving transitions are resolved explicitly rather than left to
+/// chance: a time that occurs twice when clocks go back is taken at its
+/// earliest occurrence, and one skipped when clocks go forward moves to the
+/// first minute after the gap. Only the occurrence returned is logged, so a
+/// transition is reported once per schedule rather than on every lookup.
+pub fn next_local_time(
+    after: DateTime<Utc>,
+    time: NaiveTime,
+    timezone: Tz,
+) -> Option<DateTime<Utc>> {
+    let mut date = after.with_timezone(&timezone).date_naive();
+    // Tomorrow's occurrence is always after `after`; the third day covers a
+    // date skipped entirely, as when Samoa crossed the date line
+    for _ in 0..3 {
+        if let Some((at, resolution)) = resolve_local(date, time, timezone) {
+            if at > after {
+                let local = date.and_time(time);
+                match resolution {
+                    Resolution::Single => {}
+                    Resolution::Earliest => warn!(
+                        "{} occurs twice in {} as clocks go back; using the earlier one",
+                        local, timezone
+                    ),
+                    Resolution::SkippedTo(moved) => warn!(
+                        "{} does not exist in {} as clocks go forward; using {}",
+                        local, timezone, moved
+                    ),
+                }
+                return Some(at);
+            }
+        }
+        date = date.succ_opt()?;
+    }
+    None
+}
+
+/// How a local time was mapped onto a single instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    Single,
+    /// The time occurs twice and the earlier occurrence was taken
+    Earliest,
+    /// The time falls in a gap and was moved to this local time
+    SkippedTo(NaiveDateTime),
+}
+
+fn resolve_local(
+    date: NaiveDate,
+    time: NaiveTime,
+    timezone: Tz,
+) -> Option<(DateTime<Utc>, Resolution)> {
+    let local = date.and_time(time);
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(at) => Some((at.with_timezone(&Utc), Resolution::Single)),
+        LocalResult::Ambiguous(earliest, _) => {
+            Some((earliest.with_timezone(&Utc), Resolution::Earliest))
+        }
+        LocalResult::None => {
+            // Gaps are at most a day; step a minute at a time past it
+            let mut skipped = local;
+            for _ in 0..24 * 60 {
+                skipped = skipped.checked_add_signed(chrono::Duration::minutes(1))?;
+                if let Some(at) = timezone.from_local_datetime(&skipped).earliest() {
+                    return Some((at.with_timezone(&Utc), Resolution::SkippedTo(skipped)));
+                }
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America::New_York, Europe::London};
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    fn time(text: &str) -> NaiveTime {
+        parse_time(text).unwrap()
+    }
+
+    #[test]
+    fn next_local_time_on_ordinary_days() {
+        // 09:00 EST is 14:00 UTC; once it has passed, the next one is tomorrow's
+        let at = |after| next_local_time(utc(after), time("09:00"), New_York);
+        assert_eq!(
+            at("2026-01-15T12:00:00Z"),
+            Some(utc("2026-01-15T14:00:00Z"))
+        );
+        assert_eq!(
+            at("2026-01-15T14:00:00Z"),
+            Some(utc("2026-01-16T14:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn new_york_spring_forward_skips_to_after_the_gap() {
+        // Clocks go from 02:00 EST to 03:00 EDT on 2026-03-08
+        let at = next_local_time(utc("2026-03-08T05:00:00Z"), time("02:30"), New_York);
+        assert_eq!(at, Some(utc("2026-03-08T07:00:00Z")));
+    }
+
+    #[test]
+    fn new_york_fall_back_takes_the_earliest() {
+        // 01:30 happens first in EDT (05:30 UTC) and again in EST (06:30 UTC) on 2026-11-01
+        let at = |after| next_local_time(utc(after), time("01:30"), New_York);
+        assert_eq!(
+            at("2026-11-01T04:00:00Z"),
+            Some(utc("2026-11-01T05:30:00Z"))
+        );
+        // Between the two, the repeat isn't used; the next one is the following night
+        assert_eq!(
+            at("2026-11-01T06:00:00Z"),
+            Some(utc("2026-11-02T06:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn london_spring_forward_skips_to_after_the_gap() {
+        // Clocks go from 01:00 GMT to 02:00 BST on 2026-03-29
+        let at = next_local_time(utc("2026-03-29T00:00:00Z"), time("01:30"), London);
+        assert_eq!(at, Some(utc("2026-03-29T01:00:00Z")));
+    }
+
+    #[test]
+    fn london_fall_back_takes_the_earliest() {
+        // 01:30 happens first in BST (00:30 UTC) and again in GMT (01:30 UTC) on 2026-10-25
+        let at = next_local_time(utc("2026-10-24T23:00:00Z"), time("01:30"), London);
+        assert_eq!(at, Some(utc("2026-10-25T00:30:00Z")));
+    }
+
+    fn hours(text: &str) -> BusinessHours {
+        parse_business_hours(text).unwrap()
+    }
+
+    #[test]
+    fn new_york_business_hours_follow_the_clock_change() {
+        let weekdays = hours("Mon-Fri 09:00-18:00");
+        let open = |at| weekdays.contains(utc(at), New_York);
+        // Friday 2026-03-06 is on EST (UTC-5), Monday 2026-03-09 on EDT (UTC-4)
+        assert!(!open("2026-03-06T13:59:00Z"));
+        assert!(open("2026-03-06T14:00:00Z"));
+        assert!(!open("2026-03-09T12:59:00Z"));
+        assert!(open("2026-03-09T13:00:00Z"));
+        assert!(open("2026-03-09T21:59:00Z"));
+        assert!(!open("2026-03-09T22:00:00Z"));
+    }
+
+    #[test]
+    fn new_york_hours_across_the_transitions() {
+        // 02:00-03:00 doesn't exist on 2026-03-08, so these hours are an hour shorter
+        let spring = hours("Sun 01:00-03:30");
+        let open = |at| spring.contains(utc(at), New_York);
+        assert!(!open("2026-03-08T05:59:00Z"));
+        assert!(open("2026-03-08T06:00:00Z"));
+        assert!(open("2026-03-08T06:59:00Z"));
+        assert!(open("2026-03-08T07:00:00Z"));
+        assert!(!open("2026-03-08T07:30:00Z"));
+
+        // 01:00-02:00 happens twice on 2026-11-01, and both count
+        let fall = hours("Sun 01:00-02:00");
+        let open = |at| fall.contains(utc(at), New_York);
+        assert!(!open("2026-11-01T04:59:00Z"));
+        assert!(open("2026-11-01T05:30:00Z"));
+        assert!(open("2026-11-01T06:30:00Z"));
+        assert!(!open("2026-11-01T07:00:00Z"));
+    }
+
+    #[test]
+    fn london_business_hours_follow_the_clock_change() {
+        let weekdays = hours("Mon-Fri 09:00-17:30");
+        let open = |at| weekdays.contains(utc(at), London);
+        // Friday 2026-03-27 is on GMT, Monday 2026-03-30 on BST (UTC+1)
+        assert!(!open("2026-03-27T08:59:00Z"));
+        assert!(open("2026-03-27T09:00:00Z"));
+        assert!(!open("2026-03-30T07:59:00Z"));
+        assert!(open("2026-03-30T08:00:00Z"));
+        assert!(open("2026-03-30T16:29:00Z"));
+        assert!(!open("2026-03-30T16:30:00Z"));
+    }
+
+    #[test]
+    fn london_overnight_hours_across_fall_back() {
+        // Saturday night into 2026-10-25, when 02:00 BST becomes 01:00 GMT
+        let overnight = hours("Sat 22:00-06:00");
+        let open = |at| overnight.contains(utc(at), London);
+        assert!(!open("2026-10-24T20:59:00Z"));
+        assert!(open("2026-10-24T21:00:00Z"));
+        assert!(open("2026-10-25T00:30:00Z"));
+        assert!(open("2026-10-25T01:30:00Z"));
+        assert!(open("2026-10-25T05:59:00Z"));
+        assert!(!open("2026-10-25T06:00:00Z"));
+        // The hours belong to Saturday, so Sunday night isn't covered
+        assert!(!open("2026-10-25T22:00:00Z"));
+    }
+
+    /// Digest times from `after` on, each scheduled after the previous one was sent.
+    fn daily(after: &str, at: &str, timezone: Tz, days: usize) -> Vec<DateTime<Utc>> {
+        let mut due = utc(after);
+        (0..days)
+            .map(|_| {
+                due = next_local_time(due, time(at), timezone).unwrap();
+                due
+            })
+            .collect()
+    }
+
+    #[test]
+    fn daily_digest_fires_once_a_day_through_the_transitions() {
+        // A 02:30 digest neither fires twice nor skips a day around either US change
+        let spring = daily("2026-03-07T00:00:00Z", "02:30", New_York, 3);
+        assert_eq!(
+            spring,
+            [
+                utc("2026-03-07T07:30:00Z"),
+                utc("2026-03-08T07:00:00Z"),
+                utc("2026-03-09T06:30:00Z"),
+            ]
+        );
+        let fall = daily("2026-10-31T00:00:00Z", "01:30", New_York, 3);
+        assert_eq!(
+            fall,
+            [
+                utc("2026-10-31T05:30:00Z"),
+                utc("2026-11-01T05:30:00Z"),
+                utc("2026-11-02T06:30:00Z"),
+            ]
+        );
+
+        let spring = daily("2026-03-28T00:00:00Z", "01:30", London, 3);
+        assert_eq!(
+            spring,
+            [
+                utc("2026-03-28T01:30:00Z"),
+                utc("2026-03-29T01:00:00Z"),
+                utc("2026-03-30T00:30:00Z"),
+            ]
+        );
+        let fall = daily("2026-10-24T00:00:00Z", "01:30", London, 3);
+        assert_eq!(
+            fall,
+            [
+                utc("2026-10-24T00:30:00Z"),
+                utc("2026-10-25T00:30:00Z"),
+                utc("2026-10-26T01:30:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_local_time_ends_with_the_calendar() {
+        let last = NaiveDate::MAX.and_time(time("23:00")).and_utc();
+        assert_eq!(next_local_time(last, time("22:00"), chrono_tz::UTC), None);
+    }
+}
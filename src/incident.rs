@@ -0,0 +1,155 @@
+use crate::writer::{WriteKind, Writer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{error, info};
+
+pub const INCIDENTS_PATH: &str = "metrics/incidents.json";
+
+// Oldest closed incidents are dropped beyond this so the file stays small
+const MAX_INCIDENTS: usize = 1000;
+
+/// One outage of an endpoint, from its first failed check to its first
+/// successful one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: String,
+    pub endpoint: String,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub failed_checks: u64,
+    pub last_reason: Option<String>,
+    /// Parts of the incident re-tagged afterwards with `ctl reclassify`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reclassified: Vec<Reclassification>,
+}
+
+/// Downtime re-tagged after the fact, e.g. as maintenance someone forgot to
+/// open a window for. The incident itself is kept as recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reclassification {
+    /// What the downtime counts as instead, e.g. `maintenance`
+    pub kind: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub reason: String,
+    /// Who reclassified it, and when
+    pub by: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Incident {
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        (self.closed_at.unwrap_or(now) - self.opened_at)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    /// How much of `[from, to]` the incident covers; an open one runs until `now`.
+    pub fn overlap(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+        let start = self.opened_at.max(from);
+        let end = self.closed_at.unwrap_or(now).min(to);
+        (end - start).to_std().unwrap_or_default()
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Incident>> {
+    match fs::read_to_string(path) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Incidents persisted next to the metrics, updated as checks come in.
+pub struct IncidentLog {
+    path: PathBuf,
+    incidents: Vec<Incident>,
+}
+
+impl IncidentLog {
+    pub fn open(path: &Path) -> Self {
+        let incidents = load(path).unwrap_or_else(|e| {
+            error!("Failed to read incidents from {}: {}", path.display(), e);
+            Vec::new()
+        });
+        Self {
+            path: path.to_path_buf(),
+            incidents,
+        }
+    }
+
+    /// Opens, extends or closes the endpoint's incident for one check result.
+    pub fn record(&mut self, endpoint: &str, success: bool, reason: Option<&str>, writer: &Writer) {
+        let now = Utc::now();
+        let open = self
+            .incidents
+            .iter_mut()
+            .find(|i| i.endpoint == endpoint && i.closed_at.is_none());
+
+        // Opening or closing an incident must reach storage; a longer streak is routine
+        let kind = match (open, success) {
+            (None, true) => return,
+            (Some(incident), true) => {
+                incident.closed_at = Some(now);
+                info!(
+                    "Incident {} for {} closed after {}",
+                    incident.id,
+                    endpoint,
+                    humantime::format_duration(Duration::from_secs(
+                        incident.duration(now).as_secs()
+                    ))
+                );
+                WriteKind::Required
+            }
+            (Some(incident), false) => {
+                incident.failed_checks += 1;
+                incident.last_reason = reason.map(str::to_string);
+                WriteKind::Routine
+            }
+            (None, false) => {
+                let incident = Incident {
+                    id: format!("inc-{}", now.timestamp_millis()),
+                    endpoint: endpoint.to_string(),
+                    opened_at: now,
+                    closed_at: None,
+                    failed_checks: 1,
+                    last_reason: reason.map(str::to_string),
+                    reclassified: Vec::new(),
+                };
+                info!("Incident {} opened for {}", incident.id, endpoint);
+                self.incidents.push(incident);
+                WriteKind::Required
+            }
+        };
+
+        if self.incidents.len() > MAX_INCIDENTS {
+            if let Some(index) = self.incidents.iter().position(|i| i.closed_at.is_some()) {
+                self.incidents.remove(index);
+            }
+        }
+        match serde_json::to_string_pretty(&self.incidents) {
+            Ok(json) => {
+                writer.write(&self.path, json, kind);
+            }
+            Err(e) => error!("Failed to save incidents: {}", e),
+        }
+    }
+}
+
+/// Number of incidents opened since `since` and their mean time to recovery
+/// (closed incidents only).
+pub fn summarize(incidents: &[Incident], since: DateTime<Utc>) -> (usize, Option<Duration>) {
+    let recent: Vec<&Incident> = incidents.iter().filter(|i| i.opened_at >= since).collect();
+    let closed: Vec<Duration> = recent
+        .iter()
+        .filter(|i| i.closed_at.is_some())
+        .map(|i| i.duration(Utc::now()))
+        .collect();
+    let mttr = (!closed.is_empty()).then(|| closed.iter().sum::<Duration>() / closed.len() as u32);
+    (recent.len(), mttr)
+}
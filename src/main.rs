@@ -1,43 +1,1977 @@
+mod auth;
+mod budget;
+mod check;
+mod config;
+mod control;
+mod csvlog;
+mod error;
+mod history;
+mod hours;
+mod incident;
 mod monitor;
+mod notify;
+mod outcome;
+mod priority;
+mod prometheus;
+mod quorum;
+mod ratelimit;
+mod recent;
+mod record;
+mod recording;
+mod selftest;
+mod server;
+mod slo;
+mod snapshot;
+mod sns;
+mod store;
+mod style;
+mod trend;
+mod writer;
 
-use clap::Parser;
-use std::time::Duration;
-use tracing::Level;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn, Level};
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Endpoint URLs to monitor (space-separated)
-    #[arg(value_name = "URLS", num_args = 1..)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Endpoint URLs to monitor (space-separated), each optionally as URL=DURATION to override --interval
+    #[arg(value_name = "URLS", num_args = 1.., required_unless_present = "config")]
     endpoints: Vec<String>,
 
-    /// Check interval in seconds
-    #[arg(short, long, default_value = "60")]
-    interval: u64,
+    /// Load endpoints and their per-endpoint settings from a TOML file; URLs given on the command line are added to them
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Check interval for endpoints without their own
+    #[arg(short, long, value_name = "DURATION", default_value = "60", value_parser = parse_duration)]
+    interval: Duration,
+
+    /// Request timeout, covering connecting and reading the whole response
+    #[arg(short, long, visible_alias = "read-timeout", value_name = "DURATION", default_value = "10", value_parser = parse_duration)]
+    timeout: Duration,
+
+    /// Fail a check that can't establish a connection within DURATION (defaults to --timeout)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+
+    /// Start the HTTP server on this address (127.0.0.1:9090 when given without a value)
+    #[arg(
+        long,
+        visible_alias = "listen",
+        value_name = "HOST:PORT",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:9090"
+    )]
+    serve_addr: Option<SocketAddr>,
+
+    /// Protect every HTTP server route with Basic Authentication
+    #[arg(
+        long,
+        value_name = "USER:PASSWORD",
+        env = "UPTIME_SERVE_AUTH",
+        hide_env_values = true,
+        value_parser = parse_basic_auth
+    )]
+    serve_auth: Option<String>,
+
+    /// Limit each client IP to N requests per second on the HTTP server, answering 429 beyond that
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    api_rate_limit: Option<u32>,
+
+    /// Comma-separated IPs or CIDR blocks exempt from --api-rate-limit, e.g. 10.0.0.0/8,127.0.0.1
+    #[arg(long, value_name = "CIDRS", value_delimiter = ',', requires = "api_rate_limit", value_parser = ratelimit::parse_trusted_ip)]
+    trusted_ips: Vec<ipnet::IpNet>,
+
+    /// Serve only the Prometheus /metrics route at HOST:PORT, for scraping from other hosts; a bare PORT binds to localhost
+    #[arg(long, visible_alias = "metrics-port", value_name = "[HOST:]PORT", value_parser = parse_metrics_addr)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Also serve the HTTP API on a Unix domain socket at PATH, e.g. /run/uptime.sock
+    #[arg(long, value_name = "PATH")]
+    metrics_socket: Option<PathBuf>,
+
+    /// Write an OpenMetrics snapshot to this file after every check round
+    #[arg(long, value_name = "PATH")]
+    export_openmetrics: Option<PathBuf>,
+
+    /// Batch status change alerts into one digest message sent every DURATION
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    alert_digest_interval: Option<Duration>,
+
+    /// Endpoint whose alerts bypass the digest and are sent immediately (repeatable)
+    #[arg(long = "critical", value_name = "URL")]
+    critical_endpoints: Vec<String>,
+
+    /// When alerts page immediately, as [DAYS ]HH:MM-HH:MM, e.g. "Mon-Fri 09:00-18:00"; outside them, alerts for endpoints below --after-hours-page-priority are held for a digest
+    #[arg(long, value_name = "HOURS", value_parser = hours::parse_business_hours)]
+    business_hours: Option<hours::BusinessHours>,
+
+    /// Timezone of --business-hours and --after-hours-digest-at
+    #[arg(
+        long,
+        value_name = "TZ",
+        default_value = "UTC",
+        requires = "business_hours"
+    )]
+    business_timezone: chrono_tz::Tz,
+
+    /// Local time the alerts held after hours are sent [default: start of --business-hours]
+    #[arg(long, value_name = "HH:MM", value_parser = hours::parse_time, requires = "business_hours")]
+    after_hours_digest_at: Option<chrono::NaiveTime>,
+
+    /// Endpoints of at least this priority (critical, high, normal or low) still page after hours
+    #[arg(long, value_name = "LEVEL", default_value = "critical", value_parser = priority::parse_priority, requires = "business_hours")]
+    after_hours_page_priority: priority::Priority,
+
+    /// Dispatch priority of an endpoint's checks as URL=LEVEL, where LEVEL is critical, high, normal or low (repeatable; --critical endpoints default to critical, the rest to normal)
+    #[arg(long = "check-priority", value_name = "URL=LEVEL", value_parser = priority::parse_check_priority)]
+    check_priorities: Vec<(String, priority::Priority)>,
+
+    /// Shared directory where monitor instances exchange their view for quorum alerting
+    #[arg(long, value_name = "DIR", requires = "instance_id")]
+    quorum_dir: Option<PathBuf>,
+
+    /// Unique name of this instance within the quorum directory
+    #[arg(long, value_name = "ID")]
+    instance_id: Option<String>,
+
+    /// Number of instances that must agree an endpoint is down before alerting
+    #[arg(long, default_value = "2", requires = "quorum_dir")]
+    quorum: usize,
+
+    #[command(flatten)]
+    response_rules: ResponseRuleArgs,
+
+    /// Availability SLO target in percent (e.g. 99.9); enables burn-rate alerting
+    #[arg(long, value_name = "PERCENT", value_parser = parse_slo_target)]
+    slo_target: Option<f64>,
+
+    /// Successful checks slower than DURATION also count against the SLO error budget
+    #[arg(long, value_name = "DURATION", requires = "slo_target", value_parser = parse_duration)]
+    slo_latency: Option<Duration>,
+
+    /// Log raw HTTP request/response headers and the first 512 body bytes at TRACE level.
+    /// This includes authorization headers, so only enable it while debugging.
+    #[arg(long)]
+    trace_http: bool,
+
+    /// Log each metrics save at DEBUG level with its size and serialization and write times
+    #[arg(long)]
+    debug_storage: bool,
+
+    /// Text message to send after connecting to ws:// and wss:// endpoints
+    #[arg(long, value_name = "TEXT")]
+    ws_send: Option<String>,
+
+    /// WebSocket reply must contain TEXT for the check to pass
+    #[arg(long, value_name = "TEXT")]
+    ws_expect: Option<String>,
+
+    /// WebSocket reply must be JSON containing every field of this JSON value
+    #[arg(long, value_name = "JSON", value_parser = parse_json)]
+    ws_expect_json: Option<serde_json::Value>,
+
+    /// Check this streaming endpoint (Server-Sent Events, chunked streams) by reading only until its first event or --stream-bytes bytes, then disconnecting (repeatable)
+    #[arg(long = "stream", value_name = "URL")]
+    stream_endpoints: Vec<String>,
+
+    /// The first event or data from a --stream endpoint must match this regex
+    #[arg(long, value_name = "REGEX", requires = "stream_endpoints")]
+    stream_expect: Option<regex::Regex>,
+
+    /// Bytes a --stream endpoint that isn't text/event-stream must send before the check passes
+    #[arg(long, value_name = "BYTES", default_value = "1", value_parser = clap::value_parser!(u64).range(1..))]
+    stream_bytes: u64,
+
+    /// Redact captured header values (an endpoint's capture_headers) matching this regex, e.g. session IDs or tokens (repeatable)
+    #[arg(long = "redact-header-pattern", value_name = "REGEX")]
+    redact_header_patterns: Vec<regex::Regex>,
+
+    /// Maximum idle pooled connections kept per host
+    #[arg(long, value_name = "N", default_value = "4")]
+    pool_max_idle_per_host: usize,
+
+    /// How long an idle pooled connection is kept before being closed
+    #[arg(long, value_name = "DURATION", default_value = "30", value_parser = parse_duration)]
+    pool_idle_timeout: Duration,
+
+    /// TCP keepalive interval (0 disables it)
+    #[arg(long, value_name = "DURATION", default_value = "60", value_parser = parse_duration)]
+    tcp_keepalive: Duration,
+
+    /// Send `Connection: close` to this endpoint instead of reusing pooled connections (repeatable)
+    #[arg(long = "connection-close", value_name = "URL")]
+    connection_close: Vec<String>,
+
+    /// Save every check's raw HTTP exchange as JSON into DIR, for use with `replay`
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+
+    /// Exit instead of warning when timeouts, retries and warmups can overrun the check interval
+    #[arg(long)]
+    strict: bool,
+
+    /// Check every endpoint once, then exit: 0 if all are up, 1 if some are down, 2 if all are down, 3 if checks could not run (e.g. DNS failure)
+    #[arg(long)]
+    once: bool,
+
+    /// Check that every configured integration works, print a pass/fail table and exit: endpoint hosts resolve and accept TCP connections, notifiers take a test message, the data directory is writable and the listeners can bind
+    #[arg(long, conflicts_with = "once")]
+    selftest: bool,
+
+    /// With --once, write each endpoint's result to this JSON file before exiting
+    #[arg(long, value_name = "PATH", requires = "once")]
+    summary_out: Option<PathBuf>,
+
+    /// Also time TCP connects over IPv4 and IPv6 separately to expose dual-stack fallback penalties
+    #[arg(long)]
+    ip_family_timing: bool,
+
+    /// Maximum number of response body bytes to read per check; larger bodies are truncated
+    #[arg(long, default_value = "1048576")]
+    max_response_body_bytes: usize,
+
+    /// Number of individual check results kept per endpoint in metrics/recent_checks.json (0 keeps none)
+    #[arg(long, value_name = "N", default_value = "1000")]
+    recent_checks: usize,
+
+    /// Reference host:port to probe before each round (repeatable, e.g. 1.1.1.1:443); if none answers, the round is skipped
+    #[arg(long = "connectivity-probe")]
+    connectivity_probes: Vec<String>,
+
+    /// Override whether a raw outcome counts as up for one endpoint, as ENDPOINT,OUTCOME=up|down (repeatable).
+    /// Outcomes: ok-2xx, http-<code>, timeout, connect-reset, tls-error, dns-error, other
+    #[arg(long = "map-outcome", value_parser = outcome::parse_mapping)]
+    outcome_mappings: Vec<outcome::OutcomeMapping>,
+
+    /// Emoji, label and color for a severity as SEVERITY=EMOJI:LABEL[:COLOR] (repeatable).
+    /// Severities: up, down, critical (down on a --critical endpoint)
+    #[arg(long = "status-style", value_parser = style::parse_style)]
+    status_styles: Vec<(style::Severity, style::StatusStyle)>,
+
+    /// Health score weights for uptime, latency and recent errors
+    #[arg(long, value_name = "UPTIME,LATENCY,ERRORS", default_value = "0.6,0.25,0.15", value_parser = parse_health_weights)]
+    health_score_weights: monitor::HealthWeights,
+
+    /// Warn when probe traffic to one endpoint exceeds BYTES in a UTC day
+    #[arg(long, value_name = "BYTES")]
+    max_bytes_per_day: Option<u64>,
+
+    /// Fail the check unless the endpoint's body has this SHA-256, as URL=HEX (repeatable)
+    #[arg(long = "expect-sha256", value_name = "URL=HEX", value_parser = parse_sha256)]
+    expected_sha256: Vec<(String, String)>,
+
+    /// Alert when an endpoint's Date header differs from the monitor's clock by more than DURATION, as URL=DURATION (repeatable)
+    #[arg(long = "max-clock-skew", value_name = "URL=DURATION", value_parser = parse_clock_skew)]
+    max_clock_skew: Vec<(String, Duration)>,
+
+    /// Read the TLS certificate of every HTTPS endpoint each check and alert when it expires within N days, even when the HTTP check passes
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    cert_expiry_warn_days: Option<u32>,
+
+    /// Resolve every endpoint's hostname before monitoring and exit if any fails
+    #[arg(long)]
+    validate_endpoints: bool,
+
+    /// With --validate-endpoints, only warn about hostnames that don't resolve
+    #[arg(long, requires = "validate_endpoints")]
+    allow_dns_failures: bool,
+
+    /// Check endpoints over --max-bytes-per-day only every tenth round for the rest of the day
+    #[arg(long, requires = "max_bytes_per_day")]
+    throttle_over_cap: bool,
+
+    /// POST here before checking and send the token from its JSON response with each check
+    #[arg(long, value_name = "URL")]
+    auth_login_url: Option<String>,
+
+    /// JSON body for the login request
+    #[arg(long, value_name = "JSON", value_parser = parse_json, requires = "auth_login_url")]
+    auth_login_body: Option<serde_json::Value>,
+
+    /// JSONPath to the token in the login response
+    #[arg(long, value_name = "PATH", default_value = "$.access_token")]
+    auth_token_path: String,
+
+    /// Header the token is sent in, with {token} as a placeholder
+    #[arg(long, value_name = "NAME: TEMPLATE", default_value = "Authorization: Bearer {token}", value_parser = auth::parse_header_template)]
+    auth_header: (reqwest::header::HeaderName, String),
+
+    /// Log in again after this long (default: the response's expires_in, else only on a 401)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "auth_login_url")]
+    auth_ttl: Option<Duration>,
+
+    /// Only send the token to this endpoint (repeatable; default: every HTTP endpoint)
+    #[arg(
+        long = "auth-endpoint",
+        value_name = "URL",
+        requires = "auth_login_url"
+    )]
+    auth_endpoints: Vec<String>,
+
+    /// Check this Elasticsearch /_cluster/health URL by its cluster status: green is up, yellow is up but degraded, red is down (repeatable)
+    #[arg(long = "elasticsearch", value_name = "URL")]
+    elasticsearch: Vec<String>,
+
+    /// Check this endpoint with a Range request for its first --range-bytes bytes and require a 206 (repeatable)
+    #[arg(long = "range-request", value_name = "URL")]
+    range_requests: Vec<String>,
+
+    /// Number of bytes requested by --range-request
+    #[arg(long, value_name = "BYTES", default_value = "1024", value_parser = clap::value_parser!(u64).range(1..))]
+    range_bytes: u64,
+
+    /// With --record, strip matches of this regex (e.g. timestamps, request IDs) before comparing consecutive failures (repeatable)
+    #[arg(long = "capture-normalize", value_name = "REGEX", requires = "record")]
+    capture_normalize: Vec<regex::Regex>,
+
+    /// Meter a monthly latency objective for an endpoint as URL=MS[@PERCENTILE], e.g. URL=400@95 for p95 under 400ms (repeatable)
+    #[arg(long = "latency-slo", value_name = "URL=MS[@PERCENTILE]", value_parser = budget::parse_latency_slo)]
+    latency_slos: Vec<budget::LatencySlo>,
+
+    /// Timezone whose calendar months the latency budgets reset on
+    #[arg(long, value_name = "TZ", default_value = "UTC")]
+    budget_timezone: chrono_tz::Tz,
+
+    /// Consecutive failed checks required before an endpoint is reported down
+    #[arg(long, visible_alias = "failure-threshold", value_name = "N", default_value = "1", value_parser = clap::value_parser!(u64).range(1..))]
+    failures_before_alert: u64,
+
+    /// Consecutive successful checks required before a down endpoint is reported up again
+    #[arg(long, value_name = "M", default_value = "1", value_parser = clap::value_parser!(u64).range(1..))]
+    recovery_threshold: u64,
+
+    /// HTTP statuses that count as up instead of any 2xx, as a comma-separated list of statuses and ranges, e.g. 200,204,301-399
+    #[arg(long, value_name = "STATUSES", value_parser = check::parse_status_set)]
+    expect_status: Option<check::StatusSet>,
+
+    /// HTTP method of each check, e.g. HEAD to skip downloading bodies; an endpoint's `method` in the config file wins
+    #[arg(long, default_value = "GET", value_parser = parse_method)]
+    method: reqwest::Method,
+
+    /// Request body sent with every check, e.g. for --method POST
+    #[arg(long, value_name = "TEXT")]
+    body: Option<String>,
+
+    /// Content-Type of --body
+    #[arg(long, value_name = "TYPE", requires = "body")]
+    content_type: Option<String>,
+
+    /// Maximum number of endpoints checked at the same time
+    #[arg(long, visible_alias = "concurrency", value_name = "N", default_value = "64", value_parser = clap::value_parser!(u64).range(1..))]
+    max_concurrent_checks: u64,
+
+    /// Also post every notification to this Discord webhook
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "DISCORD_WEBHOOK_URL",
+        hide_env_values = true
+    )]
+    discord_webhook: Option<String>,
+
+    /// Also post every notification as JSON to this URL; status changes carry the endpoint, status, timestamp, response time and failure count as fields (repeatable)
+    #[arg(long = "webhook-url", value_name = "URL")]
+    webhook_urls: Vec<String>,
+
+    /// Maximum number of notifications sent at the same time, across Slack, Discord and SNS
+    #[arg(long, value_name = "N", default_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+    max_concurrent_notifications: u64,
+
+    /// Save every endpoint's metrics at least this often even when nothing changed, keeping their updated_at fresh for tools that watch it
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    max_metrics_age: Option<Duration>,
+
+    /// Storage writes that may be pending before routine file updates and check rows are dropped; incidents and endpoint metrics are always written
+    #[arg(long, value_name = "N", default_value = "256", value_parser = clap::value_parser!(u64).range(1..))]
+    write_queue: u64,
+
+    /// SQLite database each endpoint's metrics and every check are saved in, queryable with any SQLite client
+    #[arg(long, value_name = "PATH", default_value = store::DEFAULT_DB_PATH)]
+    db_path: PathBuf,
+
+    /// Resume from a metrics snapshot, as served by GET /api/metrics or written by an earlier version, instead of the database, e.g. after moving the monitor to another host; it replaces the saved metrics
+    #[arg(long, value_name = "PATH")]
+    restore_snapshot: Option<PathBuf>,
+
+    /// Also append a row per check to this CSV file (timestamp, endpoint, success, response time, cumulative success rate); the header is written when the file is created
+    #[arg(long, value_name = "FILE")]
+    csv_path: Option<PathBuf>,
+
+    /// Notify when an endpoint's 24h or 7d uptime drops this many percentage points below the window before
+    #[arg(long, value_name = "POINTS")]
+    trend_alert_delta: Option<f64>,
+}
+
+// A socket left behind by a previous run would make bind fail, but only remove
+// the path when it is a socket so a mistyped path can't delete a regular file.
+fn bind_unix_socket(path: &Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path exists and is not a socket",
+            ))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    tokio::net::UnixListener::bind(path)
+}
+
+/// Probes every configured integration instead of monitoring, then exits
+/// non-zero if any probe failed. Each probe is bounded by `timeout`.
+async fn selftest(
+    monitor: &monitor::Monitor,
+    endpoints: &[String],
+    schedule_problems: usize,
+    timeout: Duration,
+    serve_addr: Option<SocketAddr>,
+    metrics_addr: Option<SocketAddr>,
+    metrics_socket: Option<&Path>,
+) -> ! {
+    let mut probes = vec![selftest::Probe {
+        name: "config".into(),
+        outcome: Ok(format!(
+            "{} endpoints, {} schedule warnings",
+            endpoints.len(),
+            schedule_problems
+        )),
+    }];
+    let connects = endpoints.iter().map(|endpoint| {
+        selftest::probe(
+            format!("connect {}", endpoint),
+            timeout,
+            selftest::connect(endpoint),
+        )
+    });
+    probes.extend(futures_util::future::join_all(connects).await);
+
+    probes.extend(monitor.test_notifiers(timeout).await);
+    match sns::SnsPublisher::from_env() {
+        Ok(Some(publisher)) => {
+            let event = monitor::MonitorEvent::Annotation {
+                endpoint: "selftest".into(),
+                message: "Uptime monitor self-test: this is a test message, no action needed"
+                    .into(),
+                timestamp: chrono::Utc::now(),
+            };
+            probes.push(
+                selftest::probe("notify sns".into(), timeout, async {
+                    publisher
+                        .publish(&event)
+                        .await
+                        .map(|()| "test message published".to_string())
+                })
+                .await,
+            );
+        }
+        Ok(None) => {}
+        Err(e) => probes.push(selftest::Probe {
+            name: "notify sns".into(),
+            outcome: Err(e),
+        }),
+    }
+
+    let data_dir = Path::new(history::HISTORY_PATH)
+        .parent()
+        .unwrap_or(Path::new("."));
+    probes.push(selftest::Probe {
+        name: "data directory".into(),
+        outcome: selftest::data_dir(data_dir),
+    });
+
+    for addr in serve_addr.into_iter().chain(metrics_addr) {
+        probes
+            .push(selftest::probe(format!("listen {}", addr), timeout, selftest::bind(addr)).await);
+    }
+    if let Some(path) = metrics_socket {
+        probes.push(
+            selftest::probe(
+                format!("listen {}", path.display()),
+                timeout,
+                selftest::bind_unix(path),
+            )
+            .await,
+        );
+    }
+
+    let failed = selftest::print_table(&probes);
+    info!(
+        "Self-test finished: {} of {} probes passed",
+        probes.len() - failed,
+        probes.len()
+    );
+    process::exit(i32::from(failed > 0))
+}
+
+fn parse_basic_auth(value: &str) -> Result<String, String> {
+    match value.split_once(':') {
+        Some((user, _)) if !user.is_empty() => Ok(value.to_string()),
+        _ => Err("expected USER:PASSWORD".into()),
+    }
+}
+
+/// `HOST:PORT`, or a bare `PORT` on localhost so the scrape port is only
+/// opened to other hosts when asked for.
+fn parse_metrics_addr(value: &str) -> Result<SocketAddr, String> {
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a PORT or HOST:PORT like 0.0.0.0:9100", value))
+}
+
+fn parse_method(value: &str) -> Result<reqwest::Method, String> {
+    reqwest::Method::from_bytes(value.to_uppercase().as_bytes())
+        .map_err(|_| format!("invalid HTTP method '{}'", value))
+}
+
+/// Parses an RFC 3339 time, also accepting it without seconds, e.g.
+/// `2024-05-01T02:00Z`.
+fn parse_timestamp(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .or_else(|_| {
+            let (time, offset) = value.split_at(value.rfind(['Z', 'z', '+', '-']).unwrap_or(0));
+            chrono::DateTime::parse_from_rfc3339(&format!("{}:00{}", time, offset))
+        })
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .map_err(|_| format!("'{}' is not a time like 2024-05-01T02:00Z", value))
+}
+
+fn parse_json(value: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid JSON: {}", e))
+}
+
+/// Flags deciding whether a response counts as up, shared with `replay`.
+#[derive(clap::Args, Debug)]
+struct ResponseRuleArgs {
+    /// Treat responses whose Server header contains TEXT as an intermediary error page (repeatable)
+    #[arg(long, value_name = "TEXT")]
+    reject_server_header: Vec<String>,
+
+    /// Treat responses whose body contains TEXT as an intermediary error page (repeatable)
+    #[arg(long, value_name = "TEXT")]
+    reject_body_contains: Vec<String>,
+
+    /// Only count a response as up if its body contains TEXT (repeatable; all must match)
+    #[arg(long, visible_alias = "expect-body", value_name = "TEXT")]
+    must_contain: Vec<String>,
+
+    /// Only count a response as up if its body matches REGEX (repeatable; all must match)
+    #[arg(long, value_name = "REGEX")]
+    expect_body_regex: Vec<regex::Regex>,
+
+    /// Require a response header, optionally containing VALUE (repeatable)
+    #[arg(long, value_name = "NAME[=VALUE]", value_parser = check::parse_header_requirement)]
+    require_header: Vec<check::HeaderRequirement>,
+
+    /// Require Strict-Transport-Security (with max-age >= 180 days unless --hsts-min-age is set),
+    /// Content-Security-Policy and X-Content-Type-Options: nosniff
+    #[arg(long)]
+    security_headers: bool,
+
+    /// Minimum Strict-Transport-Security max-age
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    hsts_min_age: Option<Duration>,
+}
+
+impl From<ResponseRuleArgs> for check::ResponseRules {
+    fn from(args: ResponseRuleArgs) -> Self {
+        let mut require_headers = args.require_header;
+        let mut hsts_min_age = args.hsts_min_age.map(|age| age.as_secs());
+        if args.security_headers {
+            require_headers.extend(check::HeaderRequirement::security_baseline());
+            hsts_min_age = hsts_min_age.or(Some(180 * 24 * 3600));
+        }
+        Self {
+            reject_server_headers: args.reject_server_header,
+            reject_body_contains: args.reject_body_contains,
+            must_contain: args.must_contain,
+            must_match: args.expect_body_regex,
+            require_headers,
+            hsts_min_age,
+        }
+    }
+}
+
+/// Splits an endpoint argument given as `URL=DURATION`. A trailing `=VALUE` that
+/// completes a bare query key, as in `https://a.com/?page=2`, stays part of the URL.
+fn split_endpoint_interval(arg: &str) -> Result<(String, Option<Duration>), String> {
+    let Some((url, suffix)) = arg.rsplit_once('=') else {
+        return Ok((arg.to_string(), None));
+    };
+    let completes_query_key = url
+        .rsplit_once(['?', '&'])
+        .is_some_and(|(_, key)| !key.contains('='));
+    match parse_duration(suffix) {
+        Ok(interval) if !completes_query_key => {
+            if interval.is_zero() {
+                return Err(format!("{}: interval must be greater than zero", url));
+            }
+            Ok((url.to_string(), Some(interval)))
+        }
+        _ => Ok((arg.to_string(), None)),
+    }
+}
+
+/// Accepts bare (possibly fractional) seconds or a humantime duration, so every
+/// duration flag reads the same way: "90", "0.5", "1m30s", "2h".
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs)
+            .map_err(|_| format!("'{}' is not a valid number of seconds", value));
+    }
+    humantime::parse_duration(value).map_err(|_| {
+        format!(
+            "'{}' is not a duration; use seconds (e.g. 90) or units like 1m30s, 500ms, 2h",
+            value
+        )
+    })
+}
+
+fn parse_sha256(value: &str) -> Result<(String, String), String> {
+    let (url, hash) = value.rsplit_once('=').ok_or("expected URL=HEX")?;
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a hex SHA-256 digest", hash));
+    }
+    Ok((url.to_string(), hash.to_lowercase()))
+}
+
+fn parse_clock_skew(value: &str) -> Result<(String, Duration), String> {
+    let (url, limit) = value.rsplit_once('=').ok_or("expected URL=DURATION")?;
+    let limit = parse_duration(limit)?;
+    // The Date header only has one-second resolution
+    if limit < Duration::from_secs(1) {
+        return Err("the skew limit must be at least 1s".into());
+    }
+    Ok((url.to_string(), limit))
+}
+
+fn parse_health_weights(value: &str) -> Result<monitor::HealthWeights, String> {
+    let weights = value
+        .split(',')
+        .map(|w| match w.trim().parse::<f64>() {
+            Ok(w) if w >= 0.0 => Ok(w),
+            _ => Err(format!("invalid weight '{}'", w)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match weights[..] {
+        [uptime, latency, errors] if uptime + latency + errors > 0.0 => {
+            Ok(monitor::HealthWeights {
+                uptime,
+                latency,
+                errors,
+            })
+        }
+        [_, _, _] => Err("at least one weight must be positive".into()),
+        _ => Err("expected three comma-separated weights".into()),
+    }
+}
+
+fn parse_slo_target(value: &str) -> Result<f64, String> {
+    let target: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if target > 0.0 && target < 100.0 {
+        Ok(target)
+    } else {
+        Err("must be between 0 and 100 (exclusive)".into())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect or adjust a monitor through its data directory
+    Ctl {
+        /// Database the monitor saves its metrics in
+        #[arg(long, value_name = "PATH", default_value = store::DEFAULT_DB_PATH, global = true)]
+        db_path: PathBuf,
+
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+    /// Re-evaluate a recorded exchange against the response rules without any network access
+    Replay {
+        /// File written by --record
+        file: PathBuf,
+
+        #[command(flatten)]
+        response_rules: ResponseRuleArgs,
+
+        /// HTTP statuses that count as up instead of any 2xx, as for a live check
+        #[arg(long, value_name = "STATUSES", value_parser = check::parse_status_set)]
+        expect_status: Option<check::StatusSet>,
+    },
+    /// Re-deliver notifications captured with `ctl record-events` to a URL, preserving their relative timing
+    ReplayEvents {
+        /// NDJSON file written by ctl record-events
+        file: PathBuf,
+
+        /// URL each recorded payload is POSTed to as JSON
+        #[arg(long, value_name = "URL")]
+        to: String,
+
+        /// Play back this many times faster than recorded, e.g. 10x or 0.5x
+        #[arg(long, default_value = "1x", value_parser = recording::parse_speed)]
+        speed: f64,
+
+        /// Retry a failed delivery this many times
+        #[arg(long, default_value = "0")]
+        retries: u32,
+    },
+    /// Print a one-line fleet health summary (under 500 characters) for a status email or Slack
+    Summary {
+        /// Database the monitor saves its metrics in
+        #[arg(long, value_name = "PATH", default_value = store::DEFAULT_DB_PATH)]
+        db_path: PathBuf,
+    },
+    /// Compare two config files endpoint by endpoint; exits 0 if they are the same, 1 if not
+    ConfigDiff {
+        /// Config file before the change
+        old: PathBuf,
+        /// Config file after the change
+        new: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CtlCommand {
+    /// Print a snapshot of the saved metrics
+    Dump {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: DumpFormat,
+    },
+    /// Summarize the saved metrics: endpoints that are down, slowest and least reliable
+    Status {
+        /// How many endpoints to list in the slowest and least reliable sections
+        #[arg(long, default_value = "5")]
+        top: usize,
+
+        /// Also show the headers captured by each endpoint's last check
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// Temporarily monitor an extra URL; the running monitor picks it up on its next round
+    Watch {
+        /// URL to watch
+        #[arg(required_unless_present = "list")]
+        url: Option<String>,
+
+        /// How long to watch it for (e.g. "90m", "24h")
+        #[arg(long = "for", value_name = "DURATION", default_value = "24h", value_parser = parse_duration)]
+        duration: Duration,
+
+        /// Label shown alongside the URL
+        #[arg(long)]
+        name: Option<String>,
+
+        /// List active temporary endpoints instead of adding one
+        #[arg(long, conflicts_with = "url")]
+        list: bool,
+    },
+    /// Show per-endpoint probe traffic: requests sent and bytes exchanged
+    Traffic,
+    /// List incidents and report their count and mean time to recovery
+    Incidents {
+        /// Only include incidents opened within this period
+        #[arg(long, value_name = "DURATION", default_value = "30d", value_parser = parse_duration)]
+        since: Duration,
+    },
+    /// Open a deploy window: alerts for the endpoint are suppressed until deploy-end or the timeout
+    DeployStart {
+        /// Endpoint URL being deployed
+        endpoint: String,
+
+        /// Close the window automatically after this long
+        #[arg(long = "for", value_name = "DURATION", default_value = "30m", value_parser = parse_duration)]
+        duration: Duration,
+    },
+    /// Close a deploy window opened with deploy-start
+    DeployEnd {
+        /// Window ID printed by deploy-start
+        id: String,
+    },
+    /// Append every notification the running monitor dispatches to an NDJSON file, with secrets redacted
+    RecordEvents {
+        /// File to append the events to
+        #[arg(long, value_name = "PATH")]
+        out: PathBuf,
+
+        /// How long to record for
+        #[arg(long = "for", value_name = "DURATION", default_value = "1h", value_parser = parse_duration)]
+        duration: Duration,
+    },
+    /// Move an endpoint's stored metrics and history to a new key after changing its URL or identity; run it while the monitor is stopped
+    RenameEndpoint {
+        /// Key the data is stored under now, e.g. the old URL
+        old: String,
+
+        /// Key to store it under, as the endpoint's new URL or identity gives it
+        new: String,
+    },
+    /// Re-tag an endpoint's recorded downtime, e.g. as maintenance nobody opened a window for (stop the monitor first)
+    Reclassify {
+        /// Endpoint as stored in the metrics and incidents
+        endpoint: String,
+
+        /// Start of the period, e.g. 2024-05-01T02:00Z
+        #[arg(long, value_name = "TIME", value_parser = parse_timestamp)]
+        from: chrono::DateTime<chrono::Utc>,
+
+        /// End of the period
+        #[arg(long, value_name = "TIME", value_parser = parse_timestamp)]
+        to: chrono::DateTime<chrono::Utc>,
+
+        /// What the downtime counts as instead
+        #[arg(
+            long = "as",
+            value_name = "KIND",
+            value_enum,
+            default_value = "maintenance"
+        )]
+        kind: ReclassifyKind,
+
+        /// Why, kept with the incident
+        #[arg(long)]
+        reason: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReclassifyKind {
+    Maintenance,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum DumpFormat {
+    Json,
+    Prometheus,
+    Openmetrics,
+    Csv,
+}
+
+fn read_saved_metrics(db: &Path) -> HashMap<String, monitor::Metrics> {
+    match store::read_metrics(db, Path::new(monitor::LEGACY_METRICS_PATH)) {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            eprintln!("Failed to read saved metrics: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn dump(db: &Path, format: DumpFormat) {
+    let metrics = read_saved_metrics(db);
+
+    match format {
+        DumpFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&metrics).expect("Failed to serialize metrics")
+        ),
+        DumpFormat::Prometheus => print!("{}", prometheus::render(&metrics)),
+        DumpFormat::Openmetrics => print!("{}", prometheus::render_openmetrics(&metrics)),
+        DumpFormat::Csv => {
+            let mut entries: Vec<_> = metrics.values().collect();
+            entries.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+            println!("{}", monitor::Metrics::csv_header());
+            for entry in entries {
+                println!("{}", entry.to_csv_row());
+            }
+        }
+    }
+}
+
+fn status(db: &Path, top: usize, verbose: bool) {
+    let metrics = read_saved_metrics(db);
+
+    let down = monitor::currently_down_in(&metrics);
+    println!("Currently down ({}):", down.len());
+    for entry in down {
+        println!(
+            "  {} (last checked {})",
+            entry.endpoint,
+            entry.last_check.map_or("-".to_string(), |t| t
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string())
+        );
+    }
+
+    println!("\nSlowest:");
+    for entry in monitor::top_n_slowest_in(&metrics, top) {
+        println!(
+            "  {:>8.3}s  {}",
+            entry.average_response_time, entry.endpoint
+        );
+    }
+
+    println!("\nLeast reliable:");
+    for entry in monitor::top_n_least_reliable_in(&metrics, top) {
+        println!("  {:>7.2}%  {}", entry.uptime_percentage(), entry.endpoint);
+    }
+
+    let mut budgets: Vec<_> = metrics
+        .values()
+        .filter_map(|m| Some((m.endpoint.as_str(), m.latency_budget.as_ref()?)))
+        .collect();
+    if !budgets.is_empty() {
+        budgets.sort_by(|a, b| b.1.consumed().total_cmp(&a.1.consumed()));
+        println!("\nLatency budget consumed this month:");
+        for (endpoint, budget) in budgets {
+            println!(
+                "  {:>7.1}%  p{} < {}ms  {}{}",
+                budget.consumed(),
+                budget.percentile,
+                budget.target_ms,
+                endpoint,
+                budget.history.last().map_or(String::new(), |last| format!(
+                    " ({}: {:.1}%)",
+                    last.month, last.consumed
+                ))
+            );
+        }
+    }
+
+    if verbose {
+        let recent = recent::RecentChecks::open(Path::new(recent::RECENT_CHECKS_PATH), 1);
+        let mut captured: Vec<_> = metrics
+            .iter()
+            .filter_map(|(key, m)| {
+                let last = recent.get(key).next_back()?;
+                (!last.headers.is_empty()).then_some((m.endpoint.as_str(), last))
+            })
+            .collect();
+        captured.sort_by_key(|(endpoint, _)| *endpoint);
+        if !captured.is_empty() {
+            println!("\nCaptured headers (last check):");
+            for (endpoint, check) in captured {
+                println!(
+                    "  {} ({})",
+                    endpoint,
+                    check.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+                for (name, value) in &check.headers {
+                    println!("    {}: {}", name, value);
+                }
+            }
+        }
+    }
+}
+
+// Coarse "2h" / "4m" style age for summaries.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn short_name(endpoint: &str) -> &str {
+    endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest)
+        .trim_end_matches('/')
+}
+
+const SUMMARY_MAX_CHARS: usize = 500;
+
+fn summary(db: &Path) -> String {
+    let metrics = read_saved_metrics(db);
+    let incidents = incident::load(Path::new(incident::INCIDENTS_PATH)).unwrap_or_default();
+    let now = chrono::Utc::now();
+
+    let total = metrics.len();
+    let down = monitor::currently_down_in(&metrics);
+    let up = total - down.len();
+    let percent = |n: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            n as f64 / total as f64 * 100.0
+        }
+    };
+
+    let (checks, successes) = metrics.values().fold((0, 0), |(c, s), m| {
+        (
+            c + m.total_checks,
+            s + m.successful_checks + m.maintenance_checks,
+        )
+    });
+    let fleet_uptime = if checks == 0 {
+        100.0
+    } else {
+        successes as f64 / checks as f64 * 100.0
+    };
+    let mut tail = format!(". Fleet uptime: {:.1}%.", fleet_uptime);
+    if let Some(slowest) = monitor::top_n_slowest_in(&metrics, 1).first() {
+        tail = format!(
+            "{} Slowest: {} (avg {:.1}s).",
+            tail,
+            short_name(&slowest.endpoint),
+            slowest.average_response_time
+        );
+    }
+
+    let mut line = format!(
+        "Monitoring {} endpoints: {} UP ({:.1}%), {} DOWN",
+        total,
+        up,
+        percent(up),
+        down.len()
+    );
+    if !down.is_empty() {
+        let details: Vec<String> = down
+            .iter()
+            .map(|m| {
+                let since = incidents
+                    .iter()
+                    .find(|i| i.endpoint == m.endpoint && i.closed_at.is_none())
+                    .map(|i| i.opened_at)
+                    .or(m.last_check)
+                    .unwrap_or(now);
+                format!(
+                    "{} since {} ago",
+                    short_name(&m.endpoint),
+                    format_age((now - since).to_std().unwrap_or_default())
+                )
+            })
+            .collect();
+
+        // List as many down endpoints as fit, leaving room for the rest of the line
+        let mut listed = Vec::new();
+        for (index, detail) in details.iter().enumerate() {
+            let remaining = details.len() - index - 1;
+            let more = if remaining > 0 {
+                format!(", and {} more", remaining)
+            } else {
+                String::new()
+            };
+            let candidate = [listed.clone(), vec![detail.clone()]].concat().join(", ");
+            if line.len() + candidate.len() + more.len() + tail.len() + 3 > SUMMARY_MAX_CHARS {
+                break;
+            }
+            listed.push(detail.clone());
+        }
+        let omitted = details.len() - listed.len();
+        let mut list = listed.join(", ");
+        if omitted > 0 {
+            if !list.is_empty() {
+                list.push_str(", ");
+            }
+            list.push_str(&format!("and {} more", omitted));
+        }
+        line = format!("{} ({})", line, list);
+    }
+    line + &tail
+}
+
+fn traffic(db: &Path) {
+    let metrics = read_saved_metrics(db);
+    let mut entries: Vec<&monitor::Metrics> = metrics.values().collect();
+    entries.sort_by_key(|m| std::cmp::Reverse(m.request_bytes + m.response_bytes));
+
+    println!(
+        "{:>10}  {:>10}  {:>10}  {:>10}  ENDPOINT",
+        "REQUESTS", "SENT", "RECEIVED", "TODAY"
+    );
+    for m in entries {
+        let today = chrono::Utc::now().date_naive();
+        println!(
+            "{:>10}  {:>10}  {:>10}  {:>10}  {}",
+            m.total_checks + m.transport_retries,
+            monitor::format_bytes(m.request_bytes),
+            monitor::format_bytes(m.response_bytes),
+            monitor::format_bytes(if m.traffic_day == Some(today) {
+                m.bytes_today
+            } else {
+                0
+            }),
+            m.endpoint
+        );
+    }
+}
+
+fn incidents(since: Duration) {
+    let incidents = match incident::load(Path::new(incident::INCIDENTS_PATH)) {
+        Ok(incidents) => incidents,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", incident::INCIDENTS_PATH, e);
+            process::exit(1);
+        }
+    };
+    let now = chrono::Utc::now();
+    let start = now - chrono::Duration::from_std(since).unwrap_or(chrono::TimeDelta::MAX);
+    // Whole seconds read better than humantime's default nanosecond precision
+    let format = |d: Duration| humantime::format_duration(Duration::from_secs(d.as_secs()));
+
+    for incident in incidents.iter().filter(|i| i.opened_at >= start) {
+        println!(
+            "{}  {}  {}  {}  {}  {} failed checks{}",
+            incident.id,
+            incident.opened_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            incident.endpoint,
+            if incident.closed_at.is_some() {
+                "closed"
+            } else {
+                "OPEN  "
+            },
+            format(incident.duration(now)),
+            incident.failed_checks,
+            incident
+                .last_reason
+                .as_ref()
+                .map_or(String::new(), |r| format!("  ({})", r))
+        );
+    }
+
+    let (count, mttr) = incident::summarize(&incidents, start);
+    println!(
+        "{} incidents in the last {}, MTTR {}",
+        count,
+        humantime::format_duration(since),
+        mttr.map_or("-".to_string(), |d| format(d).to_string())
+    );
+}
+
+fn deploy_start(endpoint: &str, duration: Duration) {
+    match control::start_deploy(Path::new(control::DEPLOY_WINDOWS_PATH), endpoint, duration) {
+        Ok(window) => println!(
+            "{} (alerts for {} suppressed until {})",
+            window.id,
+            window.endpoint,
+            window.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        Err(e) => {
+            eprintln!("Failed to open deploy window: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn deploy_end(id: &str) {
+    match control::end_deploy(Path::new(control::DEPLOY_WINDOWS_PATH), id) {
+        Ok(Some(window)) => println!("Closed deploy window {} for {}", window.id, window.endpoint),
+        Ok(None) => {
+            eprintln!("No open deploy window {}", id);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to close deploy window: {}", e);
+            process::exit(1);
+        }
+    }
+}
 
-    /// Request timeout in seconds
-    #[arg(short, long, default_value = "10")]
-    timeout: u64,
+fn rename_endpoint(db: &Path, old: &str, new: &str) {
+    match control::rename_endpoint(db, old, new) {
+        Ok(files) => println!("Renamed {} to {} in {}", old, new, files.join(", ")),
+        Err(e) => {
+            eprintln!("Failed to rename endpoint: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn reclassify(
+    db: &Path,
+    endpoint: &str,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+    kind: ReclassifyKind,
+    reason: &str,
+) {
+    let kind = match kind {
+        ReclassifyKind::Maintenance => "maintenance",
+    };
+    // Recorded with the incident as who made the change
+    let by = std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    match control::reclassify(db, endpoint, from, to, kind, reason, &by) {
+        Ok(report) => {
+            println!(
+                "Reclassified {} of downtime for {} as {} ({} failed checks) in incident {}",
+                humantime::format_duration(Duration::from_secs(report.downtime.as_secs())),
+                endpoint,
+                kind,
+                report.checks,
+                report.incidents.join(", ")
+            );
+            if let Some(since) = report.checks_since {
+                println!(
+                    "Checks before {} are no longer kept individually, so only the incident and downtime cover that part",
+                    since.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to reclassify downtime: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn watch(url: Option<String>, duration: Duration, name: Option<String>, list: bool) {
+    let path = Path::new(control::TEMPORARY_ENDPOINTS_PATH);
+
+    if list {
+        let now = chrono::Utc::now();
+        let endpoints = control::load_temporary_endpoints(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        let active: Vec<_> = endpoints.iter().filter(|e| !e.is_expired(now)).collect();
+        if active.is_empty() {
+            println!("No temporary endpoints");
+        }
+        for endpoint in active {
+            println!(
+                "{}{} (expires in {})",
+                endpoint.url,
+                endpoint
+                    .name
+                    .as_ref()
+                    .map_or(String::new(), |n| format!(" [{}]", n)),
+                control::format_remaining(endpoint.expires_at)
+            );
+        }
+        return;
+    }
+
+    // clap guarantees the URL is present when --list is not
+    let url = url.unwrap_or_default();
+    match control::watch(path, &url, duration, name) {
+        Ok(endpoint) => println!(
+            "Watching {} until {}",
+            endpoint.url,
+            endpoint.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        Err(e) => {
+            eprintln!("Failed to add temporary endpoint: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn record_events(out: &Path, duration: Duration) {
+    match recording::start(Path::new(recording::EVENT_RECORDING_PATH), out, duration) {
+        Ok(recording) => println!(
+            "Recording notifications to {} until {}",
+            recording.out.display(),
+            recording.until.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        Err(e) => {
+            eprintln!("Failed to start recording: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn replay_events(file: &Path, to: &str, speed: f64, retries: u32) {
+    let events = recording::load(file).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", file.display(), e);
+        process::exit(2);
+    });
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    if let Err(e) = runtime.block_on(recording::replay(&events, to, speed, retries)) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn config_diff(old: &Path, new: &Path) -> ! {
+    let load = |path: &Path| {
+        config::Config::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        })
+    };
+    let (old, new) = (load(old), load(new));
+    let changes = new.diff(&old);
+    if changes.is_empty() {
+        println!("No endpoint changes");
+        process::exit(0);
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    process::exit(1);
+}
+
+fn replay(file: &Path, rules: check::ResponseRules, expect_status: &check::StatusSet) {
+    let evaluate = || -> std::io::Result<Result<(), String>> {
+        let exchange = record::load(file)?;
+        println!(
+            "{} {} recorded at {} -> HTTP {} in {:.2}s",
+            exchange.request.method,
+            exchange.endpoint,
+            exchange.recorded_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            exchange.response.status,
+            exchange.response_time
+        );
+        Ok(check::evaluate_response(
+            exchange.response.status_code()?,
+            &exchange.response.header_map()?,
+            Some(exchange.response.body.as_bytes()),
+            &rules,
+            expect_status,
+            &rules.must_contain,
+        ))
+    };
+
+    match evaluate() {
+        Ok(Ok(())) => println!("Result: UP"),
+        Ok(Err(reason)) => {
+            println!("Result: DOWN ({})", reason);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to replay {}: {}", file.display(), e);
+            process::exit(2);
+        }
+    }
 }
 
 fn main() {
+    // Parse command line arguments
+    let mut args = Args::parse();
+
     // Initialize logging
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+    let mut filter = Targets::new().with_default(Level::INFO);
+    if args.trace_http {
+        filter = filter.with_target(monitor::HTTP_TRACE_TARGET, Level::TRACE);
+    }
+    if args.debug_storage {
+        filter = filter.with_target(writer::STORAGE_LOG_TARGET, Level::DEBUG);
+    }
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer())
+        .with(filter)
+        .init();
 
-    // Parse command line arguments
-    let args = Args::parse();
+    match args.command {
+        Some(Command::Ctl { db_path, command }) => {
+            match command {
+                CtlCommand::Dump { format } => dump(&db_path, format),
+                CtlCommand::Status { top, verbose } => status(&db_path, top, verbose),
+                CtlCommand::Watch {
+                    url,
+                    duration,
+                    name,
+                    list,
+                } => watch(url, duration, name, list),
+                CtlCommand::Incidents { since } => incidents(since),
+                CtlCommand::Traffic => traffic(&db_path),
+                CtlCommand::DeployStart { endpoint, duration } => deploy_start(&endpoint, duration),
+                CtlCommand::DeployEnd { id } => deploy_end(&id),
+                CtlCommand::RecordEvents { out, duration } => record_events(&out, duration),
+                CtlCommand::RenameEndpoint { old, new } => rename_endpoint(&db_path, &old, &new),
+                CtlCommand::Reclassify {
+                    endpoint,
+                    from,
+                    to,
+                    kind,
+                    reason,
+                } => reclassify(&db_path, &endpoint, from, to, kind, &reason),
+            }
+            return;
+        }
+        Some(Command::Replay {
+            file,
+            response_rules,
+            expect_status,
+        }) => {
+            replay(
+                &file,
+                response_rules.into(),
+                &expect_status.unwrap_or_default(),
+            );
+            return;
+        }
+        Some(Command::ReplayEvents {
+            file,
+            to,
+            speed,
+            retries,
+        }) => {
+            replay_events(&file, &to, speed, retries);
+            return;
+        }
+        Some(Command::Summary { db_path }) => {
+            println!("{}", summary(&db_path));
+            return;
+        }
+        Some(Command::ConfigDiff { old, new }) => config_diff(&old, &new),
+        None => {}
+    }
+
+    let mut config = match &args.config {
+        Some(path) => config::Config::load(path).unwrap_or_else(|e| {
+            error!("Invalid config: {}", e);
+            process::exit(2);
+        }),
+        None => config::Config::default(),
+    };
+    for endpoint in &args.endpoints {
+        match split_endpoint_interval(endpoint) {
+            Ok((url, interval)) => config.add_endpoint(url, interval),
+            Err(e) => {
+                error!("Invalid endpoint: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+    if config.endpoints.is_empty() {
+        error!(
+            "No endpoints to monitor; pass URLs or add [[endpoint]] sections to the config file"
+        );
+        process::exit(2);
+    }
+    if let Err(e) = config.check_identities() {
+        error!("Invalid config: {}", e);
+        process::exit(2);
+    }
+    args.endpoints = config.endpoints.iter().map(|e| e.url.clone()).collect();
+
+    let outcome_map = outcome::OutcomeMap::new(args.outcome_mappings);
+    let unknown: Vec<&str> = outcome_map
+        .endpoints()
+        .filter(|endpoint| !args.endpoints.iter().any(|e| e == endpoint))
+        .collect();
+    if !unknown.is_empty() {
+        error!(
+            "--map-outcome refers to endpoints that are not monitored: {}",
+            unknown.join(", ")
+        );
+        process::exit(2);
+    }
+
+    let mut styles = style::StatusStyles::default();
+    for (severity, status_style) in args.status_styles {
+        styles.set(severity, status_style);
+    }
 
     // Create runtime
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
     // Create and run monitor
     runtime.block_on(async {
-        let mut monitor = monitor::Monitor::new(
-            args.endpoints,
-            Duration::from_secs(args.interval),
-            Duration::from_secs(args.timeout),
-        );
+        if args.validate_endpoints {
+            let failures: Vec<(String, String)> =
+                futures_util::future::join_all(args.endpoints.iter().map(|e| check::resolve_endpoint(e)))
+                    .await
+                    .into_iter()
+                    .filter_map(Result::err)
+                    .collect();
+            for (host, reason) in &failures {
+                if args.allow_dns_failures {
+                    warn!("Hostname {} does not resolve: {}", host, reason);
+                } else {
+                    error!("Hostname {} does not resolve: {}", host, reason);
+                }
+            }
+            if !failures.is_empty() && !args.allow_dns_failures {
+                error!(
+                    "{} of {} endpoints failed DNS validation; fix them or pass --allow-dns-failures",
+                    failures.len(),
+                    args.endpoints.len()
+                );
+                process::exit(1);
+            }
+        }
+
+        let csv_log = args.csv_path.as_deref().map(|path| {
+            csvlog::CsvLog::open(path, args.write_queue as usize).unwrap_or_else(|e| {
+                error!("Failed to open {}: {}", path.display(), e);
+                process::exit(2);
+            })
+        });
+        let legacy = Path::new(monitor::LEGACY_METRICS_PATH);
+        let store = store::Store::open(&args.db_path, legacy, args.write_queue as usize)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to open database: {}", e);
+                process::exit(2);
+            });
+
+        let options = monitor::MonitorOptions {
+            openmetrics_path: args.export_openmetrics,
+            summary_path: args.summary_out,
+            alert_digest_interval: args.alert_digest_interval,
+            critical_endpoints: args.critical_endpoints,
+            after_hours_digest_at: args
+                .after_hours_digest_at
+                .or(args.business_hours.as_ref().map(hours::BusinessHours::start))
+                .unwrap_or_default(),
+            business_hours: args.business_hours,
+            business_timezone: args.business_timezone,
+            after_hours_page_priority: args.after_hours_page_priority,
+            quorum: args.quorum_dir.map(|dir| {
+                quorum::QuorumStore::new(
+                    dir,
+                    args.instance_id.unwrap_or_default(),
+                    args.quorum,
+                    // Reports from instances that missed a few rounds are treated as stale
+                    args.interval * 3,
+                )
+            }),
+            response_rules: args.response_rules.into(),
+            slo_target: args.slo_target,
+            slo_latency: args.slo_latency.map(|latency| latency.as_secs_f64()),
+            trace_http: args.trace_http,
+            websocket: check::WebSocketRules {
+                send: args.ws_send,
+                expect: args.ws_expect,
+                expect_json: args.ws_expect_json,
+            },
+            redact_header_patterns: args.redact_header_patterns,
+            stream: check::StreamRules {
+                endpoints: args.stream_endpoints,
+                expect: args.stream_expect,
+                min_bytes: args.stream_bytes as usize,
+            },
+            pool: monitor::PoolOptions {
+                max_idle_per_host: args.pool_max_idle_per_host,
+                idle_timeout: args.pool_idle_timeout,
+                connect_timeout: args.connect_timeout,
+                no_reuse: args.connection_close,
+                tcp_keepalive: (!args.tcp_keepalive.is_zero()).then_some(args.tcp_keepalive),
+            },
+            record_dir: args.record,
+            ip_family_timing: args.ip_family_timing,
+            max_response_body_bytes: args.max_response_body_bytes,
+            recent_checks: args.recent_checks,
+            connectivity_probes: args.connectivity_probes,
+            outcome_map,
+            styles,
+            health_weights: args.health_score_weights,
+            max_bytes_per_day: args.max_bytes_per_day,
+            throttle_over_cap: args.throttle_over_cap,
+            expected_sha256: args.expected_sha256.into_iter().collect(),
+            max_clock_skew: args.max_clock_skew.into_iter().collect(),
+            cert_expiry_warn_days: args.cert_expiry_warn_days,
+            auth: args.auth_login_url.map(|login_url| auth::AuthFlow {
+                login_url,
+                login_body: args.auth_login_body,
+                token_path: args.auth_token_path,
+                header_name: args.auth_header.0,
+                header_template: args.auth_header.1,
+                ttl: args.auth_ttl,
+                endpoints: args.auth_endpoints,
+            }),
+            latency_slos: args
+                .latency_slos
+                .into_iter()
+                .map(|slo| (slo.endpoint.clone(), slo))
+                .collect(),
+            budget_timezone: args.budget_timezone,
+            failures_before_alert: args.failures_before_alert,
+            recovery_threshold: args.recovery_threshold,
+            expect_status: args.expect_status.unwrap_or_default(),
+            method: args.method,
+            body: args.body,
+            content_type: args.content_type,
+            max_concurrent_checks: args.max_concurrent_checks as usize,
+            priorities: args.check_priorities.into_iter().collect(),
+            max_concurrent_notifications: args.max_concurrent_notifications as usize,
+            max_metrics_age: args.max_metrics_age.filter(|age| !age.is_zero()),
+            write_queue: args.write_queue as usize,
+            csv_log,
+            discord_webhook: args.discord_webhook,
+            webhook_urls: args.webhook_urls,
+            trend_alert_delta: args.trend_alert_delta,
+            range_requests: args.range_requests,
+            elasticsearch: args.elasticsearch,
+            range_bytes: args.range_bytes,
+            capture_normalize: args.capture_normalize,
+            data_dir: None,
+        };
+        let monitor = match &args.restore_snapshot {
+            Some(path) => monitor::Monitor::load_from_snapshot(
+                path,
+                config.endpoints,
+                args.interval,
+                args.timeout,
+                store,
+                options,
+            )
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                process::exit(2);
+            }),
+            None => {
+                let saved = store.load_metrics().await.unwrap_or_else(|e| {
+                    error!("{}", e);
+                    process::exit(2);
+                });
+                monitor::Monitor::new(
+                    config.endpoints,
+                    args.interval,
+                    args.timeout,
+                    store,
+                    saved,
+                    options,
+                )
+            }
+        };
+
+        let problems = monitor.schedule_problems();
+        for problem in &problems {
+            if args.strict {
+                error!("Inconsistent schedule: {}", problem);
+            } else {
+                warn!("Inconsistent schedule: {}", problem);
+            }
+        }
+        if args.strict && !problems.is_empty() {
+            process::exit(1);
+        }
+        if args.selftest {
+            selftest(
+                &monitor,
+                &args.endpoints,
+                problems.len(),
+                args.timeout,
+                args.serve_addr,
+                args.metrics_addr,
+                args.metrics_socket.as_deref(),
+            )
+            .await;
+        }
+
+        let publisher = match sns::SnsPublisher::from_env() {
+            Ok(Some(publisher)) => Some(tokio::spawn(publisher.run(
+                monitor.event_sender().subscribe(),
+                monitor.notification_slots(),
+            ))),
+            Ok(None) => None,
+            Err(e) => {
+                error!("SNS_TOPIC_ARN is set but SNS is not configured: {}", e);
+                process::exit(2);
+            }
+        };
+        // Before the HTTP servers start, so the monitor holds the only event
+        // sender and dropping it lets the publisher finish
+        if args.once {
+            monitor.check_once_and_exit(publisher).await;
+        }
+
+        let state = server::AppState {
+            events: monitor.event_sender(),
+            metrics: monitor.metrics_snapshot(),
+            basic_auth: args.serve_auth,
+            rate_limiter: args.api_rate_limit.map(|per_second| {
+                Arc::new(ratelimit::RateLimiter::new(per_second, args.trusted_ips))
+            }),
+        };
+        if let Some(addr) = args.serve_addr {
+            let listener = match TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to bind HTTP server to {}: {}", addr, e);
+                    process::exit(1);
+                }
+            };
+            tokio::spawn(server::serve(listener, state.clone()));
+        }
+        if let Some(addr) = args.metrics_addr {
+            let listener = match TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to bind metrics server to {}: {}", addr, e);
+                    process::exit(1);
+                }
+            };
+            tokio::spawn(server::serve_metrics(listener, state.clone()));
+        }
+        if let Some(path) = &args.metrics_socket {
+            let listener = match bind_unix_socket(path) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to bind HTTP server to {}: {}", path.display(), e);
+                    process::exit(1);
+                }
+            };
+            tokio::spawn(server::serve_unix(listener, state));
+        }
 
-        monitor.run().await;
+        if let Err(e) = monitor.run_in_background().await {
+            error!("Monitor task failed: {}", e);
+            process::exit(1);
+        }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://api.example.com/health";
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn parse_duration_accepts_bare_seconds() {
+        for (value, expected) in [
+            ("0", secs(0.0)),
+            ("90", secs(90.0)),
+            ("0.5", secs(0.5)),
+            ("1.25", secs(1.25)),
+            ("+30", secs(30.0)),
+            ("1e3", secs(1000.0)),
+            ("86400", secs(86400.0)),
+        ] {
+            assert_eq!(parse_duration(value), Ok(expected), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_duration_accepts_humantime() {
+        for (value, expected) in [
+            ("500ms", secs(0.5)),
+            ("30s", secs(30.0)),
+            ("1m", secs(60.0)),
+            ("1m30s", secs(90.0)),
+            ("1m 30s", secs(90.0)),
+            ("2h", secs(7200.0)),
+            ("1h30m", secs(5400.0)),
+            ("30d", secs(30.0 * 86400.0)),
+            ("1w", secs(7.0 * 86400.0)),
+            ("2min", secs(120.0)),
+            ("3 hours", secs(3.0 * 3600.0)),
+            ("1.5h", secs(5400.0)),
+            ("10us", Duration::from_micros(10)),
+        ] {
+            assert_eq!(parse_duration(value), Ok(expected), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_seconds() {
+        for value in ["-1", "-0.5", "NaN", "inf", "1e30"] {
+            assert_eq!(
+                parse_duration(value),
+                Err(format!("'{}' is not a valid number of seconds", value)),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_other_text_showing_the_accepted_formats() {
+        for value in ["", " ", "soon", "10x", "m", "1m30", "1 2 3", "--5"] {
+            assert_eq!(
+                parse_duration(value),
+                Err(format!(
+                    "'{}' is not a duration; use seconds (e.g. 90) or units like 1m30s, 500ms, 2h",
+                    value
+                )),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn duration_flags_take_both_forms() {
+        let args = Args::try_parse_from([
+            "uptime",
+            "--interval",
+            "1m30s",
+            "--timeout",
+            "2.5",
+            "--connect-timeout",
+            "500ms",
+            URL,
+        ])
+        .unwrap();
+        assert_eq!(args.interval, secs(90.0));
+        assert_eq!(args.timeout, secs(2.5));
+        assert_eq!(args.connect_timeout, Some(secs(0.5)));
+
+        let defaults = Args::try_parse_from(["uptime", URL]).unwrap();
+        assert_eq!(defaults.interval, secs(60.0));
+        assert_eq!(defaults.timeout, secs(10.0));
+        assert_eq!(defaults.connect_timeout, None);
+    }
+
+    #[test]
+    fn duration_flag_errors_name_the_flag_and_the_formats() {
+        for flag in ["--interval", "--timeout", "--connect-timeout"] {
+            let error = Args::try_parse_from(["uptime", flag, "soon", URL])
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains(flag), "{}", error);
+            assert!(
+                error.contains("use seconds (e.g. 90) or units like 1m30s, 500ms, 2h"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn body_flags_take_text_and_patterns() {
+        let args = Args::try_parse_from([
+            "uptime",
+            "--expect-body",
+            "ok",
+            "--must-contain",
+            "db",
+            "--expect-body-regex",
+            "v\\d+",
+            URL,
+        ])
+        .unwrap();
+        let rules = check::ResponseRules::from(args.response_rules);
+        assert_eq!(rules.must_contain, ["ok", "db"]);
+        assert_eq!(rules.must_match[0].as_str(), "v\\d+");
+
+        let error = Args::try_parse_from(["uptime", "--expect-body-regex", "(", URL]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn metrics_listener_defaults_to_localhost() {
+        let addr = |value| {
+            Args::try_parse_from(["uptime", "--metrics-addr", value, URL])
+                .map(|args| args.metrics_addr.unwrap().to_string())
+                .map_err(|e| e.kind())
+        };
+        assert_eq!(addr("9100"), Ok("127.0.0.1:9100".to_string()));
+        assert_eq!(addr("0.0.0.0:9100"), Ok("0.0.0.0:9100".to_string()));
+        assert_eq!(addr("[::]:9100"), Ok("[::]:9100".to_string()));
+        assert_eq!(
+            addr("all:9100"),
+            Err(clap::error::ErrorKind::ValueValidation)
+        );
+
+        // The old flag name still works, with the new default
+        let args = Args::try_parse_from(["uptime", "--metrics-port", "9100", URL]).unwrap();
+        assert_eq!(
+            args.metrics_addr,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9100)))
+        );
+    }
+
+    #[test]
+    fn endpoint_arguments_take_an_interval_suffix() {
+        assert_eq!(
+            split_endpoint_interval(&format!("{}=30", URL)),
+            Ok((URL.to_string(), Some(secs(30.0))))
+        );
+        assert_eq!(
+            split_endpoint_interval(&format!("{}=1m30s", URL)),
+            Ok((URL.to_string(), Some(secs(90.0))))
+        );
+        assert_eq!(split_endpoint_interval(URL), Ok((URL.to_string(), None)));
+        // The value of a bare query key stays part of the URL
+        assert_eq!(
+            split_endpoint_interval("https://a.com/?page=2"),
+            Ok(("https://a.com/?page=2".to_string(), None))
+        );
+        assert_eq!(
+            split_endpoint_interval("https://a.com/?page=2=30s"),
+            Ok(("https://a.com/?page=2".to_string(), Some(secs(30.0))))
+        );
+        // Not a duration, so not an interval
+        assert_eq!(
+            split_endpoint_interval("https://a.com/?q=soon"),
+            Ok(("https://a.com/?q=soon".to_string(), None))
+        );
+        assert_eq!(
+            split_endpoint_interval(&format!("{}=0", URL)),
+            Err(format!("{}: interval must be greater than zero", URL))
+        );
+    }
+
+    #[test]
+    fn clock_skew_limits_are_durations_of_at_least_a_second() {
+        assert_eq!(
+            parse_clock_skew(&format!("{}=2m", URL)),
+            Ok((URL.to_string(), secs(120.0)))
+        );
+        assert_eq!(
+            parse_clock_skew(&format!("{}=1", URL)),
+            Ok((URL.to_string(), secs(1.0)))
+        );
+        assert_eq!(
+            parse_clock_skew(&format!("{}=500ms", URL)),
+            Err("the skew limit must be at least 1s".to_string())
+        );
+        assert!(parse_clock_skew(&format!("{}=soon", URL))
+            .unwrap_err()
+            .contains("is not a duration"));
+        assert_eq!(parse_clock_skew(URL).unwrap_err(), "expected URL=DURATION");
+    }
+
+    #[test]
+    fn config_durations_read_like_the_flags() {
+        let config: config::Config = toml::from_str(&format!(
+            r#"
+            [[endpoint]]
+            url = "{URL}"
+            interval = 30
+            timeout = 0.5
+
+            [[endpoint]]
+            url = "https://b.example.com"
+            interval = "1m30s"
+            timeout = "2"
+            "#
+        ))
+        .unwrap();
+        let durations: Vec<_> = config
+            .endpoints
+            .iter()
+            .map(|e| (e.interval, e.timeout))
+            .collect();
+        assert_eq!(
+            durations,
+            [
+                (Some(secs(30.0)), Some(secs(0.5))),
+                (Some(secs(90.0)), Some(secs(2.0)))
+            ]
+        );
+
+        let error = toml::from_str::<config::Config>(&format!(
+            "[[endpoint]]\nurl = \"{URL}\"\ninterval = \"soon\"\n"
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("'soon' is not a duration"), "{}", error);
+        assert!(toml::from_str::<config::Config>(&format!(
+            "[[endpoint]]\nurl = \"{URL}\"\ninterval = -5\n"
+        ))
+        .is_err());
+    }
+}
@@ -1,31 +1,206 @@
-use chrono::{DateTime, Utc};
-use colored::*;
-use reqwest::Client;
+use arc_swap::ArcSwap;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use futures_util::{
+    future::join_all,
+    stream::{FuturesUnordered, StreamExt},
+};
+use regex::Regex;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Method, Request, StatusCode,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
-    fs::{self, File},
-    io::Write,
-    path::Path,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
     time::{Duration, Instant},
 };
-use tokio::time::sleep;
-use tracing::{error, info};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{broadcast, Semaphore},
+    task::JoinHandle,
+    time::sleep_until,
+};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    auth::{AuthFlow, TokenCache},
+    budget::{LatencyBudget, LatencySlo},
+    check::{
+        self, CheckResult, ClusterHealth, ResponseRules, StatusSet, StreamRules, WebSocketRules,
+    },
+    config::EndpointConfig,
+    control::{self, DeployWindow, TemporaryEndpoint},
+    csvlog::CsvLog,
+    error::UptimeError,
+    history::{self, CheckHistory},
+    hours::{self, AfterHours, BusinessHours},
+    incident::{self, IncidentLog},
+    notify::{self, DigestEntry, Notifier, StatusChange},
+    outcome::{Outcome, OutcomeMap},
+    priority::Priority,
+    prometheus,
+    quorum::{self, QuorumStore},
+    recent::{CheckRecord, RecentChecks, RECENT_CHECKS_PATH},
+    record::{self, RecordedExchange, RecordedRequest, RecordedResponse},
+    recording, selftest,
+    slo::SloTracker,
+    snapshot,
+    store::{CheckRow, Store},
+    style::{Severity, StatusStyles},
+    trend::{self, TrendTracker},
+    writer::{WriteKind, Writer},
+};
+
+/// Target used for `--trace-http` logging so it can be enabled on its own.
+pub const HTTP_TRACE_TARGET: &str = "uptime::http";
+const TRACE_BODY_BYTES: usize = 512;
+
+/// Metrics file written by earlier versions, imported into the database once.
+pub const LEGACY_METRICS_PATH: &str = "metrics/uptime_metrics.json";
+const PROMETHEUS_DUMP_PATH: &str = "metrics/uptime_metrics.prom";
+
+/// Entries per list in the status overview logged on `SIGUSR2`
+const STATUS_LOG_TOP: usize = 5;
+/// Successful checks per endpoint that response time quantiles are taken over
+const RESPONSE_SAMPLES: usize = 100;
+/// How long `--once` waits for the event publisher to send what is queued
+const ONCE_PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Columns of `ctl dump --format csv`: every field of [`Metrics`], as serde
+/// names them, in declaration order.
+const CSV_COLUMNS: &[&str] = &[
+    "endpoint",
+    "total_checks",
+    "successful_checks",
+    "failed_checks",
+    "total_downtime",
+    "last_check",
+    "last_status",
+    "average_response_time",
+    "request_bytes",
+    "response_bytes",
+    "transport_retries",
+    "ipv4_connect_time",
+    "ipv6_connect_time",
+    "last_outcome",
+    "last_status_code",
+    "draining_checks",
+    "last_response_time",
+    "recent_error_rate",
+    "health_score",
+    "deploy_downtime",
+    "maintenance_checks",
+    "maintenance_downtime",
+    "bytes_today",
+    "traffic_day",
+    "range_supported",
+    "latency_budget",
+    "priority",
+    "consecutive_failures",
+    "consecutive_successes",
+    "clock_skew",
+    "cert_expiry",
+    "uptime_windows",
+    "scheduling_delay",
+    "response_quantiles",
+    "cluster_health",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metrics {
-    endpoint: String,
-    total_checks: u64,
-    successful_checks: u64,
-    failed_checks: u64,
-    total_downtime: u64,
-    last_check: Option<DateTime<Utc>>,
-    last_status: Option<String>,
-    average_response_time: f64,
+    pub(crate) endpoint: String,
+    pub(crate) total_checks: u64,
+    pub(crate) successful_checks: u64,
+    pub(crate) failed_checks: u64,
+    pub(crate) total_downtime: u64,
+    pub(crate) last_check: Option<DateTime<Utc>>,
+    /// Confirmed status, which only flips once `--failure-threshold` or
+    /// `--recovery-threshold` checks in a row agree
+    pub(crate) last_status: Option<String>,
+    pub(crate) average_response_time: f64,
+    #[serde(default)]
+    pub(crate) request_bytes: u64,
+    #[serde(default)]
+    pub(crate) response_bytes: u64,
+    #[serde(default)]
+    pub(crate) transport_retries: u64,
+    #[serde(default)]
+    pub(crate) ipv4_connect_time: Option<f64>,
+    #[serde(default)]
+    pub(crate) ipv6_connect_time: Option<f64>,
+    /// Raw outcome of the last check, before any outcome mapping
+    #[serde(default)]
+    pub(crate) last_outcome: Option<String>,
+    /// HTTP status of the last check's response, if it got one
+    #[serde(default)]
+    pub(crate) last_status_code: Option<u16>,
+    /// Checks whose response carried the endpoint's `drain_header`
+    #[serde(default)]
+    pub(crate) draining_checks: u64,
+    #[serde(default)]
+    pub(crate) last_response_time: Option<f64>,
+    /// Exponentially weighted share of recent checks that failed (0.0-1.0)
+    #[serde(default)]
+    pub(crate) recent_error_rate: f64,
+    #[serde(default)]
+    pub(crate) health_score: Option<f64>,
+    /// Part of `total_downtime` that fell inside deploy windows
+    #[serde(default)]
+    pub(crate) deploy_downtime: u64,
+    /// Failed checks reclassified as maintenance with `ctl reclassify`; they
+    /// count as up in the uptime percentage
+    #[serde(default)]
+    pub(crate) maintenance_checks: u64,
+    /// Seconds moved out of `total_downtime` by `ctl reclassify`
+    #[serde(default)]
+    pub(crate) maintenance_downtime: u64,
+    /// Request plus response bytes exchanged on `traffic_day` (UTC)
+    #[serde(default)]
+    pub(crate) bytes_today: u64,
+    #[serde(default)]
+    pub(crate) traffic_day: Option<NaiveDate>,
+    /// Whether the last range request was answered with the right 206
+    #[serde(default)]
+    pub(crate) range_supported: Option<bool>,
+    /// This month's latency budget, for endpoints with a `--latency-slo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) latency_budget: Option<LatencyBudget>,
+    #[serde(default)]
+    pub(crate) priority: Priority,
+    /// Failed checks in a row, reset by any successful check
+    #[serde(default)]
+    pub(crate) consecutive_failures: u64,
+    /// Successful checks in a row, reset by any failed check
+    #[serde(default)]
+    pub(crate) consecutive_successes: u64,
+    /// Seconds the endpoint's clock was ahead of ours at the last check, for `--max-clock-skew`
+    #[serde(default)]
+    pub(crate) clock_skew: Option<f64>,
+    /// When the endpoint's TLS certificate expires, for `--cert-expiry-warn-days`
+    #[serde(default)]
+    pub(crate) cert_expiry: Option<DateTime<Utc>>,
+    /// Uptime percentage over the last 1h, 24h, 7d and 30d, from the check history
+    #[serde(default)]
+    pub(crate) uptime_windows: BTreeMap<String, f64>,
+    /// Seconds the last check waited past its due time before starting
+    #[serde(default)]
+    pub(crate) scheduling_delay: Option<f64>,
+    /// Median, p90 and p99 response time of recent successful checks, keyed `0.5`, `0.9`, `0.99`
+    #[serde(default)]
+    pub(crate) response_quantiles: BTreeMap<String, f64>,
+    /// Last cluster health of an `--elasticsearch` endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cluster_health: Option<ClusterHealth>,
 }
 
 impl Metrics {
-    fn new(endpoint: String) -> Self {
+    pub(crate) fn new(endpoint: String) -> Self {
         Self {
             endpoint,
             total_checks: 0,
@@ -35,6 +210,528 @@ impl Metrics {
             last_check: None,
             last_status: None,
             average_response_time: 0.0,
+            request_bytes: 0,
+            response_bytes: 0,
+            transport_retries: 0,
+            ipv4_connect_time: None,
+            ipv6_connect_time: None,
+            last_outcome: None,
+            last_status_code: None,
+            draining_checks: 0,
+            last_response_time: None,
+            recent_error_rate: 0.0,
+            health_score: None,
+            deploy_downtime: 0,
+            maintenance_checks: 0,
+            maintenance_downtime: 0,
+            bytes_today: 0,
+            traffic_day: None,
+            range_supported: None,
+            latency_budget: None,
+            priority: Priority::Normal,
+            scheduling_delay: None,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            clock_skew: None,
+            cert_expiry: None,
+            uptime_windows: BTreeMap::new(),
+            response_quantiles: BTreeMap::new(),
+            cluster_health: None,
+        }
+    }
+
+    /// Composite 0-100 score, the weighted mean of three 0-100 components:
+    /// - uptime: percentage of successful checks
+    /// - latency: 100 × average / last response time, capped at 100, so a check
+    ///   twice as slow as its baseline scores 50
+    /// - errors: 100 × (1 − recent error rate)
+    pub(crate) fn compute_health_score(&self, weights: &HealthWeights) -> f64 {
+        let uptime = self.uptime_percentage();
+        let latency = match self.last_response_time {
+            Some(last) if last > 0.0 && self.average_response_time > 0.0 => {
+                (self.average_response_time / last * 100.0).min(100.0)
+            }
+            _ => 100.0,
+        };
+        let errors = (1.0 - self.recent_error_rate) * 100.0;
+
+        let total = weights.uptime + weights.latency + weights.errors;
+        (uptime * weights.uptime + latency * weights.latency + errors * weights.errors) / total
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.request_bytes + self.response_bytes
+    }
+
+    /// Confirmed status after a check with the given result. The first check
+    /// sets it directly; after that it only flips once `failure_threshold`
+    /// checks in a row failed or `recovery_threshold` in a row succeeded.
+    pub(crate) fn next_status(
+        &self,
+        success: bool,
+        failure_threshold: u64,
+        recovery_threshold: u64,
+    ) -> &'static str {
+        match (self.last_status.as_deref(), success) {
+            (Some("up"), false) if self.consecutive_failures + 1 < failure_threshold => "up",
+            (Some("down"), true) if self.consecutive_successes + 1 < recovery_threshold => "down",
+            (_, true) => "up",
+            (_, false) => "down",
+        }
+    }
+
+    /// Percentage of successful checks, counting reclassified maintenance as
+    /// up; 100 before the first check.
+    pub(crate) fn uptime_percentage(&self) -> f64 {
+        if self.total_checks == 0 {
+            100.0
+        } else {
+            (self.successful_checks + self.maintenance_checks) as f64 / self.total_checks as f64
+                * 100.0
+        }
+    }
+
+    pub fn csv_header() -> &'static str {
+        static HEADER: LazyLock<String> = LazyLock::new(|| CSV_COLUMNS.join(","));
+        HEADER.as_str()
+    }
+
+    /// Serializes every field in the column order of [`Metrics::csv_header`]:
+    /// numbers and text as they are, missing values as empty cells and the
+    /// structured fields (budget, windows, quantiles, cluster health) as JSON.
+    pub fn to_csv_row(&self) -> String {
+        let mut fields = match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("metrics serialize to a JSON object"),
+        };
+        CSV_COLUMNS
+            .iter()
+            .map(|column| match fields.remove(*column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => csv_escape(&text),
+                Some(value @ (Value::Array(_) | Value::Object(_))) => {
+                    csv_escape(&value.to_string())
+                }
+                Some(value) => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// The status queries behind `Monitor::top_n_slowest` and friends, over any
+// metrics map, e.g. one read back from the data directory by `ctl status`.
+
+pub(crate) fn top_n_slowest_in(metrics: &HashMap<String, Metrics>, n: usize) -> Vec<&Metrics> {
+    let mut entries: Vec<&Metrics> = metrics.values().collect();
+    entries.sort_by(|a, b| b.average_response_time.total_cmp(&a.average_response_time));
+    entries.truncate(n);
+    entries
+}
+
+pub(crate) fn top_n_least_reliable_in(
+    metrics: &HashMap<String, Metrics>,
+    n: usize,
+) -> Vec<&Metrics> {
+    let mut entries: Vec<&Metrics> = metrics.values().collect();
+    entries.sort_by(|a, b| a.uptime_percentage().total_cmp(&b.uptime_percentage()));
+    entries.truncate(n);
+    entries
+}
+
+pub(crate) fn currently_down_in(metrics: &HashMap<String, Metrics>) -> Vec<&Metrics> {
+    let mut entries: Vec<&Metrics> = metrics
+        .values()
+        .filter(|m| m.last_status.as_deref() == Some("down"))
+        .collect();
+    entries.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+    entries
+}
+
+// Quotes a field when it contains a delimiter, quote or line break (RFC 4180).
+pub(crate) fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MonitorEvent {
+    Check {
+        endpoint: String,
+        success: bool,
+        response_time: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failure_reason: Option<String>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        timestamp: DateTime<Utc>,
+    },
+    Transition {
+        endpoint: String,
+        status: String,
+        severity: Severity,
+        timestamp: DateTime<Utc>,
+    },
+    /// Timeline marker, e.g. a deploy window opening or closing
+    Annotation {
+        endpoint: String,
+        message: String,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+impl MonitorEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MonitorEvent::Check { .. } => "check",
+            MonitorEvent::Transition { .. } => "transition",
+            MonitorEvent::Annotation { .. } => "annotation",
+        }
+    }
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Nearest-rank quantiles of `samples`, keyed as in Prometheus' `quantile` label.
+fn response_quantiles(samples: &VecDeque<f64>) -> BTreeMap<String, f64> {
+    let mut sorted: Vec<f64> = samples.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    [("0.5", 0.5), ("0.9", 0.9), ("0.99", 0.99)]
+        .iter()
+        .filter_map(|&(label, quantile)| {
+            let rank = ((quantile * sorted.len() as f64).ceil() as usize).max(1);
+            Some((label.to_string(), *sorted.get(rank - 1)?))
+        })
+        .collect()
+}
+
+/// Response times of the last successful checks among `checks`, so the
+/// quantile window resumes where it was after a restart.
+fn saved_response_samples<'a>(
+    checks: impl DoubleEndedIterator<Item = &'a CheckRecord>,
+) -> VecDeque<f64> {
+    let mut samples: VecDeque<f64> = checks
+        .rev()
+        .filter(|check| check.success)
+        .take(RESPONSE_SAMPLES)
+        .map(|check| check.response_time_ms as f64 / 1000.0)
+        .collect();
+    samples.make_contiguous().reverse();
+    samples
+}
+
+/// Relative weights of the health score components; they need not sum to 1.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthWeights {
+    pub uptime: f64,
+    pub latency: f64,
+    pub errors: f64,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            uptime: 0.6,
+            latency: 0.25,
+            errors: 0.15,
+        }
+    }
+}
+
+/// Connection and pool settings for the HTTP client used by checks.
+#[derive(Debug)]
+pub struct PoolOptions {
+    pub max_idle_per_host: usize,
+    /// Kept below common server keep-alive timeouts so the pool drops
+    /// connections before the server does
+    pub idle_timeout: Duration,
+    pub tcp_keepalive: Option<Duration>,
+    /// Separate limit for establishing a connection, within the overall timeout
+    pub connect_timeout: Option<Duration>,
+    /// Endpoints checked over a fresh connection each time (`Connection: close`),
+    /// for infrequent checks where an idle pooled connection only costs the server
+    pub no_reuse: Vec<String>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 4,
+            idle_timeout: Duration::from_secs(30),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            connect_timeout: None,
+            no_reuse: Vec::new(),
+        }
+    }
+}
+
+/// The `--summary-out` file: how one `--once` run went.
+#[derive(Serialize)]
+struct RunSummary<'a> {
+    finished_at: DateTime<Utc>,
+    exit_code: i32,
+    up: usize,
+    down: usize,
+    endpoints: Vec<RunResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct RunResult<'a> {
+    endpoint: &'a str,
+    name: Option<&'a str>,
+    status: &'static str,
+    /// Seconds
+    response_time: f64,
+    outcome: String,
+    failure_reason: Option<&'a str>,
+}
+
+/// Optional behaviour that is off unless explicitly configured.
+#[derive(Debug, Default)]
+pub struct MonitorOptions {
+    /// Keep the check history, recent checks, incidents and trend files here
+    /// instead of under `metrics/` in the working directory
+    pub data_dir: Option<PathBuf>,
+    /// Write an OpenMetrics snapshot to this path after every check round
+    pub openmetrics_path: Option<PathBuf>,
+    /// Write a JSON summary of the run here before `check_once_and_exit` exits
+    pub summary_path: Option<PathBuf>,
+    /// Batch status changes into a single digest sent at most this often
+    pub alert_digest_interval: Option<Duration>,
+    /// Endpoints whose alerts bypass the digest and are sent immediately
+    pub critical_endpoints: Vec<String>,
+    /// Outside these hours, alerts that don't page are held for a digest
+    pub business_hours: Option<BusinessHours>,
+    /// Timezone of `business_hours` and `after_hours_digest_at`
+    pub business_timezone: Tz,
+    /// Local time the alerts held after hours are sent
+    pub after_hours_digest_at: NaiveTime,
+    /// Endpoints of at least this priority still page after hours
+    pub after_hours_page_priority: Priority,
+    /// Only alert once this many instances sharing the store agree an endpoint is down
+    pub quorum: Option<QuorumStore>,
+    /// Rules for recognising error pages served by intermediaries
+    pub response_rules: ResponseRules,
+    /// Availability SLO target in percent; enables burn-rate alerting
+    pub slo_target: Option<f64>,
+    /// Successful checks slower than this many seconds count against the SLO
+    pub slo_latency: Option<f64>,
+    /// Log raw request/response headers and the start of each body at TRACE level
+    pub trace_http: bool,
+    /// Application-level round trip for `ws://` and `wss://` endpoints
+    pub websocket: WebSocketRules,
+    /// Captured header values matching any of these are redacted
+    pub redact_header_patterns: Vec<Regex>,
+    /// Endpoints checked by reading only the start of their stream
+    pub stream: StreamRules,
+    pub pool: PoolOptions,
+    /// Save every check's raw HTTP exchange into this directory
+    pub record_dir: Option<PathBuf>,
+    /// Measure TCP connect time separately over IPv4 and IPv6 for HTTP endpoints
+    pub ip_family_timing: bool,
+    /// Stop reading a response body after this many bytes; rules see the truncated body
+    pub max_response_body_bytes: usize,
+    /// Individual checks kept per endpoint in the recent checks file
+    pub recent_checks: usize,
+    /// Reference `host:port` targets; when none is reachable the round is skipped
+    pub connectivity_probes: Vec<String>,
+    /// Per-endpoint overrides of which raw outcomes count as up
+    pub outcome_map: OutcomeMap,
+    /// Emoji, label and color per severity for console and notification output
+    pub styles: StatusStyles,
+    pub health_weights: HealthWeights,
+    /// Soft cap on probe traffic per endpoint per UTC day
+    pub max_bytes_per_day: Option<u64>,
+    /// Endpoint -> lowercase hex SHA-256 its body must hash to
+    pub expected_sha256: HashMap<String, String>,
+    /// Endpoint -> largest tolerated difference between its `Date` header and our clock
+    pub max_clock_skew: HashMap<String, Duration>,
+    /// Alert when an HTTPS endpoint's certificate expires within this many days
+    pub cert_expiry_warn_days: Option<u32>,
+    /// Check endpoints over the daily cap only every tenth round
+    pub throttle_over_cap: bool,
+    /// Log in and send the returned token with checks
+    pub auth: Option<AuthFlow>,
+    /// Per-endpoint latency objectives metered against a monthly budget
+    pub latency_slos: HashMap<String, LatencySlo>,
+    /// Timezone whose months the latency budgets follow
+    pub budget_timezone: Tz,
+    /// Consecutive failed checks needed before an endpoint is reported down
+    pub failures_before_alert: u64,
+    /// Consecutive successful checks needed before a down endpoint is reported up again
+    pub recovery_threshold: u64,
+    /// Statuses that count as up for endpoints without their own; any 2xx when empty
+    pub expect_status: StatusSet,
+    /// Method of endpoints without their own
+    pub method: Method,
+    /// Sent as the body of every check
+    pub body: Option<String>,
+    /// Content-Type sent with `body`
+    pub content_type: Option<String>,
+    /// Upper bound on checks in flight at once
+    pub max_concurrent_checks: usize,
+    /// Per-endpoint overrides of the dispatch priority
+    pub priorities: HashMap<String, Priority>,
+    /// Upper bound on notifications in flight at once, across all notifiers
+    pub max_concurrent_notifications: usize,
+    /// Save every endpoint's metrics at least this often, even when nothing changed
+    pub max_metrics_age: Option<Duration>,
+    /// Pending storage writes before routine file updates are dropped
+    pub write_queue: usize,
+    /// Append a row per check to this CSV file
+    pub csv_log: Option<CsvLog>,
+    /// Alert when rolling uptime drops by more than this many percentage points
+    pub trend_alert_delta: Option<f64>,
+    /// Discord webhook every notification is also posted to
+    pub discord_webhook: Option<String>,
+    /// Generic webhooks every notification is also posted to as JSON
+    pub webhook_urls: Vec<String>,
+    /// Endpoints whose body is an Elasticsearch `/_cluster/health` answer
+    pub elasticsearch: Vec<String>,
+    /// Endpoints checked with a `Range` request that must be answered with a 206
+    pub range_requests: Vec<String>,
+    /// Size of the range requested from `range_requests` endpoints
+    pub range_bytes: u64,
+    /// Stripped from captures before comparing consecutive failures
+    pub capture_normalize: Vec<Regex>,
+}
+
+impl MonitorOptions {
+    /// Where the file the monitor keeps at `default` lives with `data_dir`.
+    fn data_path(&self, default: &str) -> PathBuf {
+        let default = Path::new(default);
+        match (&self.data_dir, default.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => default.to_path_buf(),
+        }
+    }
+}
+
+// Writes to a temporary file first so readers never see a partial file.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Point-in-time copy of every endpoint's metrics shared with readers such as
+/// the HTTP server. The check loop swaps in a whole new map, so readers never
+/// lock and never see an endpoint entry half-updated.
+pub type MetricsSnapshot = Arc<ArcSwap<HashMap<String, Metrics>>>;
+
+/// What a monitor resumes from on startup, so a restarted monitor carries on
+/// as if it had kept running: the counters, status and failure streaks saved
+/// for each endpoint, the response time samples behind its quantiles, and the
+/// clock skew and certificate expiry alerts already sent.
+pub struct MonitorState {
+    /// Metrics of each monitored endpoint, by URL
+    metrics: HashMap<String, Metrics>,
+    response_samples: HashMap<String, VecDeque<f64>>,
+    clock_skewed: HashSet<String>,
+    cert_expiry_warned: HashMap<String, DateTime<Utc>>,
+    /// Keys of saved endpoints that are no longer monitored
+    dropped: Vec<String>,
+}
+
+impl MonitorState {
+    /// Rebuilds the state of `endpoints` from `saved`, their metrics keyed as
+    /// stored, and their recent checks. Saved endpoints that are no longer
+    /// monitored are left out and listed in `dropped`.
+    fn restore(
+        endpoints: &[String],
+        stored_keys: &HashMap<String, String>,
+        mut saved: HashMap<String, Metrics>,
+        recent: &RecentChecks,
+        options: &MonitorOptions,
+    ) -> Self {
+        let key = |endpoint: &String| stored_keys.get(endpoint).unwrap_or(endpoint).clone();
+        let metrics: HashMap<String, Metrics> = endpoints
+            .iter()
+            .map(|endpoint| {
+                let metrics = match saved.remove(&key(endpoint)) {
+                    // Stored under its identity, possibly from before a URL edit
+                    Some(metrics) => Metrics {
+                        endpoint: endpoint.clone(),
+                        ..metrics
+                    },
+                    None => Metrics::new(endpoint.clone()),
+                };
+                (endpoint.clone(), metrics)
+            })
+            .collect();
+        let restored = metrics.values().filter(|m| m.total_checks > 0).count();
+        if restored > 0 {
+            info!("Restored metrics for {} endpoints", restored);
+        }
+        let dropped: Vec<String> = saved.into_keys().collect();
+        for endpoint in &dropped {
+            info!(
+                "Dropping saved metrics for {}, which is no longer monitored \
+                 (ctl rename-endpoint carries them over to a changed URL or identity)",
+                endpoint
+            );
+        }
+
+        let response_samples = endpoints
+            .iter()
+            .map(|endpoint| {
+                (
+                    endpoint.clone(),
+                    saved_response_samples(recent.get(&key(endpoint))),
+                )
+            })
+            .collect();
+        let clock_skewed = metrics
+            .iter()
+            .filter(|(endpoint, m)| {
+                let limit = options.max_clock_skew.get(*endpoint);
+                m.clock_skew
+                    .zip(limit)
+                    .is_some_and(|(skew, limit)| skew.abs() > limit.as_secs_f64())
+            })
+            .map(|(endpoint, _)| endpoint.clone())
+            .collect();
+        let cert_expiry_warned = options
+            .cert_expiry_warn_days
+            .map(|days| {
+                let warn_from = Utc::now() + chrono::Duration::days(i64::from(days));
+                metrics
+                    .iter()
+                    .filter_map(|(endpoint, m)| {
+                        let expiry = m.cert_expiry.filter(|&expiry| expiry <= warn_from)?;
+                        Some((endpoint.clone(), expiry))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            metrics,
+            response_samples,
+            clock_skewed,
+            cert_expiry_warned,
+            dropped,
         }
     }
 }
@@ -45,22 +742,130 @@ pub struct Monitor {
     timeout: Duration,
     metrics: HashMap<String, Metrics>,
     client: Client,
-    slack_webhook_url: Option<String>,
+    /// Chat webhooks every notification is posted to
+    notifiers: Vec<Box<dyn Notifier>>,
+    events: broadcast::Sender<MonitorEvent>,
+    /// Shared with the other notifiers so an outage across many endpoints
+    /// can't fan out into a burst of webhook calls
+    notification_slots: Arc<Semaphore>,
+    options: MonitorOptions,
+    digest: Vec<DigestEntry>,
+    /// Alerts held outside business hours
+    after_hours_digest: Vec<DigestEntry>,
+    /// When the held alerts are sent
+    after_hours_digest_due: Option<DateTime<Utc>>,
+    last_digest: Instant,
+    quorum_down: HashSet<String>,
+    slo: Option<SloTracker>,
+    trend: Option<TrendTracker>,
+    history: CheckHistory,
+    recent: RecentChecks,
+    /// Response times of each endpoint's most recent successful checks
+    response_samples: HashMap<String, VecDeque<f64>>,
+    temporary: HashMap<String, DateTime<Utc>>,
+    connectivity_lost: bool,
+    snapshot: MetricsSnapshot,
+    /// Open deploy windows by endpoint
+    deploys: HashMap<String, DeployWindow>,
+    incidents: IncidentLog,
+    round: u64,
+    auth_token: TokenCache,
+    /// Persists the state files off the check loop
+    writer: Writer,
+    /// Database the metrics and every check are saved in
+    store: Store,
+    /// Whether the persistence degraded alert has been sent and not yet resolved
+    persistence_degraded: bool,
+    /// Per-endpoint settings from the config file or `URL=DURATION`
+    configs: HashMap<String, EndpointConfig>,
+    /// When each endpoint is next due to be checked
+    next_due: HashMap<String, Instant>,
+    /// Endpoints whose clock skew has been alerted on and not yet resolved
+    clock_skewed: HashSet<String>,
+    /// Expiry of the certificate each endpoint was alerted about, until it is renewed
+    cert_expiry_warned: HashMap<String, DateTime<Utc>>,
+    /// When every endpoint's metrics were last saved
+    metrics_written: Instant,
+    /// Normalized capture of each endpoint's last failure in the current streak
+    last_failures: Mutex<HashMap<String, String>>,
+    /// Last seen `warmup_version_header` value of each endpoint
+    versions: Mutex<HashMap<String, String>>,
+    /// Keys that stored metrics and history are saved under, for endpoints
+    /// whose `identity` is not their full URL
+    stored_keys: HashMap<String, String>,
 }
 
 impl Monitor {
-    pub fn new(endpoints: Vec<String>, check_interval: Duration, timeout: Duration) -> Self {
-        let slack_webhook_url = std::env::var("SLACK_WEBHOOK_URL").ok();
+    /// Creates a monitor that saves to `store`, resuming from `saved`, the
+    /// metrics loaded from it.
+    pub fn new(
+        configs: Vec<EndpointConfig>,
+        check_interval: Duration,
+        timeout: Duration,
+        store: Store,
+        saved: HashMap<String, Metrics>,
+        options: MonitorOptions,
+    ) -> Self {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        if let Ok(webhook_url) = std::env::var("SLACK_WEBHOOK_URL") {
+            notifiers.push(Box::new(notify::Slack { webhook_url }));
+        }
+        if let Some(webhook_url) = options.discord_webhook.clone() {
+            notifiers.push(Box::new(notify::Discord { webhook_url }));
+        }
+        for webhook_url in &options.webhook_urls {
+            notifiers.push(Box::new(notify::Webhook {
+                webhook_url: webhook_url.clone(),
+            }));
+        }
 
-        let client = Client::builder()
+        let mut builder = Client::builder();
+        if let Some(connect_timeout) = options.pool.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        let client = builder
             .timeout(timeout)
+            .pool_max_idle_per_host(options.pool.max_idle_per_host)
+            .pool_idle_timeout(options.pool.idle_timeout)
+            .tcp_keepalive(options.pool.tcp_keepalive)
             .build()
             .expect("Failed to create HTTP client");
 
-        let metrics = endpoints
+        let endpoints: Vec<String> = configs.iter().map(|c| c.url.clone()).collect();
+        let stored_keys: HashMap<String, String> = configs
             .iter()
-            .map(|endpoint| (endpoint.clone(), Metrics::new(endpoint.clone())))
+            .filter(|c| c.key() != c.url)
+            .map(|c| (c.url.clone(), c.key()))
             .collect();
+        let configs = configs.into_iter().map(|c| (c.url.clone(), c)).collect();
+        let recent = RecentChecks::open(
+            &options.data_path(RECENT_CHECKS_PATH),
+            options.recent_checks,
+        );
+        let MonitorState {
+            metrics,
+            response_samples,
+            clock_skewed,
+            cert_expiry_warned,
+            dropped,
+        } = MonitorState::restore(&endpoints, &stored_keys, saved, &recent, &options);
+        for key in &dropped {
+            store.remove(key);
+        }
+        let snapshot = Arc::new(ArcSwap::from_pointee(metrics.clone()));
+
+        let (events, _) = broadcast::channel(256);
+
+        let slo = options
+            .slo_target
+            .map(|target| SloTracker::new(target, options.slo_latency));
+        let trend = options
+            .trend_alert_delta
+            .map(|delta| TrendTracker::open(&options.data_path(trend::TREND_PATH), delta));
+
+        let writer = Writer::spawn(options.write_queue);
+        let history = CheckHistory::open(&options.data_path(history::HISTORY_PATH));
+        let incidents = IncidentLog::open(&options.data_path(incident::INCIDENTS_PATH));
 
         Self {
             endpoints,
@@ -68,220 +873,3046 @@ impl Monitor {
             timeout,
             metrics,
             client,
-            slack_webhook_url,
+            notifiers,
+            events,
+            notification_slots: Arc::new(Semaphore::new(options.max_concurrent_notifications)),
+            options,
+            digest: Vec::new(),
+            after_hours_digest: Vec::new(),
+            after_hours_digest_due: None,
+            last_digest: Instant::now(),
+            quorum_down: HashSet::new(),
+            slo,
+            trend,
+            history,
+            recent,
+            response_samples,
+            temporary: HashMap::new(),
+            connectivity_lost: false,
+            snapshot,
+            deploys: HashMap::new(),
+            incidents,
+            round: 0,
+            auth_token: TokenCache::default(),
+            writer,
+            store,
+            persistence_degraded: false,
+            last_failures: Mutex::new(HashMap::new()),
+            versions: Mutex::new(HashMap::new()),
+            stored_keys,
+            configs,
+            next_due: HashMap::new(),
+            clock_skewed,
+            cert_expiry_warned,
+            metrics_written: Instant::now(),
         }
     }
 
-    async fn check_endpoint(&self, endpoint: &str) -> (bool, f64) {
-        let start = Instant::now();
-
-        match self.client.get(endpoint).send().await {
-            Ok(response) => {
-                let duration = start.elapsed().as_secs_f64();
-                let success = response.status().is_success();
-                (success, duration)
-            }
-            Err(e) => {
-                error!("Request failed for {}: {}", endpoint, e);
-                (false, 0.0)
-            }
+    /// Creates a monitor that resumes from the metrics snapshot at `path`, as
+    /// served by `GET /api/metrics` or written by an earlier version, instead
+    /// of from the database, e.g. after moving the monitor to another host.
+    /// The snapshot's metrics replace the ones saved in `store`.
+    pub fn load_from_snapshot(
+        path: &Path,
+        configs: Vec<EndpointConfig>,
+        check_interval: Duration,
+        timeout: Duration,
+        store: Store,
+        options: MonitorOptions,
+    ) -> Result<Self, UptimeError> {
+        let saved = snapshot::read_metrics_file(path).map_err(|source| UptimeError::Snapshot {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let monitor = Self::new(configs, check_interval, timeout, store, saved, options);
+        info!("Resuming from snapshot {}", path.display());
+        for endpoint in &monitor.endpoints {
+            monitor.save_endpoint(endpoint);
         }
+        Ok(monitor)
     }
 
-    async fn send_slack_notification(
-        &self,
-        endpoint: &str,
-        is_down: bool,
-        response_time: Option<f64>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        info!(
-            "=== Starting Slack notification process for {} ===",
-            endpoint
-        );
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.snapshot.clone()
+    }
 
-        let webhook_url = match &self.slack_webhook_url {
-            Some(url) => {
-                info!("Found webhook URL: [webhook url]");
-                url
-            }
-            None => {
-                error!("No webhook URL configured!");
-                return Ok(());
-            }
-        };
+    fn publish_snapshot(&self) {
+        self.snapshot.store(Arc::new(self.metrics.clone()));
+    }
 
-        let message = if is_down {
-            format!(
-                "🔴 {} is DOWN! (Time: {})",
-                endpoint,
-                Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-            )
-        } else {
-            format!(
-                "🟢 {} is back UP! (Time: {}, Response Time: {:.2}s)",
-                endpoint,
-                Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
-                response_time.unwrap_or(0.0)
-            )
-        };
+    /// Probes the reference hosts and logs when local connectivity is lost or
+    /// restored. Rounds without connectivity are skipped entirely so neither
+    /// alerts nor downtime are recorded for them.
+    async fn check_connectivity(&mut self) -> bool {
+        let connected =
+            check::has_connectivity(&self.options.connectivity_probes, self.timeout).await;
+        if !connected && !self.connectivity_lost {
+            warn!("local connectivity lost, suspending checks");
+        } else if connected && self.connectivity_lost {
+            info!("local connectivity restored, resuming checks");
+        }
+        self.connectivity_lost = !connected;
+        connected
+    }
 
-        info!("Preparing to send message: {}", message);
+    /// HTTP checks may be retried once after a closed pooled connection.
+    fn max_attempts(endpoint: &str) -> u32 {
+        if check::is_websocket(endpoint) || check::is_tcp(endpoint) {
+            1
+        } else {
+            2
+        }
+    }
 
-        let payload = serde_json::json!({
-            "text": message
-        });
+    /// Requests sent before the measured check, plus the repeated check after a
+    /// version change. Only HTTP endpoints are warmed up.
+    fn max_warmup(&self, endpoint: &str) -> u32 {
+        let Some(config) = self.configs.get(endpoint) else {
+            return 0;
+        };
+        if config.warmup == 0
+            || check::is_mqtt(endpoint)
+            || check::is_websocket(endpoint)
+            || check::is_tcp(endpoint)
+        {
+            return 0;
+        }
+        config.warmup + u32::from(config.warmup_version_header.is_some())
+    }
 
-        info!("Sending request to Slack...");
+    /// Describes every way the timeout, retry and interval settings can make a
+    /// round overrun its schedule. Empty when the configuration is consistent.
+    pub fn schedule_problems(&self) -> Vec<String> {
+        let budgets: Vec<CheckBudget> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| CheckBudget {
+                endpoint,
+                timeout: self.timeout_for(endpoint),
+                attempts: Self::max_attempts(endpoint),
+                warmup: self.max_warmup(endpoint),
+                interval: self.interval_for(endpoint),
+                confirmations: self.options.failures_before_alert,
+            })
+            .collect();
+        schedule_problems(
+            &budgets,
+            self.check_interval,
+            self.options.max_concurrent_checks,
+        )
+    }
 
-        match self
-            .client
-            .post(webhook_url)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(res) => {
-                let status = res.status();
-                match res.text().await {
-                    Ok(text) => {
-                        info!("Slack response - Status: {}, Body: {}", status, text);
-                        if !status.is_success() {
-                            error!("Failed to send Slack notification! Status: {}", status);
-                        } else {
-                            info!("Slack notification sent successfully!");
-                        }
-                    }
-                    Err(e) => error!("Failed to read Slack response: {}", e),
-                }
-            }
-            Err(e) => error!("Failed to send request to Slack: {}", e),
-        };
+    fn interval_for(&self, endpoint: &str) -> Duration {
+        self.configs
+            .get(endpoint)
+            .and_then(|c| c.interval)
+            .unwrap_or(self.check_interval)
+    }
 
-        info!("=== Finished Slack notification process ===");
-        Ok(())
+    /// The endpoint's `expected_status` from the config file, else `--expect-status`.
+    fn expected_status(&self, endpoint: &str) -> &StatusSet {
+        self.configs
+            .get(endpoint)
+            .map(|c| &c.expected_status)
+            .filter(|statuses| !statuses.is_empty())
+            .unwrap_or(&self.options.expect_status)
     }
 
-    fn update_metrics(&mut self, endpoint: &str, success: bool, response_time: f64) {
-        let metrics = self.metrics.get_mut(endpoint).unwrap();
+    /// The endpoint's `must_contain` from the config file, else `--must-contain`.
+    fn must_contain(&self, endpoint: &str) -> &[String] {
+        self.configs
+            .get(endpoint)
+            .map(|c| &c.must_contain)
+            .filter(|keywords| !keywords.is_empty())
+            .unwrap_or(&self.options.response_rules.must_contain)
+    }
 
-        metrics.total_checks += 1;
-        metrics.last_check = Some(Utc::now());
-        metrics.last_status = Some(if success { "up".into() } else { "down".into() });
+    fn timeout_for(&self, endpoint: &str) -> Duration {
+        self.configs
+            .get(endpoint)
+            .and_then(|c| c.timeout)
+            .unwrap_or(self.timeout)
+    }
 
-        if success {
-            metrics.successful_checks += 1;
-            let prev_avg = metrics.average_response_time;
-            metrics.average_response_time = (prev_avg * (metrics.successful_checks as f64 - 1.0)
-                + response_time)
-                / metrics.successful_checks as f64;
-        } else {
-            metrics.failed_checks += 1;
-            metrics.total_downtime += self.check_interval.as_secs();
-        }
+    /// Endpoints whose interval has elapsed; ones not scheduled yet (e.g. just
+    /// added as temporary endpoints) are due immediately.
+    fn due_endpoints(&self, now: Instant) -> Vec<String> {
+        self.endpoints
+            .iter()
+            .filter(|endpoint| self.next_due.get(*endpoint).is_none_or(|due| *due <= now))
+            .cloned()
+            .collect()
+    }
 
-        // Save metrics to file
-        if let Err(e) = self.save_metrics() {
-            error!("Failed to save metrics: {}", e);
+    /// Schedules the endpoints' next checks one interval after `started`, the
+    /// start of their round, so slow checks don't push the schedule back.
+    fn schedule(&mut self, endpoints: &[String], started: Instant) {
+        for endpoint in endpoints {
+            let interval = self.interval_for(endpoint);
+            self.next_due.insert(endpoint.clone(), started + interval);
         }
     }
 
-    fn save_metrics(&self) -> std::io::Result<()> {
-        fs::create_dir_all("metrics")?;
-        let metrics_path = Path::new("metrics/uptime_metrics.json");
-        let mut file = File::create(metrics_path)?;
-        let json = serde_json::to_string_pretty(&self.metrics)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
+    /// When the next endpoint becomes due or the saved metrics need refreshing.
+    fn next_wake(&self) -> Instant {
+        let refresh = self
+            .options
+            .max_metrics_age
+            .map(|age| self.metrics_written + age);
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                self.next_due
+                    .get(endpoint)
+                    .copied()
+                    .unwrap_or_else(Instant::now)
+            })
+            .chain(refresh)
+            .min()
+            .unwrap_or_else(|| Instant::now() + self.check_interval)
     }
 
-    pub async fn run(&mut self) {
-        info!(
-            "Starting uptime monitoring for {} endpoints",
-            self.endpoints.len()
+    /// Checks endpoints concurrently, at most `max_concurrent_checks` at a time,
+    /// returning the results in the same order along with how long each check
+    /// waited past its due time before starting. Checks start in priority order
+    /// (then by due time), so when more are due than may run at once the critical
+    /// ones are never stuck behind a backlog of low priority endpoints.
+    async fn check_all(
+        &self,
+        endpoints: &[String],
+        started: Instant,
+    ) -> (Vec<CheckResult>, Vec<Duration>) {
+        let due_at = |endpoint: &String| self.next_due.get(endpoint).copied().unwrap_or(started);
+        let order = dispatch_order(
+            &endpoints
+                .iter()
+                .map(|endpoint| (self.priority_for(endpoint), due_at(endpoint)))
+                .collect::<Vec<_>>(),
         );
 
-        // Verify webhook configuration
-        match &self.slack_webhook_url {
-            Some(_) => info!("Slack webhook configured"),
-            None => error!("No Slack webhook URL configured - notifications will not be sent"),
-        };
-
-        // Initial check for all endpoints
-        let endpoints: Vec<String> = self.endpoints.clone();
-        for endpoint in &endpoints {
-            info!("Performing initial status check for {}", endpoint);
-            let (success, response_time) = self.check_endpoint(endpoint).await;
-            info!(
-                "Initial check result for {} - Success: {}",
-                endpoint, success
-            );
-
-            // Force initial notification
-            info!("Forcing initial notification for {}", endpoint);
-            if let Err(e) = self
-                .send_slack_notification(endpoint, !success, Some(response_time))
-                .await
-            {
-                error!(
-                    "Failed to send initial notification for {}: {:?}",
-                    endpoint, e
-                );
+        // Driven by hand rather than with `buffer_unordered`, whose closures trip the
+        // compiler's Send inference for `run_in_background`
+        let mut pending = order.into_iter();
+        let mut in_flight = FuturesUnordered::new();
+        let mut results: Vec<Option<CheckResult>> = endpoints.iter().map(|_| None).collect();
+        let mut delays = vec![Duration::ZERO; endpoints.len()];
+        for index in pending.by_ref().take(self.options.max_concurrent_checks) {
+            delays[index] = due_at(&endpoints[index]).elapsed();
+            in_flight.push(self.check_indexed(index, &endpoints[index]));
+        }
+        while let Some((index, result)) = in_flight.next().await {
+            results[index] = Some(result);
+            if let Some(index) = pending.next() {
+                delays[index] = due_at(&endpoints[index]).elapsed();
+                in_flight.push(self.check_indexed(index, &endpoints[index]));
             }
-
-            self.update_metrics(endpoint, success, response_time);
         }
+        (results.into_iter().flatten().collect(), delays)
+    }
 
-        // Start monitoring loop
-        loop {
-            sleep(self.check_interval).await;
+    async fn check_indexed(&self, index: usize, endpoint: &str) -> (usize, CheckResult) {
+        (index, self.check_endpoint(endpoint).await)
+    }
 
-            let endpoints: Vec<String> = self.endpoints.clone();
-            for endpoint in &endpoints {
-                let (success, response_time) = self.check_endpoint(endpoint).await;
+    fn priority_for(&self, endpoint: &str) -> Priority {
+        match self.options.priorities.get(endpoint) {
+            Some(priority) => *priority,
+            None if self.is_critical(endpoint) => Priority::Critical,
+            None => Priority::Normal,
+        }
+    }
 
-                if let Some(metrics) = self.metrics.get(endpoint) {
-                    if let Some(last_status) = &metrics.last_status {
-                        let status_changed =
-                            (last_status == "up" && !success) || (last_status == "down" && success);
-                        info!(
-                            "Status check for {} - Last: {}, Current: {}, Changed: {}",
-                            endpoint,
-                            last_status,
-                            if success { "up" } else { "down" },
-                            status_changed
-                        );
+    fn record_scheduling_delays(&mut self, endpoints: &[String], delays: &[Duration]) {
+        for (endpoint, delay) in endpoints.iter().zip(delays) {
+            let priority = self.priority_for(endpoint);
+            if let Some(metrics) = self.metrics.get_mut(endpoint) {
+                metrics.priority = priority;
+                metrics.scheduling_delay = Some(delay.as_secs_f64());
+            }
+        }
+    }
 
-                        if status_changed {
-                            info!("Status changed for {} - sending notification", endpoint);
-                            if let Err(e) = self
-                                .send_slack_notification(endpoint, !success, Some(response_time))
-                                .await
-                            {
-                                error!("Failed to send notification for {}: {:?}", endpoint, e);
-                            }
-                        }
-                    }
-                }
+    pub fn event_sender(&self) -> broadcast::Sender<MonitorEvent> {
+        self.events.clone()
+    }
 
-                self.update_metrics(endpoint, success, response_time);
+    pub fn notification_slots(&self) -> Arc<Semaphore> {
+        self.notification_slots.clone()
+    }
 
-                let (status_emoji, status_color) = if success {
-                    ("🟢", "UP".green().bold())
-                } else {
-                    ("🔴", "DOWN".red().bold())
-                };
+    fn publish_check(&self, endpoint: &str, result: &CheckResult) {
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = self.events.send(MonitorEvent::Check {
+            endpoint: endpoint.to_string(),
+            success: result.success,
+            response_time: result.response_time,
+            failure_reason: result.failure_reason.clone(),
+            headers: result.captured_headers.clone(),
+            timestamp: Utc::now(),
+        });
+    }
 
-                let metrics = self.metrics.get(endpoint).unwrap();
+    async fn check_endpoint(&self, endpoint: &str) -> CheckResult {
+        let config = self.configs.get(endpoint);
+        let warmup = config.map_or(0, |c| c.warmup);
+        let on_version_change = config.is_some_and(|c| c.warmup_version_header.is_some());
+        if warmup > 0 && !on_version_change {
+            self.warm_up(endpoint, warmup).await;
+        }
+        let mut result = self.probe_endpoint(endpoint).await;
+
+        // A changed version means a restart with cold caches, so this result is
+        // discarded and the check repeated once the endpoint has been warmed up
+        if let Some(version) = &result.version {
+            let previous = self
+                .versions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(endpoint.to_string(), version.clone());
+            if previous.is_some_and(|previous| previous != *version) {
                 info!(
-                    "{} {} {} | ⏱️  {:.2}s | 📈 {:.2}%",
-                    status_emoji,
-                    endpoint,
-                    status_color,
-                    response_time,
-                    (metrics.successful_checks as f64 / metrics.total_checks as f64) * 100.0
+                    "{} is now at version {}, sending {} warmup requests before measuring",
+                    endpoint, version, warmup
                 );
+                self.warm_up(endpoint, warmup).await;
+                result = self.probe_endpoint(endpoint).await;
             }
         }
+
+        // Only outcomes mapped away from their default are overridden, so response
+        // rule failures on a 2xx still count unless ok-2xx itself is remapped
+        let up = self.options.outcome_map.is_up(endpoint, result.outcome);
+        if up != result.outcome.default_up() {
+            result.success = up;
+            result.failure_reason =
+                (!up).then(|| format!("outcome {} is mapped to down", result.outcome));
+        }
+
+        // A draining instance is expected to fail some requests during a deploy
+        if result.draining && !result.success {
+            info!(
+                "{} failed while draining, not counted as down: {}",
+                endpoint,
+                result.failure_reason.as_deref().unwrap_or("unknown reason")
+            );
+            result.success = true;
+            result.failure_reason = None;
+        }
+        result
+    }
+
+    /// Sends `count` requests whose results are discarded, to prime caches and
+    /// connections before the measured check. Only HTTP endpoints are warmed up.
+    async fn warm_up(&self, endpoint: &str, count: u32) {
+        if check::is_mqtt(endpoint) || check::is_websocket(endpoint) || check::is_tcp(endpoint) {
+            return;
+        }
+        let auth = match self.auth_header(endpoint, false).await {
+            Ok(auth) => auth,
+            Err(e) => {
+                warn!("Skipping warmup for {}: auth flow failed: {}", endpoint, e);
+                return;
+            }
+        };
+        for _ in 0..count {
+            let request = match self.build_request(endpoint, auth.as_ref()) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("Skipping warmup for {}: {}", endpoint, e);
+                    return;
+                }
+            };
+            // Read to the end so the server finishes whatever the request triggers
+            match self.client.execute(request).await {
+                Ok(response) => {
+                    let _ = response.bytes().await;
+                }
+                Err(e) => debug!("Warmup request to {} failed: {}", endpoint, e),
+            }
+        }
+    }
+
+    /// Compares a failing response with the endpoint's previous consecutive failure,
+    /// when captures are enabled with `--record`.
+    fn compare_failure(
+        &self,
+        endpoint: &str,
+        status: StatusCode,
+        body: &[u8],
+        failed: bool,
+    ) -> Option<f64> {
+        self.options.record_dir.as_ref()?;
+        let mut last_failures = self.last_failures.lock().unwrap_or_else(|e| e.into_inner());
+        if !failed {
+            last_failures.remove(endpoint);
+            return None;
+        }
+        let capture = record::normalize_capture(
+            status.as_u16(),
+            &String::from_utf8_lossy(body),
+            &self.options.capture_normalize,
+        );
+        let previous = last_failures.insert(endpoint.to_string(), capture);
+        previous.map(|previous| record::similarity(&previous, &last_failures[endpoint]))
+    }
+
+    /// Header carrying the login token, for endpoints covered by `--auth-login-url`.
+    async fn auth_header(
+        &self,
+        endpoint: &str,
+        refresh: bool,
+    ) -> Result<Option<(HeaderName, HeaderValue)>, String> {
+        let Some(flow) = self
+            .options
+            .auth
+            .as_ref()
+            .filter(|f| f.applies_to(endpoint))
+        else {
+            return Ok(None);
+        };
+        let token = self.auth_token.token(flow, &self.client, refresh).await?;
+        Ok(Some((flow.header_name.clone(), flow.header_value(&token)?)))
+    }
+
+    fn build_request(
+        &self,
+        endpoint: &str,
+        auth: Option<&(HeaderName, HeaderValue)>,
+    ) -> reqwest::Result<Request> {
+        let config = self.configs.get(endpoint);
+        let method = config
+            .and_then(|c| c.method.clone())
+            .unwrap_or_else(|| self.options.method.clone());
+        let mut request = self.client.request(method, endpoint);
+        if let Some(body) = &self.options.body {
+            if let Some(content_type) = &self.options.content_type {
+                request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+            }
+            request = request.body(body.clone());
+        }
+        if let Some(config) = config {
+            if let Some(timeout) = config.timeout {
+                request = request.timeout(timeout);
+            }
+            // Validated when the config was loaded
+            for (name, value) in config.header_pairs().unwrap_or_default() {
+                request = request.header(name, value);
+            }
+        }
+        if self.options.pool.no_reuse.iter().any(|e| e == endpoint) {
+            request = request.header(reqwest::header::CONNECTION, "close");
+        }
+        if let Some((name, value)) = auth {
+            request = request.header(name, value);
+        }
+        if self.options.range_requests.iter().any(|e| e == endpoint) {
+            request = request.header(
+                reqwest::header::RANGE,
+                format!("bytes=0-{}", self.options.range_bytes - 1),
+            );
+        }
+        request.build()
+    }
+
+    async fn probe_endpoint(&self, endpoint: &str) -> CheckResult {
+        if check::is_tcp(endpoint) {
+            let result = check::check_tcp(endpoint, self.timeout_for(endpoint)).await;
+            if let Some(reason) = &result.failure_reason {
+                error!("TCP check failed for {}: {}", endpoint, reason);
+            }
+            return result;
+        }
+
+        if check::is_mqtt(endpoint) {
+            let result = check::check_mqtt(endpoint, self.timeout_for(endpoint)).await;
+            if let Some(reason) = &result.failure_reason {
+                error!("MQTT check failed for {}: {}", endpoint, reason);
+            }
+            return result;
+        }
+
+        if check::is_websocket(endpoint) {
+            let result = check::check_websocket(
+                endpoint,
+                &self.options.websocket,
+                self.timeout_for(endpoint),
+            )
+            .await;
+            if let Some(reason) = &result.failure_reason {
+                error!("WebSocket check failed for {}: {}", endpoint, reason);
+            }
+            return result;
+        }
+
+        let auth = match self.auth_header(endpoint, false).await {
+            Ok(auth) => auth,
+            Err(e) => {
+                error!("Auth flow failed for {}: {}", endpoint, e);
+                return CheckResult::failed(format!("auth flow failed: {}", e), 0);
+            }
+        };
+        let request = match self.build_request(endpoint, auth.as_ref()) {
+            Ok(request) => request,
+            Err(e) => {
+                error!("Invalid request for {}: {}", endpoint, e);
+                return CheckResult::failed(format!("invalid request: {}", e), 0);
+            }
+        };
+
+        let request_line = request.method().as_str().len()
+            + request.url().path().len()
+            + request.url().query().map_or(0, |q| q.len() + 1)
+            + " HTTP/1.1\r\n".len();
+        let request_bytes = request_line as u64
+            + check::header_bytes(request.headers())
+            + request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map_or(0, |body| body.len() as u64);
+
+        // Traced here rather than in a reqwest-middleware layer. A middleware only
+        // gets the response before its body is read, so logging a body preview would
+        // mean buffering the body there, breaking --stream and hashing past the
+        // buffering cap for --expect-sha256. The connection-closed retry and the
+        // re-login below also need the endpoint's auth state, which a middleware
+        // doesn't have.
+        if self.options.trace_http {
+            trace!(
+                target: HTTP_TRACE_TARGET,
+                "--> {} {} headers={:?}",
+                request.method(),
+                request.url(),
+                request.headers()
+            );
+        }
+
+        let recorded_request = self.options.record_dir.as_ref().map(|_| RecordedRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: record::headers_to_pairs(request.headers()),
+        });
+
+        let retry_request = request.try_clone();
+        let mut retried = false;
+        let mut start = Instant::now();
+        let mut sent_at = Utc::now();
+        let mut outcome = self.client.execute(request).await;
+
+        // A pooled connection closed by the server is not an outage; retry once on a fresh one
+        let connection_closed = matches!(&outcome, Err(e) if check::is_connection_closed(e));
+        if let (true, Some(retry_request)) = (connection_closed, retry_request) {
+            warn!(
+                "Connection closed before message completed for {}, retrying once",
+                endpoint
+            );
+            retried = true;
+            start = Instant::now();
+            sent_at = Utc::now();
+            outcome = self.client.execute(retry_request).await;
+        }
+
+        // An expired or revoked token is refreshed once before the check counts as failed
+        let rejected =
+            matches!(&outcome, Ok(response) if response.status() == StatusCode::UNAUTHORIZED);
+        if rejected && auth.is_some() {
+            warn!("{} rejected the auth token, logging in again", endpoint);
+            let request = self.auth_header(endpoint, true).await.and_then(|auth| {
+                self.build_request(endpoint, auth.as_ref())
+                    .map_err(|e| e.to_string())
+            });
+            match request {
+                Ok(request) => {
+                    retried = true;
+                    start = Instant::now();
+                    sent_at = Utc::now();
+                    outcome = self.client.execute(request).await;
+                }
+                Err(e) => {
+                    error!("Auth flow failed for {}: {}", endpoint, e);
+                    return CheckResult::failed(format!("auth flow failed: {}", e), 0);
+                }
+            }
+        }
+
+        let mut result = match outcome {
+            Ok(mut response) if self.options.stream.applies_to(endpoint) => {
+                self.check_stream(endpoint, &mut response, start, request_bytes, retried)
+                    .await
+            }
+            Ok(mut response) => {
+                let duration = start.elapsed().as_secs_f64();
+                let status = response.status();
+                let headers = response.headers().clone();
+                let clock_skew = self
+                    .options
+                    .max_clock_skew
+                    .contains_key(endpoint)
+                    .then(|| check::clock_skew(&headers, sent_at, duration))
+                    .flatten();
+
+                // Drain the body so the byte count reflects what was actually transferred,
+                // keeping it only when a rule needs to inspect it
+                let cluster_health_check = self.options.elasticsearch.iter().any(|e| e == endpoint);
+                let must_contain = self.must_contain(endpoint);
+                let inspect_body =
+                    self.options.response_rules.needs_body() || !must_contain.is_empty();
+                let keep_body = inspect_body
+                    || cluster_health_check
+                    || self.options.trace_http
+                    || recorded_request.is_some();
+                let limit = self.options.max_response_body_bytes;
+                let expected_sha256 = self.options.expected_sha256.get(endpoint);
+                let mut hasher = expected_sha256.map(|_| Sha256::new());
+                let mut truncated = false;
+                let mut body_error = None;
+                let mut body = Vec::new();
+                let mut body_bytes = 0;
+                let mut hashed_bytes = 0u64;
+                let mut response_bytes = check::header_bytes(&headers);
+                loop {
+                    match response.chunk().await {
+                        Ok(Some(chunk)) => {
+                            let take = chunk.len().min(limit - body_bytes);
+                            body_bytes += take;
+                            if keep_body {
+                                body.extend_from_slice(&chunk[..take]);
+                            }
+                            // The digest covers the whole artifact; only buffering stops
+                            // at the limit
+                            if let Some(hasher) = &mut hasher {
+                                hasher.update(&chunk);
+                                hashed_bytes += chunk.len() as u64;
+                                response_bytes += chunk.len() as u64;
+                                if take < chunk.len() && !truncated {
+                                    truncated = true;
+                                    debug!(
+                                        "Response body for {} exceeds {} bytes; hashing the rest \
+                                         without keeping it",
+                                        endpoint, limit
+                                    );
+                                }
+                                continue;
+                            }
+                            response_bytes += take as u64;
+                            if take < chunk.len() {
+                                truncated = true;
+                                // Dropping the response closes the connection instead of
+                                // downloading the rest
+                                warn!(
+                                    "Response body for {} exceeds {} bytes, truncating",
+                                    endpoint, limit
+                                );
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("Failed to read response body for {}: {}", endpoint, e);
+                            response_bytes += response.content_length().unwrap_or(0);
+                            truncated = true;
+                            body_error = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                if self.options.trace_http {
+                    let preview = &body[..body.len().min(TRACE_BODY_BYTES)];
+                    trace!(
+                        target: HTTP_TRACE_TARGET,
+                        "<-- {} {} headers={:?} body[..{}]={:?}",
+                        status,
+                        endpoint,
+                        headers,
+                        preview.len(),
+                        String::from_utf8_lossy(preview)
+                    );
+                }
+
+                let range_check = self
+                    .options
+                    .range_requests
+                    .iter()
+                    .any(|e| e == endpoint)
+                    .then(|| {
+                        check::check_range(
+                            status,
+                            &headers,
+                            body_bytes as u64,
+                            self.options.range_bytes,
+                        )
+                    });
+
+                let cluster_health = (cluster_health_check && !truncated)
+                    .then(|| check::check_cluster_health(&body));
+
+                let body_incomplete = body_error.is_some();
+                // Body rules can't be judged on a body that failed partway
+                let verdict = match body_error.filter(|_| inspect_body) {
+                    Some(e) => Err(format!("failed to read response body: {}", e)),
+                    None => check::evaluate_response(
+                        status,
+                        &headers,
+                        inspect_body.then_some(body.as_slice()),
+                        &self.options.response_rules,
+                        self.expected_status(endpoint),
+                        must_contain,
+                    ),
+                }
+                .and_then(|()| match (expected_sha256, hasher) {
+                    (Some(expected), Some(hasher)) => {
+                        let actual = format!("{:x}", hasher.finalize());
+                        if actual.eq_ignore_ascii_case(expected) {
+                            Ok(())
+                        } else {
+                            Err(format!(
+                                "SHA-256 mismatch: got {}, expected {}{}",
+                                actual,
+                                expected,
+                                if body_incomplete {
+                                    format!(" (body incomplete after {} bytes)", hashed_bytes)
+                                } else {
+                                    String::new()
+                                }
+                            ))
+                        }
+                    }
+                    _ => Ok(()),
+                })
+                .and_then(|()| range_check.clone().unwrap_or(Ok(())))
+                .and_then(|()| match (cluster_health_check, &cluster_health) {
+                    (true, None) => Err(format!(
+                        "incomplete cluster health body after {} bytes",
+                        body_bytes
+                    )),
+                    (_, Some(Err(reason))) => Err(reason.clone()),
+                    _ => Ok(()),
+                });
+
+                let previous_failure_similarity =
+                    self.compare_failure(endpoint, status, &body, verdict.is_err());
+                let verdict = verdict.map_err(|reason| match previous_failure_similarity {
+                    Some(similarity) => {
+                        format!("{} ({})", reason, record::describe_similarity(similarity))
+                    }
+                    None => reason,
+                });
+
+                if let (Some(dir), Some(request)) = (&self.options.record_dir, recorded_request) {
+                    let exchange = RecordedExchange {
+                        endpoint: endpoint.to_string(),
+                        recorded_at: Utc::now(),
+                        response_time: duration,
+                        request,
+                        response: RecordedResponse {
+                            status: status.as_u16(),
+                            headers: record::headers_to_pairs(&headers),
+                            body: String::from_utf8_lossy(&body).into_owned(),
+                        },
+                        previous_failure_similarity,
+                    };
+                    if let Err(e) = record::save(dir, &exchange) {
+                        error!("Failed to record exchange for {}: {}", endpoint, e);
+                    }
+                }
+
+                CheckResult {
+                    success: verdict.is_ok(),
+                    response_time: duration,
+                    request_bytes,
+                    response_bytes,
+                    failure_reason: verdict.err(),
+                    retried,
+                    family_timings: None,
+                    outcome: Outcome::from_status(status),
+                    status_code: Some(status.as_u16()),
+                    draining: self
+                        .configs
+                        .get(endpoint)
+                        .and_then(|c| c.drain_header.as_deref())
+                        .is_some_and(|name| headers.contains_key(name)),
+                    range_supported: range_check.map(|check| check.is_ok()),
+                    clock_skew,
+                    cert_expiry: None,
+                    captured_headers: self.capture_headers(endpoint, &headers),
+                    cluster_health: cluster_health.and_then(Result::ok),
+                    version: self
+                        .configs
+                        .get(endpoint)
+                        .and_then(|c| c.warmup_version_header.as_deref())
+                        .and_then(|name| headers.get(name))
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string),
+                }
+            }
+            Err(e) => {
+                error!("Request failed for {}: {}", endpoint, e);
+                CheckResult {
+                    retried,
+                    outcome: Outcome::from_error(&e),
+                    ..CheckResult::failed(format!("request failed: {}", e), request_bytes)
+                }
+            }
+        };
+
+        if self.options.ip_family_timing {
+            result.family_timings =
+                check::measure_family_connect(endpoint, self.timeout_for(endpoint)).await;
+        }
+        if self.options.cert_expiry_warn_days.is_some() && endpoint.starts_with("https://") {
+            match check::leaf_cert_expiry(endpoint, self.timeout_for(endpoint)).await {
+                Ok(expiry) => result.cert_expiry = Some(expiry),
+                Err(e) => warn!("Failed to read the TLS certificate of {}: {}", endpoint, e),
+            }
+        }
+
+        result
+    }
+
+    /// The endpoint's `capture_headers` from a response.
+    fn capture_headers(&self, endpoint: &str, headers: &HeaderMap) -> BTreeMap<String, String> {
+        match self.configs.get(endpoint) {
+            Some(config) if !config.capture_headers.is_empty() => check::capture_headers(
+                headers,
+                &config.capture_headers,
+                &self.options.redact_header_patterns,
+            ),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Judges a streaming endpoint by its status and the first data it sends,
+    /// then disconnects. The response time is the time to that data.
+    async fn check_stream(
+        &self,
+        endpoint: &str,
+        response: &mut reqwest::Response,
+        start: Instant,
+        request_bytes: u64,
+        retried: bool,
+    ) -> CheckResult {
+        let status = response.status();
+        let response_bytes = check::header_bytes(response.headers());
+        let rules = &self.options.stream;
+        let verdict = check::evaluate_response(
+            status,
+            response.headers(),
+            None,
+            &self.options.response_rules,
+            self.expected_status(endpoint),
+            &[],
+        );
+        let data = match verdict {
+            Ok(()) => {
+                check::read_stream_start(response, rules, self.options.max_response_body_bytes)
+                    .await
+            }
+            Err(reason) => Err(reason),
+        };
+        let verdict = data
+            .as_deref()
+            .map_err(Clone::clone)
+            .and_then(|data| check::check_stream_data(data, rules));
+        if self.options.trace_http {
+            trace!(
+                target: HTTP_TRACE_TARGET,
+                "<-- {} {} headers={:?} stream={:?}",
+                status,
+                endpoint,
+                response.headers(),
+                data.as_deref().map(String::from_utf8_lossy)
+            );
+        }
+        let data_bytes = data.as_ref().map_or(0, Vec::len) as u64;
+        CheckResult {
+            success: verdict.is_ok(),
+            response_time: start.elapsed().as_secs_f64(),
+            request_bytes,
+            response_bytes: response_bytes + data_bytes,
+            failure_reason: verdict.err(),
+            retried,
+            family_timings: None,
+            outcome: Outcome::from_status(status),
+            status_code: Some(status.as_u16()),
+            captured_headers: self.capture_headers(endpoint, response.headers()),
+            ..CheckResult::failed(String::new(), request_bytes)
+        }
+    }
+
+    async fn send_notification(
+        &self,
+        endpoint: &str,
+        is_down: bool,
+        response_time: Option<f64>,
+        status_code: Option<u16>,
+        reason: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        info!("=== Starting notification process for {} ===", endpoint);
+
+        let style = self.options.styles.get(self.severity(endpoint, is_down));
+        let change = StatusChange {
+            endpoint,
+            name: self.configs.get(endpoint).and_then(|c| c.name.as_deref()),
+            is_down,
+            response_time,
+            status_code,
+            reason,
+            at: Utc::now(),
+            consecutive_failures: self
+                .metrics
+                .get(endpoint)
+                .map_or(0, |m| m.consecutive_failures),
+        };
+        let message = notify::status_message(&change, style);
+
+        if self.notifiers.is_empty() {
+            error!("No webhook URL configured!");
+        } else {
+            info!("Preparing to send message: {}", message);
+            let sends = self.notifiers.iter().map(|notifier| {
+                let payload = notifier.status_payload(&change, &message);
+                self.post(notifier.as_ref(), "transition", payload)
+            });
+            join_all(sends).await;
+        }
+
+        info!("=== Finished notification process ===");
+        Ok(())
+    }
+
+    /// Posts a message to every configured chat webhook at once.
+    async fn send_message(&self, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.notifiers.is_empty() {
+            error!("No webhook URL configured!");
+            return Ok(());
+        }
+
+        info!("Preparing to send message: {}", message);
+        let sends = self
+            .notifiers
+            .iter()
+            .map(|notifier| self.post_message(notifier.as_ref(), message));
+        join_all(sends).await;
+        Ok(())
+    }
+
+    async fn post_message(&self, notifier: &dyn Notifier, message: &str) -> Result<(), String> {
+        self.post(notifier, "message", notifier.payload(message))
+            .await
+    }
+
+    /// Failures are logged here; the error is only for callers that report them.
+    async fn post(
+        &self,
+        notifier: &dyn Notifier,
+        event: &str,
+        payload: serde_json::Value,
+    ) -> Result<(), String> {
+        let name = notifier.name();
+        recording::record(name, event, &payload);
+
+        // Held until the response is read; the semaphore is never closed
+        let Ok(_slot) = self.notification_slots.acquire().await else {
+            return Err("notifications are shut down".into());
+        };
+        info!("Sending request to {}...", name);
+
+        let result = match self
+            .client
+            .post(notifier.webhook_url())
+            .header("Content-Type", "application/json")
+            .json(&payload)
+            .send()
+            .await
+        {
+            Ok(res) => {
+                let status = res.status();
+                match res.text().await {
+                    Ok(text) => {
+                        info!("{} response - Status: {}, Body: {}", name, status, text);
+                        if !status.is_success() {
+                            Err(format!("HTTP {}", status))
+                        } else {
+                            info!("{} notification sent successfully!", name);
+                            Ok(())
+                        }
+                    }
+                    Err(e) => Err(format!("reading the response: {}", e)),
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = &result {
+            error!("Failed to send {} notification: {}", name, e);
+        }
+        result
+    }
+
+    /// Sends a clearly marked test message through every notifier, each
+    /// bounded by `timeout`.
+    pub async fn test_notifiers(&self, timeout: Duration) -> Vec<selftest::Probe> {
+        let message = "🧪 Uptime monitor self-test: this is a test message, no action needed";
+        let probes = self.notifiers.iter().map(|notifier| {
+            selftest::probe(format!("notify {}", notifier.name()), timeout, async {
+                self.post_message(notifier.as_ref(), message)
+                    .await
+                    .map(|()| "test message delivered".to_string())
+            })
+        });
+        join_all(probes).await
+    }
+
+    fn is_critical(&self, endpoint: &str) -> bool {
+        self.options
+            .critical_endpoints
+            .iter()
+            .any(|critical| critical == endpoint)
+    }
+
+    /// Whether an alert for `endpoint` should wait for the after-hours digest
+    /// rather than interrupt someone now.
+    fn defer_after_hours(&self, endpoint: &str, now: DateTime<Utc>) -> bool {
+        let Some(hours) = &self.options.business_hours else {
+            return false;
+        };
+        if hours.contains(now, self.options.business_timezone) {
+            return false;
+        }
+        match self.configs.get(endpoint).and_then(|c| c.after_hours) {
+            Some(after_hours) => after_hours == AfterHours::Defer,
+            None => self.priority_for(endpoint) > self.options.after_hours_page_priority,
+        }
+    }
+
+    fn severity(&self, endpoint: &str, is_down: bool) -> Severity {
+        match (is_down, self.is_critical(endpoint)) {
+            (false, _) => Severity::Up,
+            (true, false) => Severity::Down,
+            (true, true) => Severity::Critical,
+        }
+    }
+
+    async fn notify_status_change(
+        &mut self,
+        endpoint: &str,
+        is_down: bool,
+        response_time: f64,
+        status_code: Option<u16>,
+        reason: Option<String>,
+    ) {
+        if let Some(window) = self.deploy_window(endpoint) {
+            info!(
+                "Suppressing alert for {} during deploy window {}",
+                endpoint, window.id
+            );
+            return;
+        }
+
+        let now = Utc::now();
+        if self.defer_after_hours(endpoint, now) {
+            let due = self.after_hours_digest_due.or_else(|| {
+                hours::next_local_time(
+                    now,
+                    self.options.after_hours_digest_at,
+                    self.options.business_timezone,
+                )
+            });
+            // Without a digest time to wait for, the alert is sent now rather than held forever
+            if let Some(due) = due {
+                self.after_hours_digest_due = Some(due);
+                info!(
+                    "Holding status change for {} outside business hours until {}",
+                    endpoint,
+                    due.format("%Y-%m-%d %H:%M UTC")
+                );
+                self.after_hours_digest.push(DigestEntry {
+                    endpoint: endpoint.to_string(),
+                    is_down,
+                    response_time,
+                    reason,
+                    at: now,
+                });
+                return;
+            }
+        }
+
+        if self.options.alert_digest_interval.is_some() && !self.is_critical(endpoint) {
+            info!(
+                "Queueing status change for {} into the alert digest",
+                endpoint
+            );
+            self.digest.push(DigestEntry {
+                endpoint: endpoint.to_string(),
+                is_down,
+                response_time,
+                reason,
+                at: Utc::now(),
+            });
+            return;
+        }
+
+        if let Err(e) = self
+            .send_notification(
+                endpoint,
+                is_down,
+                Some(response_time),
+                status_code,
+                reason.as_deref(),
+            )
+            .await
+        {
+            error!("Failed to send notification for {}: {:?}", endpoint, e);
+        }
+    }
+
+    async fn evaluate_quorum(&mut self) {
+        let Some(store) = &self.options.quorum else {
+            return;
+        };
+        let required = store.required;
+
+        let statuses = self
+            .metrics
+            .iter()
+            .filter_map(|(endpoint, m)| Some((endpoint.clone(), m.last_status.as_deref()? == "up")))
+            .collect();
+        if let Err(e) = store.publish(statuses) {
+            error!("Failed to publish quorum report: {}", e);
+        }
+
+        let reports = match store.read_reports() {
+            Ok(reports) => reports,
+            Err(e) => {
+                error!("Failed to read quorum reports: {}", e);
+                return;
+            }
+        };
+
+        let endpoints: Vec<String> = self.endpoints.clone();
+        for endpoint in &endpoints {
+            let votes = quorum::down_votes(&reports, endpoint);
+            let confirmed_down = votes >= required;
+            let was_down = self.quorum_down.contains(endpoint);
+            if confirmed_down == was_down {
+                continue;
+            }
+
+            info!(
+                "Quorum {} for {} ({}/{} instances report down)",
+                if confirmed_down { "reached" } else { "lost" },
+                endpoint,
+                votes,
+                reports.len()
+            );
+            if confirmed_down {
+                self.quorum_down.insert(endpoint.clone());
+            } else {
+                self.quorum_down.remove(endpoint);
+            }
+
+            let response_time = self
+                .metrics
+                .get(endpoint)
+                .map_or(0.0, |m| m.average_response_time);
+            let status_code = self.metrics.get(endpoint).and_then(|m| m.last_status_code);
+            let reason = confirmed_down.then(|| format!("{} instances report down", votes));
+            self.notify_status_change(endpoint, confirmed_down, response_time, status_code, reason)
+                .await;
+        }
+    }
+
+    async fn evaluate_slo(&mut self, endpoint: &str, result: &CheckResult) {
+        let Some(slo) = self.slo.as_mut() else {
+            return;
+        };
+
+        let alerts = slo.record(endpoint, Utc::now(), result.success, result.response_time);
+        for alert in alerts {
+            let message = alert.message();
+            info!("{}", message);
+            if let Err(e) = self.send_message(&message).await {
+                error!("Failed to send SLO alert for {}: {:?}", endpoint, e);
+            }
+        }
+    }
+
+    async fn evaluate_latency_budget(&mut self, endpoint: &str, result: &CheckResult) {
+        let Some(slo) = self.options.latency_slos.get(endpoint) else {
+            return;
+        };
+        let interval = self.interval_for(endpoint);
+        let Some(metrics) = self.metrics.get_mut(endpoint) else {
+            return;
+        };
+
+        let alert = LatencyBudget::record(
+            &mut metrics.latency_budget,
+            slo,
+            Utc::now(),
+            self.options.budget_timezone,
+            interval,
+            result.response_time,
+            result.success,
+        );
+        if let Some(alert) = alert {
+            let message = alert.message();
+            info!("{}", message);
+            if let Err(e) = self.send_message(&message).await {
+                error!(
+                    "Failed to send latency budget alert for {}: {:?}",
+                    endpoint, e
+                );
+            }
+        }
+    }
+
+    async fn evaluate_trend(&mut self, endpoint: &str, result: &CheckResult) {
+        let Some(trend) = self.trend.as_mut() else {
+            return;
+        };
+
+        // Low priority: sent on its own rather than competing with outage alerts
+        let key = self
+            .stored_keys
+            .get(endpoint)
+            .map_or(endpoint, String::as_str);
+        if let Some(alert) = trend.record(key, Utc::now(), result.success) {
+            let message = alert.message();
+            info!("{}", message);
+            if let Err(e) = self.send_message(&message).await {
+                error!("Failed to send trend alert for {}: {:?}", endpoint, e);
+            }
+        }
+    }
+
+    /// Alerts once when an endpoint's clock drifts past its `--max-clock-skew`,
+    /// and again when it is back within the limit.
+    async fn evaluate_clock_skew(&mut self, endpoint: &str, result: &CheckResult) {
+        let (Some(limit), Some(skew)) =
+            (self.options.max_clock_skew.get(endpoint), result.clock_skew)
+        else {
+            return;
+        };
+        let skewed = skew.abs() > limit.as_secs_f64();
+        let message = if skewed && self.clock_skewed.insert(endpoint.to_string()) {
+            format!(
+                "🕰️ Clock skew on {}: its clock is {:.1}s {} ours (limit {})",
+                endpoint,
+                skew.abs(),
+                if skew > 0.0 { "ahead of" } else { "behind" },
+                humantime::format_duration(*limit)
+            )
+        } else if !skewed && self.clock_skewed.remove(endpoint) {
+            format!(
+                "🕰️ Clock skew on {} is back within {} ({:+.1}s)",
+                endpoint,
+                humantime::format_duration(*limit),
+                skew
+            )
+        } else {
+            return;
+        };
+        info!("{}", message);
+        if let Err(e) = self.send_message(&message).await {
+            error!("Failed to send clock skew alert for {}: {:?}", endpoint, e);
+        }
+    }
+
+    /// Warns once per certificate when an endpoint's expires within
+    /// `--cert-expiry-warn-days`, and again once it has been renewed.
+    async fn evaluate_cert_expiry(&mut self, endpoint: &str, result: &CheckResult) {
+        let (Some(days), Some(expiry)) = (self.options.cert_expiry_warn_days, result.cert_expiry)
+        else {
+            return;
+        };
+        let now = Utc::now();
+        let expiring = expiry - now <= chrono::Duration::days(i64::from(days));
+        let message = if expiring
+            && self.cert_expiry_warned.insert(endpoint.to_string(), expiry) != Some(expiry)
+        {
+            let left = expiry - now;
+            if left <= chrono::Duration::zero() {
+                format!(
+                    "🔐 TLS certificate of {} expired {} days ago ({})",
+                    endpoint,
+                    -left.num_days(),
+                    expiry.format("%Y-%m-%d %H:%M UTC")
+                )
+            } else {
+                format!(
+                    "🔐 TLS certificate of {} expires in {} days ({})",
+                    endpoint,
+                    left.num_days(),
+                    expiry.format("%Y-%m-%d %H:%M UTC")
+                )
+            }
+        } else if !expiring && self.cert_expiry_warned.remove(endpoint).is_some() {
+            format!(
+                "🔐 TLS certificate of {} was renewed; it now expires {}",
+                endpoint,
+                expiry.format("%Y-%m-%d")
+            )
+        } else {
+            return;
+        };
+        if expiring {
+            warn!("{}", message);
+        } else {
+            info!("{}", message);
+        }
+        if let Err(e) = self.send_message(&message).await {
+            error!(
+                "Failed to send certificate expiry alert for {}: {:?}",
+                endpoint, e
+            );
+        }
+    }
+
+    /// Alerts once when storage writes keep failing and again when the backlog
+    /// has been saved.
+    async fn evaluate_persistence(&mut self) {
+        let health = self.writer.health().merge(self.store.health());
+        if health.degraded == self.persistence_degraded {
+            return;
+        }
+        self.persistence_degraded = health.degraded;
+        let message = notify::persistence_message(&health);
+        if health.degraded {
+            warn!("{}", message);
+        } else {
+            info!("{}", message);
+        }
+        if let Err(e) = self.send_message(&message).await {
+            error!("Failed to send persistence alert: {:?}", e);
+        }
+    }
+
+    /// Whether an endpoint over its daily traffic cap sits out this round.
+    fn is_throttled(&self, endpoint: &str) -> bool {
+        if self.priority_for(endpoint) == Priority::Critical {
+            return false;
+        }
+        let (Some(cap), true) = (
+            self.options.max_bytes_per_day,
+            self.options.throttle_over_cap,
+        ) else {
+            return false;
+        };
+        let today = Utc::now().date_naive();
+        let over_cap = self
+            .metrics
+            .get(endpoint)
+            .is_some_and(|m| m.traffic_day == Some(today) && m.bytes_today >= cap);
+        over_cap && !self.round.is_multiple_of(10)
+    }
+
+    fn deploy_window(&self, endpoint: &str) -> Option<&DeployWindow> {
+        self.deploys
+            .get(endpoint)
+            .filter(|window| !window.is_expired(Utc::now()))
+    }
+
+    fn annotate(&self, endpoint: &str, message: String) {
+        info!("{}", message);
+        let _ = self.events.send(MonitorEvent::Annotation {
+            endpoint: endpoint.to_string(),
+            message,
+            timestamp: Utc::now(),
+        });
+    }
+
+    async fn sync_deploy_windows(&mut self) {
+        let windows = match control::load_deploy_windows(Path::new(control::DEPLOY_WINDOWS_PATH)) {
+            Ok(windows) => windows,
+            Err(e) => {
+                error!("Failed to read deploy windows: {}", e);
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        // Overlapping deploys of one endpoint count as one window, open until the last one ends
+        let mut active: HashMap<String, DeployWindow> = HashMap::new();
+        for window in windows.into_iter().filter(|w| !w.is_expired(now)) {
+            match active.get_mut(&window.endpoint) {
+                Some(merged) => {
+                    merged.started_at = merged.started_at.min(window.started_at);
+                    merged.expires_at = merged.expires_at.max(window.expires_at);
+                }
+                None => {
+                    active.insert(window.endpoint.clone(), window);
+                }
+            }
+        }
+
+        for (endpoint, window) in &active {
+            if !self.deploys.contains_key(endpoint) {
+                self.annotate(
+                    endpoint,
+                    format!(
+                        "Deploy window {} opened for {} (at most {})",
+                        window.id,
+                        endpoint,
+                        control::format_remaining(window.expires_at)
+                    ),
+                );
+            }
+        }
+
+        let closed: Vec<DeployWindow> = self
+            .deploys
+            .values()
+            .filter(|w| !active.contains_key(&w.endpoint))
+            .cloned()
+            .collect();
+        self.deploys = active;
+
+        for window in closed {
+            self.annotate(
+                &window.endpoint,
+                format!("Deploy window {} for {} closed", window.id, window.endpoint),
+            );
+            // The transition to down was suppressed, so alert now if the deploy didn't recover
+            let still_down = self
+                .metrics
+                .get(&window.endpoint)
+                .is_some_and(|m| m.last_status.as_deref() == Some("down"));
+            if still_down && self.options.quorum.is_none() {
+                let status_code = self
+                    .metrics
+                    .get(&window.endpoint)
+                    .and_then(|m| m.last_status_code);
+                self.notify_status_change(
+                    &window.endpoint,
+                    true,
+                    0.0,
+                    status_code,
+                    Some("still down after deploy window".into()),
+                )
+                .await;
+            }
+        }
+    }
+
+    fn sync_temporary_endpoints(&mut self) {
+        let path = Path::new(control::TEMPORARY_ENDPOINTS_PATH);
+        let entries = match control::load_temporary_endpoints(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Failed to read temporary endpoints: {}", e);
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        let (active, expired): (Vec<TemporaryEndpoint>, Vec<TemporaryEndpoint>) =
+            entries.into_iter().partition(|e| !e.is_expired(now));
+
+        for entry in &active {
+            if self.temporary.contains_key(&entry.url) {
+                self.temporary.insert(entry.url.clone(), entry.expires_at);
+            } else if !self.endpoints.contains(&entry.url) {
+                info!(
+                    "Watching temporary endpoint {}{} (expires in {})",
+                    entry.url,
+                    entry
+                        .name
+                        .as_ref()
+                        .map_or(String::new(), |n| format!(" [{}]", n)),
+                    control::format_remaining(entry.expires_at)
+                );
+                self.endpoints.push(entry.url.clone());
+                self.metrics
+                    .entry(entry.url.clone())
+                    .or_insert_with(|| Metrics::new(entry.url.clone()));
+                self.temporary.insert(entry.url.clone(), entry.expires_at);
+            }
+        }
+
+        // Expired endpoints stop being checked but keep their metrics
+        let active_urls: HashSet<&str> = active.iter().map(|e| e.url.as_str()).collect();
+        let finished: Vec<String> = self
+            .temporary
+            .keys()
+            .filter(|url| !active_urls.contains(url.as_str()))
+            .cloned()
+            .collect();
+        for url in finished {
+            info!("Temporary endpoint {} expired, history retained", url);
+            self.temporary.remove(&url);
+            self.endpoints.retain(|e| *e != url);
+        }
+
+        if !expired.is_empty() {
+            if let Err(e) = control::save_temporary_endpoints(path, &active) {
+                error!("Failed to prune expired temporary endpoints: {}", e);
+            }
+        }
+    }
+
+    async fn flush_digest_if_due(&mut self) {
+        let Some(interval) = self.options.alert_digest_interval else {
+            return;
+        };
+        if self.last_digest.elapsed() < interval {
+            return;
+        }
+        self.last_digest = Instant::now();
+
+        if self.digest.is_empty() {
+            return;
+        }
+
+        let entries = std::mem::take(&mut self.digest);
+        let budgets: Vec<(&str, f64)> = self
+            .metrics
+            .values()
+            .filter_map(|m| Some((m.endpoint.as_str(), m.latency_budget.as_ref()?.consumed())))
+            .collect();
+        let message = notify::digest_message(&entries, interval, &self.options.styles, &budgets);
+        info!("Sending alert digest with {} events", entries.len());
+        if let Err(e) = self.send_message(&message).await {
+            error!("Failed to send alert digest: {:?}", e);
+        }
+    }
+
+    async fn flush_after_hours_digest_if_due(&mut self) {
+        if self
+            .after_hours_digest_due
+            .is_none_or(|due| Utc::now() < due)
+        {
+            return;
+        }
+        self.after_hours_digest_due = None;
+        let entries = std::mem::take(&mut self.after_hours_digest);
+        let message = notify::after_hours_digest_message(&entries, &self.options.styles);
+        info!("Sending after-hours digest with {} events", entries.len());
+        if let Err(e) = self.send_message(&message).await {
+            error!("Failed to send after-hours digest: {:?}", e);
+        }
+    }
+
+    fn update_metrics(&mut self, endpoint: &str, result: &CheckResult) {
+        let CheckResult {
+            success,
+            response_time,
+            ..
+        } = *result;
+        let interval = self.interval_for(endpoint);
+        let now = Utc::now();
+        let key = self
+            .stored_keys
+            .get(endpoint)
+            .map_or(endpoint, String::as_str);
+        self.history.record(key, now, success);
+        self.recent.record(
+            key,
+            CheckRecord {
+                timestamp: now,
+                success,
+                response_time_ms: (response_time * 1000.0).round() as u64,
+                headers: result.captured_headers.clone(),
+            },
+        );
+        self.store.record_check(CheckRow {
+            endpoint: key.to_string(),
+            checked_at: now,
+            success,
+            response_time_ms: (response_time * 1000.0).round() as u64,
+            status_code: result.status_code,
+            failure_reason: result.failure_reason.clone(),
+        });
+        let uptime_windows = self.history.uptime_windows(key, now);
+        let response_quantiles = success.then(|| {
+            let samples = self
+                .response_samples
+                .entry(endpoint.to_string())
+                .or_default();
+            if samples.len() == RESPONSE_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(response_time);
+            response_quantiles(samples)
+        });
+        let (failure_threshold, recovery_threshold) = (
+            self.options.failures_before_alert,
+            self.options.recovery_threshold,
+        );
+        let metrics = self.metrics.get_mut(endpoint).unwrap();
+        let status = metrics.next_status(success, failure_threshold, recovery_threshold);
+        let was_down = metrics.last_status.as_deref() == Some("down");
+        let status_changed = metrics.last_status.as_deref() != Some(status);
+
+        metrics.total_checks += 1;
+        if success {
+            metrics.consecutive_failures = 0;
+            metrics.consecutive_successes += 1;
+        } else {
+            metrics.consecutive_failures += 1;
+            metrics.consecutive_successes = 0;
+        }
+        metrics.last_check = Some(now);
+        metrics.last_status = Some(status.into());
+        metrics.uptime_windows = uptime_windows;
+        if let Some(quantiles) = response_quantiles {
+            metrics.response_quantiles = quantiles;
+        }
+        metrics.last_outcome = Some(result.outcome.to_string());
+        metrics.last_status_code = result.status_code;
+        metrics.draining_checks += u64::from(result.draining);
+
+        if success {
+            metrics.successful_checks += 1;
+            let prev_avg = metrics.average_response_time;
+            metrics.average_response_time = (prev_avg * (metrics.successful_checks as f64 - 1.0)
+                + response_time)
+                / metrics.successful_checks as f64;
+        } else {
+            metrics.failed_checks += 1;
+        }
+        if let Some(csv_log) = &self.options.csv_log {
+            let success_rate =
+                metrics.successful_checks as f64 / metrics.total_checks as f64 * 100.0;
+            csv_log.append(now, endpoint, success, response_time, success_rate);
+        }
+
+        // Downtime follows the confirmed status; confirming an outage counts the
+        // whole failure streak, since the endpoint was down from its first failure
+        let downtime = match (status, was_down) {
+            ("down", true) => interval.as_secs(),
+            ("down", false) => interval.as_secs() * metrics.consecutive_failures,
+            _ => 0,
+        };
+        metrics.total_downtime += downtime;
+        if self
+            .deploys
+            .get(endpoint)
+            .is_some_and(|w| !w.is_expired(Utc::now()))
+        {
+            metrics.deploy_downtime += downtime;
+        }
+
+        metrics.request_bytes += result.request_bytes;
+        metrics.response_bytes += result.response_bytes;
+
+        let today = Utc::now().date_naive();
+        if metrics.traffic_day != Some(today) {
+            metrics.traffic_day = Some(today);
+            metrics.bytes_today = 0;
+        }
+        let before = metrics.bytes_today;
+        metrics.bytes_today += result.request_bytes + result.response_bytes;
+        if let Some(cap) = self.options.max_bytes_per_day {
+            if before < cap && metrics.bytes_today >= cap {
+                warn!(
+                    "Probe traffic to {} reached {} today, over the {} daily cap{}",
+                    endpoint,
+                    format_bytes(metrics.bytes_today),
+                    format_bytes(cap),
+                    if self.options.throttle_over_cap {
+                        "; checking it every tenth round until midnight UTC"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        if result.retried {
+            metrics.transport_retries += 1;
+        }
+        if success {
+            metrics.last_response_time = Some(response_time);
+        }
+        // Roughly the error rate over the last ten checks
+        metrics.recent_error_rate =
+            metrics.recent_error_rate * 0.9 + if success { 0.0 } else { 0.1 };
+        metrics.health_score = Some(metrics.compute_health_score(&self.options.health_weights));
+        if result.range_supported.is_some() {
+            metrics.range_supported = result.range_supported;
+        }
+        if result.clock_skew.is_some() {
+            metrics.clock_skew = result.clock_skew;
+        }
+        if result.cert_expiry.is_some() {
+            metrics.cert_expiry = result.cert_expiry;
+        }
+        if let Some(health) = &result.cluster_health {
+            let was_degraded = metrics
+                .cluster_health
+                .as_ref()
+                .is_some_and(ClusterHealth::is_degraded);
+            if health.is_degraded() && !was_degraded {
+                warn!(
+                    "Elasticsearch cluster at {} is yellow (degraded): {:.1}% of shards active, {} nodes",
+                    endpoint, health.active_shards_percent, health.number_of_nodes
+                );
+            } else if !health.is_degraded() && was_degraded {
+                info!(
+                    "Elasticsearch cluster at {} is {} again",
+                    endpoint, health.status
+                );
+            }
+            metrics.cluster_health = Some(health.clone());
+        }
+        if let Some(timings) = result.family_timings {
+            metrics.ipv4_connect_time = timings.ipv4;
+            metrics.ipv6_connect_time = timings.ipv6;
+        }
+
+        self.save_endpoint(endpoint);
+
+        self.incidents.record(
+            endpoint,
+            success,
+            result.failure_reason.as_deref(),
+            &self.writer,
+        );
+
+        // Readers otherwise see the snapshot published at the end of each round
+        if status_changed {
+            self.publish_snapshot();
+        }
+    }
+
+    /// Queues the endpoint's metrics for the database; only the endpoint that
+    /// changed is written.
+    fn save_endpoint(&self, endpoint: &str) {
+        let key = self
+            .stored_keys
+            .get(endpoint)
+            .map_or(endpoint, String::as_str);
+        self.store.save(key, &self.metrics[endpoint]);
+    }
+
+    fn metrics_stale(&self) -> bool {
+        self.options
+            .max_metrics_age
+            .is_some_and(|age| self.metrics_written.elapsed() >= age)
+    }
+
+    /// Re-saves every endpoint's metrics once `--max-metrics-age` passed, so
+    /// their `updated_at` stays fresh while no checks run, e.g. with long
+    /// intervals or while connectivity is lost.
+    fn refresh_stale_metrics(&mut self) {
+        if self.metrics_stale() {
+            for endpoint in &self.endpoints {
+                self.save_endpoint(endpoint);
+            }
+            self.metrics_written = Instant::now();
+        }
+    }
+
+    fn dump_prometheus(&self) {
+        self.writer.write(
+            Path::new(PROMETHEUS_DUMP_PATH),
+            prometheus::render(&self.metrics),
+            WriteKind::Required,
+        );
+    }
+
+    /// Endpoints with the highest average response time, slowest first.
+    pub fn top_n_slowest(&self, n: usize) -> Vec<&Metrics> {
+        top_n_slowest_in(&self.metrics, n)
+    }
+
+    /// Endpoints with the lowest uptime percentage, least reliable first.
+    pub fn top_n_least_reliable(&self, n: usize) -> Vec<&Metrics> {
+        top_n_least_reliable_in(&self.metrics, n)
+    }
+
+    /// Endpoints whose confirmed status is down, i.e. that have failed
+    /// `--failures-before-alert` checks in a row without recovering since,
+    /// sorted by URL.
+    pub fn currently_down(&self) -> Vec<&Metrics> {
+        currently_down_in(&self.metrics)
+    }
+
+    /// Logs the same overview as `ctl status`, from the live metrics.
+    fn log_status(&self) {
+        let list = |entries: Vec<&Metrics>, value: &dyn Fn(&Metrics) -> String| {
+            if entries.is_empty() {
+                return "none".to_string();
+            }
+            entries
+                .iter()
+                .map(|m| format!("{} ({})", m.endpoint, value(m)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        info!(
+            "Currently down: {}",
+            list(self.currently_down(), &|m| m.last_check.map_or(
+                "-".to_string(),
+                |t| format!("last checked {}", t.format("%H:%M:%S UTC"))
+            ))
+        );
+        info!(
+            "Slowest: {}",
+            list(self.top_n_slowest(STATUS_LOG_TOP), &|m| format!(
+                "{:.3}s",
+                m.average_response_time
+            ))
+        );
+        info!(
+            "Least reliable: {}",
+            list(self.top_n_least_reliable(STATUS_LOG_TOP), &|m| format!(
+                "{:.2}%",
+                m.uptime_percentage()
+            ))
+        );
+    }
+
+    fn export_round(&mut self) {
+        self.publish_snapshot();
+        self.history.flush(&self.writer);
+        self.recent.flush(&self.writer);
+        if let Some(trend) = self.trend.as_mut() {
+            trend.flush(&self.writer);
+        }
+        if let Some(path) = &self.options.openmetrics_path {
+            self.writer.write(
+                path,
+                prometheus::render_openmetrics(&self.metrics),
+                WriteKind::Routine,
+            );
+        }
+    }
+
+    /// Runs the monitor on its own task; await the handle to wait for it (it only
+    /// finishes by panicking) or abort it to stop monitoring.
+    pub fn run_in_background(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move { self.run().await })
+    }
+
+    /// Logs the configuration, then checks every endpoint once and sends the
+    /// initial notifications. Returns when the round started and its results.
+    async fn initial_round(&mut self) -> (Instant, Vec<(String, CheckResult)>) {
+        info!(
+            "Starting uptime monitoring for {} endpoints (interval: {}, timeout: {})",
+            self.endpoints.len(),
+            humantime::format_duration(self.check_interval),
+            humantime::format_duration(self.timeout)
+        );
+        for config in self.configs.values() {
+            if let Some(interval) = config.interval {
+                info!(
+                    "Checking {} every {}",
+                    config.url,
+                    humantime::format_duration(interval)
+                );
+            }
+        }
+
+        // Verify webhook configuration
+        if self.notifiers.is_empty() {
+            error!("No Slack or Discord webhook URL configured - notifications will not be sent");
+        }
+        for notifier in &self.notifiers {
+            info!("{} webhook configured", notifier.name());
+        }
+
+        self.sync_temporary_endpoints();
+        self.sync_deploy_windows().await;
+
+        // Initial check for all endpoints
+        let endpoints: Vec<String> = if self.check_connectivity().await {
+            self.endpoints.clone()
+        } else {
+            Vec::new()
+        };
+        info!(
+            "Performing initial status check for {} endpoints",
+            endpoints.len()
+        );
+        let started = Instant::now();
+        let (results, delays) = self.check_all(&endpoints, started).await;
+        self.record_scheduling_delays(&endpoints, &delays);
+        for (endpoint, result) in endpoints.iter().zip(&results) {
+            info!(
+                "Initial check result for {} - Success: {}",
+                endpoint, result.success
+            );
+            self.update_metrics(endpoint, result);
+
+            // Force initial notification (in quorum mode the first quorum evaluation alerts instead)
+            if self.options.quorum.is_none() {
+                info!("Forcing initial notification for {}", endpoint);
+                if let Err(e) = self
+                    .send_notification(
+                        endpoint,
+                        !result.success,
+                        Some(result.response_time),
+                        result.status_code,
+                        result.failure_reason.as_deref(),
+                    )
+                    .await
+                {
+                    error!(
+                        "Failed to send initial notification for {}: {:?}",
+                        endpoint, e
+                    );
+                }
+            }
+
+            self.publish_check(endpoint, result);
+            self.evaluate_slo(endpoint, result).await;
+            self.evaluate_trend(endpoint, result).await;
+            self.evaluate_latency_budget(endpoint, result).await;
+            self.evaluate_clock_skew(endpoint, result).await;
+            self.evaluate_cert_expiry(endpoint, result).await;
+        }
+        self.export_round();
+        self.evaluate_persistence().await;
+        self.evaluate_quorum().await;
+        (started, endpoints.into_iter().zip(results).collect())
+    }
+
+    /// Checks every endpoint once, saves the metrics, sends the notifications
+    /// and prints one line per endpoint, then exits with a code scripts can act
+    /// on: 0 when all are up, 1 when some are down, 2 when all are down and 3
+    /// when checks could not be carried out, e.g. a DNS lookup failed or local
+    /// connectivity is down.
+    ///
+    /// `publisher` is the task forwarding events, e.g. to SNS. Dropping the
+    /// monitor closes the event channel, so the task finishes once it has sent
+    /// what is queued; it is given [`ONCE_PUBLISH_TIMEOUT`] to do so.
+    pub async fn check_once_and_exit(mut self, publisher: Option<JoinHandle<()>>) -> ! {
+        let (_, results) = self.initial_round().await;
+        self.writer.flush().await;
+        self.store.flush().await;
+        if let Some(csv_log) = &self.options.csv_log {
+            csv_log.flush().await;
+        }
+
+        for (endpoint, result) in &results {
+            println!(
+                "{:<4} {} {:.3}s{}",
+                if result.success { "UP" } else { "DOWN" },
+                endpoint,
+                result.response_time,
+                result
+                    .failure_reason
+                    .as_ref()
+                    .map_or(String::new(), |r| format!(" ({})", r))
+            );
+        }
+        let down = results.iter().filter(|(_, r)| !r.success).count();
+        let code: i32 =
+            if results.is_empty() || results.iter().any(|(_, r)| r.outcome == Outcome::DnsError) {
+                3
+            } else if down == 0 {
+                0
+            } else if down < results.len() {
+                1
+            } else {
+                2
+            };
+        if let Some(path) = &self.options.summary_path {
+            self.write_summary(path, &results, code);
+        }
+
+        drop(self);
+        if let Some(publisher) = publisher {
+            if tokio::time::timeout(ONCE_PUBLISH_TIMEOUT, publisher)
+                .await
+                .is_err()
+            {
+                warn!(
+                    "Gave up on publishing events after {}",
+                    humantime::format_duration(ONCE_PUBLISH_TIMEOUT)
+                );
+            }
+        }
+        std::process::exit(code)
+    }
+
+    fn write_summary(&self, path: &Path, results: &[(String, CheckResult)], exit_code: i32) {
+        let endpoints: Vec<RunResult> = results
+            .iter()
+            .map(|(endpoint, result)| RunResult {
+                endpoint,
+                name: self.configs.get(endpoint).and_then(|c| c.name.as_deref()),
+                status: if result.success { "up" } else { "down" },
+                response_time: result.response_time,
+                outcome: result.outcome.to_string(),
+                failure_reason: result.failure_reason.as_deref(),
+            })
+            .collect();
+        let up = endpoints.iter().filter(|e| e.status == "up").count();
+        let summary = RunSummary {
+            finished_at: Utc::now(),
+            exit_code,
+            up,
+            down: endpoints.len() - up,
+            endpoints,
+        };
+        let result = serde_json::to_string_pretty(&summary)
+            .map_err(std::io::Error::from)
+            .and_then(|json| write_atomic(path, &json));
+        if let Err(e) = result {
+            error!("Failed to write run summary {}: {}", path.display(), e);
+        }
+    }
+
+    pub async fn run(&mut self) {
+        let started = self.initial_round().await.0;
+
+        let mut dump_signal =
+            signal(SignalKind::user_defined2()).expect("Failed to install SIGUSR2 handler");
+
+        let all = self.endpoints.clone();
+        self.schedule(&all, started);
+
+        // Start monitoring loop; each round checks the endpoints whose own interval has elapsed
+        loop {
+            let next_round = sleep_until(self.next_wake().into());
+            tokio::pin!(next_round);
+            loop {
+                tokio::select! {
+                    _ = &mut next_round => break,
+                    _ = dump_signal.recv() => {
+                        self.dump_prometheus();
+                        info!("Writing metrics dump to {}", PROMETHEUS_DUMP_PATH);
+                        self.log_status();
+                    }
+                }
+            }
+
+            self.sync_temporary_endpoints();
+            self.sync_deploy_windows().await;
+            self.refresh_stale_metrics();
+            let due = self.due_endpoints(Instant::now());
+            if due.is_empty() {
+                continue;
+            }
+            if !self.check_connectivity().await {
+                self.schedule(&due, Instant::now());
+                continue;
+            }
+            self.round += 1;
+
+            let endpoints: Vec<String> = due
+                .iter()
+                .filter(|endpoint| !self.is_throttled(endpoint))
+                .cloned()
+                .collect();
+            let started = Instant::now();
+            let (results, delays) = self.check_all(&endpoints, started).await;
+            self.record_scheduling_delays(&endpoints, &delays);
+            self.schedule(&due, started);
+            for (endpoint, result) in endpoints.iter().zip(results) {
+                let success = result.success;
+                let response_time = result.response_time;
+                let mut notify = false;
+
+                if let Some(metrics) = self.metrics.get(endpoint) {
+                    if let Some(last_status) = &metrics.last_status {
+                        // The status is only confirmed, and alerted on once, when the failure
+                        // or success streak reaches its threshold
+                        let (failure_threshold, recovery_threshold) = (
+                            self.options.failures_before_alert,
+                            self.options.recovery_threshold,
+                        );
+                        let next_status =
+                            metrics.next_status(success, failure_threshold, recovery_threshold);
+                        let status_changed = last_status != next_status;
+                        info!(
+                            "Status check for {} - Last: {}, Current: {}, Changed: {}",
+                            endpoint,
+                            last_status,
+                            if success { "up" } else { "down" },
+                            status_changed
+                        );
+                        if !status_changed && last_status != (if success { "up" } else { "down" }) {
+                            let (streak, threshold, action) = if success {
+                                (
+                                    metrics.consecutive_successes + 1,
+                                    recovery_threshold,
+                                    "recovering",
+                                )
+                            } else {
+                                (
+                                    metrics.consecutive_failures + 1,
+                                    failure_threshold,
+                                    "alerting",
+                                )
+                            };
+                            info!(
+                                "{} {} {} of {} consecutive checks before {}",
+                                endpoint,
+                                if success { "passed" } else { "failed" },
+                                streak,
+                                threshold,
+                                action
+                            );
+                        }
+
+                        if status_changed {
+                            let _ = self.events.send(MonitorEvent::Transition {
+                                endpoint: endpoint.clone(),
+                                status: if success { "up".into() } else { "down".into() },
+                                severity: self.severity(endpoint, !success),
+                                timestamp: Utc::now(),
+                            });
+
+                            notify = self.options.quorum.is_none();
+                        }
+                    }
+                }
+
+                self.update_metrics(endpoint, &result);
+                // After the metrics update, so the alert sees this check's failure streak
+                if notify {
+                    info!("Status changed for {} - sending notification", endpoint);
+                    self.notify_status_change(
+                        endpoint,
+                        !success,
+                        response_time,
+                        result.status_code,
+                        result.failure_reason.clone(),
+                    )
+                    .await;
+                }
+                self.publish_check(endpoint, &result);
+                self.evaluate_slo(endpoint, &result).await;
+                self.evaluate_trend(endpoint, &result).await;
+                self.evaluate_latency_budget(endpoint, &result).await;
+                self.evaluate_clock_skew(endpoint, &result).await;
+                self.evaluate_cert_expiry(endpoint, &result).await;
+
+                let style = self.options.styles.get(self.severity(endpoint, !success));
+
+                let metrics = self.metrics.get(endpoint).unwrap();
+                // A result that disagrees with the confirmed status is still within its threshold
+                let pending = match (metrics.last_status.as_deref(), success) {
+                    (Some("up"), false) => format!(
+                        " | ⚠️ degraded ({}/{} failures)",
+                        metrics.consecutive_failures, self.options.failures_before_alert
+                    ),
+                    (Some("down"), true) => format!(
+                        " | recovering ({}/{} successes)",
+                        metrics.consecutive_successes, self.options.recovery_threshold
+                    ),
+                    _ => String::new(),
+                };
+                info!(
+                    "{} {}{} {}{}{} | ⏱️  {:.2}s | 📈 {:.2}% | 📦 {}{}{}{}",
+                    style.emoji,
+                    endpoint,
+                    self.configs
+                        .get(endpoint)
+                        .and_then(|c| c.name.as_ref())
+                        .map_or(String::new(), |name| format!(" [{}]", name)),
+                    style.colored_label(),
+                    result
+                        .status_code
+                        .map_or(String::new(), |code| format!(" ({})", code)),
+                    pending,
+                    response_time,
+                    metrics.uptime_percentage(),
+                    format_bytes(metrics.total_bytes()),
+                    result
+                        .failure_reason
+                        .as_ref()
+                        .map_or(String::new(), |r| format!(" | ❗ {}", r)),
+                    result
+                        .family_timings
+                        .map_or(String::new(), |t| format!(" | 🌐 {}", t)),
+                    self.temporary
+                        .get(endpoint)
+                        .map_or(String::new(), |expires_at| format!(
+                            " | ⏳ temporary (expires in {})",
+                            control::format_remaining(*expires_at)
+                        ))
+                );
+            }
+
+            self.export_round();
+            self.evaluate_persistence().await;
+            self.evaluate_quorum().await;
+            self.flush_digest_if_due().await;
+            self.flush_after_hours_digest_if_due().await;
+        }
+    }
+}
+
+/// Indexes of the due checks in the order they start: by priority, then
+/// longest due first, then as listed.
+fn dispatch_order(due: &[(Priority, Instant)]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..due.len()).collect();
+    order.sort_by_key(|&index| due[index]);
+    order
+}
+
+/// How long one endpoint's check may take, for [`schedule_problems`].
+struct CheckBudget<'a> {
+    endpoint: &'a str,
+    timeout: Duration,
+    attempts: u32,
+    /// Warmup requests sent before the measured check
+    warmup: u32,
+    interval: Duration,
+    /// Consecutive failed checks before an outage is alerted on
+    confirmations: u64,
+}
+
+impl CheckBudget<'_> {
+    /// Every warmup request and attempt running into the timeout. Retries go
+    /// out as soon as the previous attempt fails, so there is no delay between
+    /// them to add.
+    fn worst_case(&self) -> Duration {
+        self.timeout * (self.attempts + self.warmup)
+    }
+
+    /// From the first failed check until the outage is confirmed: the
+    /// remaining confirmation checks each wait for the interval, or for the
+    /// previous check when it overruns it.
+    fn worst_case_to_confirm(&self) -> Duration {
+        let worst = self.worst_case();
+        let rounds = self.confirmations.saturating_sub(1) as u32;
+        worst.max(self.interval) * rounds + worst
+    }
+}
+
+fn schedule_problems(
+    budgets: &[CheckBudget],
+    check_interval: Duration,
+    max_concurrent_checks: usize,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut slowest = Duration::ZERO;
+    let mut shortest = check_interval;
+    for budget in budgets {
+        let worst = budget.worst_case();
+        slowest = slowest.max(worst);
+        shortest = shortest.min(budget.interval);
+        if worst > budget.interval {
+            let warmup = match budget.warmup {
+                0 => String::new(),
+                warmup => format!(" + {} warmup", warmup),
+            };
+            // Each confirmation check overruns too, so the alert slips by every overrun
+            let confirm = match budget.confirmations {
+                0 | 1 => String::new(),
+                checks => format!(
+                    "; confirming an outage over {} failed checks takes up to {}",
+                    checks,
+                    humantime::format_duration(budget.worst_case_to_confirm())
+                ),
+            };
+            problems.push(format!(
+                "{}: worst-case check takes {} (timeout {} x {} attempts{}), longer than the {} interval{}",
+                budget.endpoint,
+                humantime::format_duration(worst),
+                humantime::format_duration(budget.timeout),
+                budget.attempts,
+                warmup,
+                humantime::format_duration(budget.interval),
+                confirm
+            ));
+        }
+    }
+
+    // Endpoints are checked in batches of up to --max-concurrent-checks, so a round
+    // can take as long as the slowest check once per batch
+    let batches = budgets.len().div_ceil(max_concurrent_checks) as u32;
+    let round = slowest * batches;
+    if batches > 1 && round > shortest {
+        problems.push(format!(
+            "worst-case round across {} endpoints ({} at a time) takes {}, longer than the {} interval",
+            budgets.len(),
+            max_concurrent_checks,
+            humantime::format_duration(round),
+            humantime::format_duration(shortest)
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const URL: &str = "https://api.example.com/health";
+
+    async fn open_store(dir: &Path) -> Store {
+        Store::open(&dir.join("uptime.db"), &dir.join("legacy.json"), 16)
+            .await
+            .unwrap()
+    }
+
+    /// Keeps every file the monitor writes in `dir`, away from the working directory.
+    fn options(dir: &Path) -> MonitorOptions {
+        MonitorOptions {
+            data_dir: Some(dir.to_path_buf()),
+            write_queue: 16,
+            failures_before_alert: 1,
+            recovery_threshold: 1,
+            max_concurrent_checks: 1,
+            max_concurrent_notifications: 1,
+            ..MonitorOptions::default()
+        }
+    }
+
+    fn saved_metrics() -> Metrics {
+        Metrics {
+            total_checks: 41,
+            successful_checks: 40,
+            failed_checks: 1,
+            total_downtime: 60,
+            last_status: Some("up".to_string()),
+            consecutive_successes: 12,
+            average_response_time: 0.25,
+            clock_skew: Some(30.0),
+            cert_expiry: Some(Utc::now() + chrono::Duration::days(3)),
+            ..Metrics::new(URL.to_string())
+        }
+    }
+
+    fn success(response_time: f64) -> CheckResult {
+        CheckResult {
+            success: true,
+            response_time,
+            failure_reason: None,
+            outcome: Outcome::Ok2xx,
+            status_code: Some(200),
+            ..CheckResult::failed(String::new(), 0)
+        }
+    }
+
+    #[tokio::test]
+    async fn load_from_snapshot_resumes_where_the_snapshot_left_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        let snapshot = HashMap::from([
+            (URL.to_string(), saved_metrics()),
+            (
+                "https://gone.example.com".to_string(),
+                Metrics::new("https://gone.example.com".to_string()),
+            ),
+        ]);
+        fs::write(&path, snapshot::to_json(&snapshot).unwrap()).unwrap();
+        let options = MonitorOptions {
+            max_clock_skew: HashMap::from([(URL.to_string(), Duration::from_secs(10))]),
+            cert_expiry_warn_days: Some(14),
+            ..options(dir.path())
+        };
+
+        let mut monitor = Monitor::load_from_snapshot(
+            &path,
+            vec![EndpointConfig::new(URL.to_string())],
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+            open_store(dir.path()).await,
+            options,
+        )
+        .unwrap();
+
+        assert_eq!(monitor.metrics.len(), 1);
+        assert_eq!(monitor.metrics[URL].total_checks, 41);
+        assert_eq!(monitor.metrics[URL].consecutive_successes, 12);
+        // Alerts sent before the snapshot are not repeated
+        assert!(monitor.clock_skewed.contains(URL));
+        assert!(monitor.cert_expiry_warned.contains_key(URL));
+
+        monitor.update_metrics(URL, &success(0.5));
+        let metrics = &monitor.metrics[URL];
+        assert_eq!(metrics.total_checks, 42);
+        assert_eq!(metrics.successful_checks, 41);
+        assert_eq!(metrics.consecutive_successes, 13);
+        assert_eq!(metrics.last_status.as_deref(), Some("up"));
+
+        monitor.store.flush().await;
+        let stored = monitor.store.load_metrics().await.unwrap();
+        assert_eq!(stored.keys().collect::<Vec<_>>(), [URL]);
+        assert_eq!(stored[URL].total_checks, 42);
+        assert_eq!(stored[URL].total_downtime, 60);
+    }
+
+    #[tokio::test]
+    async fn load_from_snapshot_reports_a_missing_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.json");
+        let result = Monitor::load_from_snapshot(
+            &path,
+            vec![EndpointConfig::new(URL.to_string())],
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+            open_store(dir.path()).await,
+            options(dir.path()),
+        );
+        match result {
+            Err(UptimeError::Snapshot {
+                path: failed,
+                source,
+            }) => {
+                assert_eq!(failed, path);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            Ok(_) => panic!("loaded a missing snapshot"),
+        }
+    }
+
+    #[test]
+    fn restore_keys_endpoints_by_identity_and_drops_the_rest() {
+        let renamed = "https://api.example.com/health?v=2";
+        let stored_keys = HashMap::from([(renamed.to_string(), URL.to_string())]);
+        let saved = HashMap::from([
+            (URL.to_string(), saved_metrics()),
+            ("https://gone.example.com".to_string(), saved_metrics()),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut recent = RecentChecks::open(&dir.path().join("recent.json"), 10);
+        for (success, response_time_ms) in [(true, 100), (false, 0), (true, 300)] {
+            recent.record(
+                URL,
+                CheckRecord {
+                    timestamp: Utc::now(),
+                    success,
+                    response_time_ms,
+                    headers: BTreeMap::new(),
+                },
+            );
+        }
+
+        let state = MonitorState::restore(
+            &[renamed.to_string(), "https://new.example.com".to_string()],
+            &stored_keys,
+            saved,
+            &recent,
+            &options(dir.path()),
+        );
+
+        assert_eq!(state.metrics[renamed].endpoint, renamed);
+        assert_eq!(state.metrics[renamed].total_checks, 41);
+        assert_eq!(state.metrics["https://new.example.com"].total_checks, 0);
+        assert_eq!(state.dropped, ["https://gone.example.com"]);
+        // Only successful checks feed the quantiles
+        assert_eq!(state.response_samples[renamed], VecDeque::from([0.1, 0.3]));
+        // No limits configured, so nothing counts as already alerted
+        assert!(state.clock_skewed.is_empty());
+        assert!(state.cert_expiry_warned.is_empty());
+    }
+
+    /// Every field set, including text that needs quoting.
+    fn known_good_metrics() -> Metrics {
+        Metrics {
+            endpoint: "https://api.example.com/search?q=\"a,b\"".to_string(),
+            total_checks: 1440,
+            successful_checks: 1436,
+            failed_checks: 4,
+            total_downtime: 240,
+            last_check: Some("2024-05-01T12:00:00.123456Z".parse().unwrap()),
+            last_status: Some("up".to_string()),
+            average_response_time: 0.1234567,
+            request_bytes: 86_400,
+            response_bytes: 1_234_567,
+            transport_retries: 2,
+            ipv4_connect_time: Some(0.012),
+            ipv6_connect_time: Some(0.015),
+            last_outcome: Some("http_status".to_string()),
+            last_status_code: Some(200),
+            draining_checks: 3,
+            last_response_time: Some(0.2),
+            recent_error_rate: 0.1,
+            health_score: Some(97.5),
+            deploy_downtime: 60,
+            maintenance_checks: 1,
+            maintenance_downtime: 30,
+            bytes_today: 4096,
+            traffic_day: Some(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()),
+            range_supported: Some(true),
+            latency_budget: Some(LatencyBudget {
+                month: "2024-05".to_string(),
+                target_ms: 300.0,
+                percentile: 99.0,
+                checks: 1440,
+                slow_checks: 7,
+                allowed_slow_checks: 432.0,
+                alerted: 0,
+                history: Vec::new(),
+            }),
+            priority: Priority::High,
+            consecutive_failures: 0,
+            consecutive_successes: 17,
+            clock_skew: Some(-1.5),
+            cert_expiry: Some("2024-08-01T00:00:00Z".parse().unwrap()),
+            uptime_windows: BTreeMap::from([("1h".to_string(), 100.0), ("24h".to_string(), 99.7)]),
+            scheduling_delay: Some(0.004),
+            response_quantiles: BTreeMap::from([
+                ("0.5".to_string(), 0.1),
+                ("0.99".to_string(), 0.4),
+            ]),
+            cluster_health: Some(ClusterHealth {
+                status: "yellow".to_string(),
+                number_of_nodes: 3,
+                active_shards_percent: 87.5,
+                active_primary_shards: 12,
+            }),
+        }
+    }
+
+    #[test]
+    fn csv_columns_are_every_serialized_field() {
+        let Value::Object(fields) = serde_json::to_value(known_good_metrics()).unwrap() else {
+            panic!("metrics did not serialize to an object");
+        };
+        let mut serialized: Vec<&str> = fields.keys().map(String::as_str).collect();
+        let mut columns = CSV_COLUMNS.to_vec();
+        serialized.sort_unstable();
+        columns.sort_unstable();
+        assert_eq!(columns, serialized);
+        assert_eq!(Metrics::csv_header(), CSV_COLUMNS.join(","));
+    }
+
+    #[test]
+    fn csv_row_round_trips() {
+        let metrics = known_good_metrics();
+        let csv = format!("{}\n{}\n", Metrics::csv_header(), metrics.to_csv_row());
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let header = reader.headers().unwrap().clone();
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), CSV_COLUMNS.len());
+
+        // Cells holding JSON (numbers, booleans, the structured fields) parse
+        // as such; anything else is text and empty cells are missing values
+        let parsed: serde_json::Map<String, Value> = header
+            .iter()
+            .zip(rows[0].iter())
+            .filter(|(_, cell)| !cell.is_empty())
+            .map(|(column, cell)| {
+                let value =
+                    serde_json::from_str(cell).unwrap_or_else(|_| Value::String(cell.to_string()));
+                (column.to_string(), value)
+            })
+            .collect();
+        let parsed: Metrics = serde_json::from_value(Value::Object(parsed)).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(&metrics).unwrap()
+        );
+    }
+
+    #[test]
+    fn csv_row_leaves_missing_values_empty() {
+        let row = Metrics::new("https://api.example.com".to_string()).to_csv_row();
+        let cells: Vec<&str> = row.split(',').collect();
+        assert_eq!(cells.len(), CSV_COLUMNS.len());
+        let cell = |column| cells[CSV_COLUMNS.iter().position(|c| *c == column).unwrap()];
+        assert_eq!(cell("endpoint"), "https://api.example.com");
+        assert_eq!(cell("last_check"), "");
+        assert_eq!(cell("cluster_health"), "");
+        assert_eq!(cell("priority"), "normal");
+        assert_eq!(cell("uptime_windows"), "{}");
+    }
+
+    fn budget(endpoint: &str, timeout: u64, attempts: u32, interval: u64) -> CheckBudget<'_> {
+        CheckBudget {
+            endpoint,
+            timeout: Duration::from_secs(timeout),
+            attempts,
+            warmup: 0,
+            interval: Duration::from_secs(interval),
+            confirmations: 1,
+        }
+    }
+
+    #[test]
+    fn worst_case_check_is_every_attempt_timing_out() {
+        assert_eq!(budget(URL, 10, 2, 60).worst_case(), Duration::from_secs(20));
+        assert_eq!(budget(URL, 10, 1, 60).worst_case(), Duration::from_secs(10));
+        let warmed = CheckBudget {
+            warmup: 3,
+            ..budget(URL, 10, 2, 60)
+        };
+        assert_eq!(warmed.worst_case(), Duration::from_secs(50));
+    }
+
+    #[test]
+    fn worst_case_to_confirm_waits_for_the_interval_or_the_overrun() {
+        let confirmed = |timeout, interval, confirmations| {
+            CheckBudget {
+                confirmations,
+                ..budget(URL, timeout, 2, interval)
+            }
+            .worst_case_to_confirm()
+        };
+        // The first failure alone, with or without a threshold
+        assert_eq!(confirmed(10, 60, 0), Duration::from_secs(20));
+        assert_eq!(confirmed(10, 60, 1), Duration::from_secs(20));
+        // Two more checks an interval apart, the last one timing out
+        assert_eq!(confirmed(10, 60, 3), Duration::from_secs(140));
+        // Checks overrunning a 15s interval start back to back
+        assert_eq!(confirmed(10, 15, 3), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn overrun_reports_warmup_and_confirmation() {
+        let budgets = [CheckBudget {
+            warmup: 1,
+            confirmations: 3,
+            ..budget(URL, 10, 2, 15)
+        }];
+        let problems = schedule_problems(&budgets, Duration::from_secs(60), 1);
+        assert_eq!(
+            problems,
+            [format!(
+                "{}: worst-case check takes 30s (timeout 10s x 2 attempts + 1 warmup), longer than \
+                 the 15s interval; confirming an outage over 3 failed checks takes up to 1m 30s",
+                URL
+            )]
+        );
+        // Warmup alone can push a check past its interval
+        let budgets = [CheckBudget {
+            warmup: 2,
+            ..budget(URL, 5, 2, 15)
+        }];
+        assert_eq!(
+            schedule_problems(&budgets, Duration::from_secs(60), 1).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn schedule_that_fits_has_no_problems() {
+        let budgets = [budget(URL, 10, 2, 60), budget("tcp://db:5432", 5, 1, 30)];
+        assert!(schedule_problems(&budgets, Duration::from_secs(60), 2).is_empty());
+        // A check taking exactly its interval still fits
+        let budgets = [budget(URL, 15, 2, 30)];
+        assert!(schedule_problems(&budgets, Duration::from_secs(30), 1).is_empty());
+    }
+
+    #[test]
+    fn check_longer_than_its_interval_is_reported() {
+        let budgets = [
+            budget(URL, 10, 2, 15),
+            budget("https://ok.example.com", 5, 2, 60),
+        ];
+        let problems = schedule_problems(&budgets, Duration::from_secs(60), 2);
+        assert_eq!(
+            problems,
+            [format!(
+                "{}: worst-case check takes 20s (timeout 10s x 2 attempts), longer than the 15s interval",
+                URL
+            )]
+        );
+    }
+
+    #[test]
+    fn round_is_reported_once_batches_overrun_the_shortest_interval() {
+        // Three endpoints one at a time: 3 batches of up to 20s each
+        let budgets = [
+            budget("https://a.example.com", 10, 2, 60),
+            budget("https://b.example.com", 5, 2, 45),
+            budget("https://c.example.com", 1, 2, 60),
+        ];
+        let problems = schedule_problems(&budgets, Duration::from_secs(60), 1);
+        assert_eq!(
+            problems,
+            ["worst-case round across 3 endpoints (1 at a time) takes 1m, longer than the 45s interval"]
+        );
+        // All at once, the round is just the slowest check
+        assert!(schedule_problems(&budgets, Duration::from_secs(60), 3).is_empty());
+        // Two batches of 20s fit in 45s
+        assert!(schedule_problems(&budgets, Duration::from_secs(60), 2).is_empty());
+    }
+
+    #[test]
+    fn shortest_interval_includes_the_default() {
+        let budgets = [
+            budget("https://a.example.com", 10, 1, 60),
+            budget("https://b.example.com", 10, 1, 60),
+        ];
+        let problems = schedule_problems(&budgets, Duration::from_secs(15), 1);
+        assert_eq!(
+            problems,
+            ["worst-case round across 2 endpoints (1 at a time) takes 20s, longer than the 15s interval"]
+        );
+    }
+
+    #[test]
+    fn single_batch_never_reports_a_round() {
+        let budgets = [budget(URL, 30, 2, 30)];
+        let problems = schedule_problems(&budgets, Duration::from_secs(30), 1);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with(URL));
+        assert!(schedule_problems(&[], Duration::from_secs(30), 1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn endpoint_keywords_replace_the_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let own = EndpointConfig {
+            must_contain: vec!["ready".to_string()],
+            ..EndpointConfig::new("https://a.example.com".to_string())
+        };
+        let monitor = Monitor::new(
+            vec![own, EndpointConfig::new(URL.to_string())],
+            Duration::from_secs(60),
+            Duration::from_secs(5),
+            open_store(dir.path()).await,
+            HashMap::new(),
+            MonitorOptions {
+                response_rules: check::ResponseRules {
+                    must_contain: vec!["ok".to_string(), "db".to_string()],
+                    ..check::ResponseRules::default()
+                },
+                ..options(dir.path())
+            },
+        );
+
+        assert_eq!(monitor.must_contain("https://a.example.com"), ["ready"]);
+        assert_eq!(monitor.must_contain(URL), ["ok", "db"]);
+    }
+
+    #[tokio::test]
+    async fn schedule_problems_use_each_endpoints_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        // A version change adds a repeated check to the warmup request
+        let tight = EndpointConfig {
+            timeout: Some(Duration::from_secs(10)),
+            interval: Some(Duration::from_secs(15)),
+            warmup: 1,
+            warmup_version_header: Some("x-version".to_string()),
+            ..EndpointConfig::new(URL.to_string())
+        };
+        // TCP checks aren't retried or warmed up, so 10s fits a 15s interval
+        let tcp = EndpointConfig {
+            timeout: Some(Duration::from_secs(10)),
+            interval: Some(Duration::from_secs(15)),
+            warmup: 3,
+            ..EndpointConfig::new("tcp://db.example.com:5432".to_string())
+        };
+        let monitor = Monitor::new(
+            vec![tight, tcp],
+            Duration::from_secs(60),
+            Duration::from_secs(5),
+            open_store(dir.path()).await,
+            HashMap::new(),
+            MonitorOptions {
+                max_concurrent_checks: 2,
+                failures_before_alert: 2,
+                ..options(dir.path())
+            },
+        );
+
+        assert_eq!(
+            monitor.schedule_problems(),
+            [format!(
+                "{}: worst-case check takes 40s (timeout 10s x 2 attempts + 2 warmup), longer than \
+                 the 15s interval; confirming an outage over 2 failed checks takes up to 1m 20s",
+                URL
+            )]
+        );
+    }
+
+    /// Whether `metrics` is one endpoint's entry as it was between two checks
+    /// of the snapshot stress test, which only ever records successes whose
+    /// response time encodes the check's number.
+    fn is_whole_entry(metrics: &Metrics) -> bool {
+        let checks = metrics.total_checks;
+        metrics.successful_checks == checks
+            && metrics.failed_checks == 0
+            && metrics.consecutive_successes == checks
+            && metrics.last_check.is_some() == (checks > 0)
+            && metrics.last_response_time == (checks > 0).then(|| checks as f64 / 1000.0)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn snapshot_readers_never_see_a_half_updated_entry() {
+        const ENDPOINTS: usize = 100;
+        const ROUNDS: u64 = 50;
+        const READERS: usize = 8;
+        const MIN_READS: usize = 1000;
+
+        let dir = tempfile::tempdir().unwrap();
+        let endpoints: Vec<String> = (0..ENDPOINTS)
+            .map(|i| format!("https://host{}.example.com/health", i))
+            .collect();
+        let mut monitor = Monitor::new(
+            endpoints.iter().cloned().map(EndpointConfig::new).collect(),
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+            open_store(dir.path()).await,
+            HashMap::new(),
+            options(dir.path()),
+        );
+        let snapshot = monitor.metrics_snapshot();
+        let done = AtomicBool::new(false);
+
+        let reads = std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..READERS)
+                .map(|_| {
+                    let (snapshot, endpoints, done) = (&snapshot, &endpoints, &done);
+                    scope.spawn(move || {
+                        let mut last_seen = vec![0; ENDPOINTS];
+                        let mut reads = 0;
+                        while reads < MIN_READS || !done.load(Ordering::Relaxed) {
+                            let metrics = snapshot.load();
+                            assert_eq!(metrics.len(), ENDPOINTS);
+                            let mut previous = None;
+                            for (i, endpoint) in endpoints.iter().enumerate() {
+                                let entry = &metrics[endpoint];
+                                assert!(is_whole_entry(entry), "half-updated entry {:?}", entry);
+                                // Never older than a snapshot read before
+                                assert!(entry.total_checks >= last_seen[i]);
+                                last_seen[i] = entry.total_checks;
+                                // One point in time: endpoints are checked in
+                                // order, so later ones are at most one check behind
+                                if let Some(previous) = previous {
+                                    assert!(
+                                        entry.total_checks == previous
+                                            || entry.total_checks + 1 == previous,
+                                        "snapshot mixes rounds"
+                                    );
+                                }
+                                previous = Some(entry.total_checks);
+                            }
+                            reads += 1;
+                        }
+                        reads
+                    })
+                })
+                .collect();
+
+            for round in 1..=ROUNDS {
+                for (i, endpoint) in endpoints.iter().enumerate() {
+                    monitor.update_metrics(endpoint, &success(round as f64 / 1000.0));
+                    // Stands in for the immediate publishes on status changes
+                    if i % 7 == 0 {
+                        monitor.publish_snapshot();
+                    }
+                }
+                monitor.publish_snapshot();
+            }
+            done.store(true, Ordering::Relaxed);
+            readers
+                .into_iter()
+                .map(|reader| reader.join().unwrap())
+                .sum::<usize>()
+        });
+
+        assert!(reads >= READERS * MIN_READS);
+        let metrics = snapshot.load();
+        assert!(metrics.values().all(|m| m.total_checks == ROUNDS));
+    }
+
+    #[tokio::test]
+    async fn saved_metrics_carry_over_into_a_new_monitor() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("legacy.json");
+        let gone = "https://gone.example.com";
+        let saved = HashMap::from([
+            (URL.to_string(), saved_metrics()),
+            (gone.to_string(), saved_metrics()),
+        ]);
+        fs::write(&legacy, snapshot::to_json(&saved).unwrap()).unwrap();
+
+        let store = open_store(dir.path()).await;
+        let saved = store.load_metrics().await.unwrap();
+        let mut monitor = Monitor::new(
+            vec![
+                EndpointConfig::new(URL.to_string()),
+                EndpointConfig::new("https://new.example.com".to_string()),
+            ],
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+            store,
+            saved,
+            options(dir.path()),
+        );
+
+        // The metrics file is imported once, then set aside
+        assert!(!legacy.exists());
+        assert!(dir.path().join("legacy.json.imported").exists());
+        assert_eq!(monitor.metrics[URL].total_checks, 41);
+        assert_eq!(monitor.metrics[URL].successful_checks, 40);
+        assert_eq!(monitor.metrics["https://new.example.com"].total_checks, 0);
+        assert!(!monitor.metrics.contains_key(gone));
+
+        // And what the monitor saves is what the next start resumes from
+        monitor.update_metrics(URL, &success(0.1));
+        monitor.store.flush().await;
+        let restarted = open_store(dir.path()).await.load_metrics().await.unwrap();
+        assert_eq!(restarted[URL].total_checks, 42);
+        assert_eq!(restarted[URL].successful_checks, 41);
+        assert!(!restarted.contains_key(gone));
+    }
+
+    #[tokio::test]
+    async fn corrupt_metrics_file_starts_fresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("legacy.json");
+        fs::write(&legacy, "{\"truncated\": ").unwrap();
+
+        let store = open_store(dir.path()).await;
+        assert!(store.load_metrics().await.unwrap().is_empty());
+        // Left in place for a look at what went wrong
+        assert!(legacy.exists());
+
+        let monitor = Monitor::new(
+            vec![EndpointConfig::new(URL.to_string())],
+            Duration::from_secs(60),
+            Duration::from_secs(10),
+            store,
+            HashMap::new(),
+            options(dir.path()),
+        );
+        assert_eq!(monitor.metrics[URL].total_checks, 0);
+    }
+
+    #[tokio::test]
+    async fn missing_metrics_file_starts_fresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path()).await;
+        assert!(store.load_metrics().await.unwrap().is_empty());
+        assert!(!dir.path().join("legacy.json.imported").exists());
+    }
+
+    #[test]
+    fn dispatch_order_is_priority_then_due_time_then_listing() {
+        let now = Instant::now();
+        let earlier = now - Duration::from_secs(30);
+        let due = [
+            (Priority::Low, earlier),
+            (Priority::Normal, now),
+            (Priority::Critical, now),
+            (Priority::Low, now),
+            (Priority::High, now),
+            (Priority::Normal, earlier),
+            (Priority::Critical, now),
+        ];
+        assert_eq!(dispatch_order(&due), [2, 6, 4, 5, 1, 0, 3]);
+        assert!(dispatch_order(&[]).is_empty());
+    }
+
+    /// Serves `200 OK` to every request, recording the paths in the order
+    /// they arrive.
+    async fn recording_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let read = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                seen.lock().unwrap().push(path);
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    )
+                    .await;
+            }
+        });
+        (base, paths)
+    }
+
+    #[tokio::test]
+    async fn state_files_are_kept_in_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        // Nothing listens on the port once the listener is dropped, so the check fails fast
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut monitor = Monitor::new(
+            vec![EndpointConfig::new(url)],
+            Duration::from_secs(60),
+            Duration::from_secs(5),
+            open_store(dir.path()).await,
+            HashMap::new(),
+            MonitorOptions {
+                recent_checks: 10,
+                ..options(dir.path())
+            },
+        );
+        monitor.initial_round().await;
+        monitor.writer.flush().await;
+
+        let mut files: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".json"))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            ["check_history.json", "incidents.json", "recent_checks.json"]
+        );
+        let incidents = incident::load(&dir.path().join("incidents.json")).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert!(incidents[0].closed_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn critical_checks_start_first_when_only_one_may_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, paths) = recording_server().await;
+        let url = |path: &str| format!("{}/{}", base, path);
+
+        // Far more low priority checks than may run at once, with the
+        // critical and high ones listed last
+        let mut endpoints: Vec<String> = (0..30).map(|i| url(&format!("low{}", i))).collect();
+        endpoints.extend([url("normal"), url("high"), url("critical")]);
+        let mut priorities: HashMap<String, Priority> = (0..30)
+            .map(|i| (url(&format!("low{}", i)), Priority::Low))
+            .collect();
+        priorities.insert(url("high"), Priority::High);
+        let mut monitor = Monitor::new(
+            endpoints.iter().cloned().map(EndpointConfig::new).collect(),
+            Duration::from_secs(60),
+            Duration::from_secs(5),
+            open_store(dir.path()).await,
+            HashMap::new(),
+            MonitorOptions {
+                critical_endpoints: vec![url("critical")],
+                priorities,
+                max_concurrent_checks: 1,
+                ..options(dir.path())
+            },
+        );
+        let started = Instant::now();
+        // The last low priority endpoint has been due the longest
+        monitor
+            .next_due
+            .insert(url("low29"), started - Duration::from_secs(5));
+
+        let (results, delays) = monitor.check_all(&endpoints, started).await;
+
+        assert!(results.iter().all(|r| r.success));
+        let paths = paths.lock().unwrap().clone();
+        let mut expected = vec![
+            "/critical".to_string(),
+            "/high".to_string(),
+            "/normal".to_string(),
+            "/low29".to_string(),
+        ];
+        expected.extend((0..29).map(|i| format!("/low{}", i)));
+        assert_eq!(paths, expected);
+
+        // Results still line up with the endpoints
+        assert_eq!(results.len(), endpoints.len());
+        let critical = delays[32];
+        assert!(critical < Duration::from_secs(1), "{:?}", critical);
+        assert!(delays[..30].iter().all(|&low| low >= critical));
+
+        monitor.record_scheduling_delays(&endpoints, &delays);
+        assert_eq!(
+            monitor.metrics[&url("critical")].priority,
+            Priority::Critical
+        );
+        assert_eq!(monitor.metrics[&url("low0")].priority, Priority::Low);
+        assert_eq!(monitor.metrics[&url("normal")].priority, Priority::Normal);
+        assert!(monitor.metrics[&url("low28")].scheduling_delay.unwrap() >= critical.as_secs_f64());
     }
 }
@@ -0,0 +1,479 @@
+use crate::{
+    style::{Severity, StatusStyle, StatusStyles},
+    writer::PersistenceHealth,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// A single endpoint changing state.
+pub struct StatusChange<'a> {
+    pub endpoint: &'a str,
+    /// The endpoint's `name` from the config file
+    pub name: Option<&'a str>,
+    pub is_down: bool,
+    pub response_time: Option<f64>,
+    /// HTTP status of the response that caused the change, if there was one
+    pub status_code: Option<u16>,
+    pub reason: Option<&'a str>,
+    pub at: DateTime<Utc>,
+    /// Failed checks in a row when the change was sent
+    pub consecutive_failures: u64,
+}
+
+/// Text of a status change notification. Kept free of I/O, like the rest of
+/// this module, so payloads can be checked without sending anything.
+pub fn status_message(change: &StatusChange, style: &StatusStyle) -> String {
+    let endpoint = match change.name {
+        Some(name) => format!("{} ({})", name, change.endpoint),
+        None => change.endpoint.to_string(),
+    };
+    let status = change
+        .status_code
+        .map_or(String::new(), |code| format!(", Status: {}", code));
+    if change.is_down {
+        format!(
+            "{} {} is {}! (Time: {}{}{})",
+            style.emoji,
+            endpoint,
+            style.label,
+            change.at.format("%Y-%m-%d %H:%M:%S UTC"),
+            status,
+            change
+                .reason
+                .map_or(String::new(), |r| format!(", Reason: {}", r))
+        )
+    } else {
+        format!(
+            "{} {} is back {}! (Time: {}, Response Time: {:.2}s{})",
+            style.emoji,
+            endpoint,
+            style.label,
+            change.at.format("%Y-%m-%d %H:%M:%S UTC"),
+            change.response_time.unwrap_or(0.0),
+            status
+        )
+    }
+}
+
+/// Body of a Slack incoming-webhook request.
+pub fn slack_payload(message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "text": message
+    })
+}
+
+/// Discord rejects messages longer than this many characters.
+const DISCORD_MAX_CONTENT: usize = 2000;
+
+/// Body of a Discord webhook request. Long messages such as big digests are
+/// cut short rather than rejected.
+pub fn discord_payload(message: &str) -> serde_json::Value {
+    let content = match message.char_indices().nth(DISCORD_MAX_CONTENT - 1) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message.to_string(),
+    };
+    serde_json::json!({
+        "content": content
+    })
+}
+
+/// A chat service that takes messages on an incoming webhook. Adding a
+/// channel only takes its payload format; sending is shared.
+pub trait Notifier: Send + Sync {
+    /// Shown in logs and used as the notifier of recorded events
+    fn name(&self) -> &'static str;
+    fn webhook_url(&self) -> &str;
+    fn payload(&self, message: &str) -> serde_json::Value;
+
+    /// Body of a status change notification, the rendered `message` unless
+    /// the service takes structured events.
+    fn status_payload(&self, _change: &StatusChange, message: &str) -> serde_json::Value {
+        self.payload(message)
+    }
+}
+
+pub struct Slack {
+    pub webhook_url: String,
+}
+
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    fn payload(&self, message: &str) -> serde_json::Value {
+        slack_payload(message)
+    }
+}
+
+pub struct Discord {
+    pub webhook_url: String,
+}
+
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    fn payload(&self, message: &str) -> serde_json::Value {
+        discord_payload(message)
+    }
+}
+
+/// Any service taking a plain JSON POST, e.g. an internal alerting system.
+/// Status changes are sent as structured events rather than chat text.
+pub struct Webhook {
+    pub webhook_url: String,
+}
+
+impl Notifier for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    fn payload(&self, message: &str) -> serde_json::Value {
+        serde_json::json!({
+            "event": "message",
+            "message": message,
+        })
+    }
+
+    fn status_payload(&self, change: &StatusChange, _message: &str) -> serde_json::Value {
+        webhook_status_payload(change)
+    }
+}
+
+/// Body of a `--webhook-url` status change event.
+pub fn webhook_status_payload(change: &StatusChange) -> serde_json::Value {
+    serde_json::json!({
+        "event": "status_change",
+        "endpoint": change.endpoint,
+        "name": change.name,
+        "status": if change.is_down { "down" } else { "up" },
+        "timestamp": change.at.to_rfc3339(),
+        "response_time": change.response_time,
+        "status_code": change.status_code,
+        "reason": change.reason,
+        "consecutive_failures": change.consecutive_failures,
+    })
+}
+
+/// Alert sent when persistence degrades, or the all-clear once it recovers.
+pub fn persistence_message(health: &PersistenceHealth) -> String {
+    if health.degraded {
+        format!(
+            "💾 Persistence degraded: writes keep failing ({}). Monitoring continues from memory; {} writes are waiting to be saved",
+            health.last_error.as_deref().unwrap_or("unknown error"),
+            health.backlog
+        )
+    } else {
+        "💾 Persistence recovered: unsaved writes were saved and metrics are being saved again"
+            .to_string()
+    }
+}
+
+/// A status change queued for the next alert digest.
+pub struct DigestEntry {
+    pub endpoint: String,
+    pub is_down: bool,
+    pub response_time: f64,
+    pub reason: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+fn format_period(period: Duration) -> String {
+    let secs = period.as_secs();
+    let (value, unit) = if secs >= 3600 && secs.is_multiple_of(3600) {
+        (secs / 3600, "hour")
+    } else if secs >= 60 && secs.is_multiple_of(60) {
+        (secs / 60, "minute")
+    } else {
+        (secs, "second")
+    };
+    if value == 1 {
+        format!("{} {}", value, unit)
+    } else {
+        format!("{} {}s", value, unit)
+    }
+}
+
+/// Builds the digest of status changes, followed by each metered endpoint's
+/// latency budget consumption when `budgets` is not empty.
+pub fn digest_message(
+    entries: &[DigestEntry],
+    period: Duration,
+    styles: &StatusStyles,
+    budgets: &[(&str, f64)],
+) -> String {
+    let down = entries.iter().filter(|e| e.is_down).count();
+    let up = entries.len() - down;
+    let plural = |n: usize| if n == 1 { "endpoint" } else { "endpoints" };
+
+    let mut message = format!(
+        "📋 Alert digest: {} {} went down, {} recovered in the last {}\n",
+        down,
+        plural(down),
+        up,
+        format_period(period)
+    );
+    message.push_str(&digest_entries(entries, styles));
+
+    if !budgets.is_empty() {
+        message.push_str("\n⏳ Latency budget consumed this month:");
+        for (endpoint, consumed) in budgets {
+            message.push_str(&format!("\n• {} {:.0}%", endpoint, consumed));
+        }
+    }
+    message
+}
+
+/// Status changes held outside business hours, sent at `--after-hours-digest-at`.
+pub fn after_hours_digest_message(entries: &[DigestEntry], styles: &StatusStyles) -> String {
+    let down = entries.iter().filter(|e| e.is_down).count();
+    let up = entries.len() - down;
+    let since = entries.first().map_or(String::new(), |e| {
+        e.at.format(" since %a %H:%M UTC").to_string()
+    });
+    format!(
+        "🌙 After-hours digest: {} down, {} recovered{}\n{}",
+        down,
+        up,
+        since,
+        digest_entries(entries, styles)
+    )
+}
+
+/// One line per status change, in a code block.
+fn digest_entries(entries: &[DigestEntry], styles: &StatusStyles) -> String {
+    let mut message = String::from("```\n");
+    for entry in entries {
+        let style = styles.get(if entry.is_down {
+            Severity::Down
+        } else {
+            Severity::Up
+        });
+        let status = format!("{} {:<4}", style.emoji, style.label);
+        let detail = if entry.is_down {
+            entry
+                .reason
+                .as_ref()
+                .map_or(String::new(), |r| format!(" ({})", r))
+        } else {
+            format!(" ({:.2}s)", entry.response_time)
+        };
+        message.push_str(&format!(
+            "{} {} {}{}\n",
+            entry.at.format("%H:%M:%S UTC"),
+            status,
+            entry.endpoint,
+            detail
+        ));
+    }
+    message.push_str("```");
+    message
+}
+
+/// Golden-file tests of every notifier's payloads. The crate is a binary
+/// without a library target, so `tests/` can't reach these builders; the
+/// harness lives here and keeps its fixtures in `tests/golden/`. Run with
+/// `UPDATE_GOLDEN=1` to rewrite the fixtures after an intended change.
+#[cfg(test)]
+mod golden {
+    use super::*;
+    use crate::style::parse_style;
+    use chrono::TimeZone;
+    use std::{env, fs, path::PathBuf};
+
+    const ENDPOINT: &str = "https://api.example.com/health";
+
+    fn notifiers() -> Vec<Box<dyn Notifier>> {
+        let webhook_url = "https://hooks.example.com/golden".to_string();
+        vec![
+            Box::new(Slack {
+                webhook_url: webhook_url.clone(),
+            }),
+            Box::new(Discord {
+                webhook_url: webhook_url.clone(),
+            }),
+            Box::new(Webhook { webhook_url }),
+        ]
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, 0).unwrap()
+    }
+
+    fn styles() -> StatusStyles {
+        let mut styles = StatusStyles::default();
+        let (severity, style) = parse_style("critical=🚨:CRITICAL:magenta").unwrap();
+        styles.set(severity, style);
+        styles
+    }
+
+    fn outage() -> StatusChange<'static> {
+        StatusChange {
+            endpoint: ENDPOINT,
+            name: Some("API"),
+            is_down: true,
+            response_time: None,
+            status_code: Some(502),
+            reason: Some("HTTP 502 Bad Gateway"),
+            at: at(10, 0),
+            consecutive_failures: 3,
+        }
+    }
+
+    /// Compares `payload` with `tests/golden/<name>.json`, or rewrites the
+    /// file when `UPDATE_GOLDEN=1` is set.
+    fn assert_golden(name: &str, payload: &serde_json::Value) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden"]
+            .iter()
+            .collect::<PathBuf>()
+            .join(format!("{}.json", name));
+        let rendered = serde_json::to_string_pretty(payload).unwrap() + "\n";
+        if env::var_os("UPDATE_GOLDEN").is_some_and(|v| v == "1") {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, rendered).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "failed to read {} ({}); run with UPDATE_GOLDEN=1 to create it",
+                path.display(),
+                e
+            )
+        });
+        assert!(
+            expected == rendered,
+            "{} is out of date; run with UPDATE_GOLDEN=1 to update it if the change is intended\n--- expected\n{}\n--- actual\n{}",
+            path.display(),
+            expected,
+            rendered
+        );
+    }
+
+    fn assert_status_golden(event: &str, change: &StatusChange, severity: Severity) {
+        let message = status_message(change, styles().get(severity));
+        for notifier in notifiers() {
+            assert_golden(
+                &format!("{}/{}", notifier.name(), event),
+                &notifier.status_payload(change, &message),
+            );
+        }
+    }
+
+    fn assert_message_golden(event: &str, message: &str) {
+        for notifier in notifiers() {
+            assert_golden(
+                &format!("{}/{}", notifier.name(), event),
+                &notifier.payload(message),
+            );
+        }
+    }
+
+    #[test]
+    fn down() {
+        assert_status_golden("down", &outage(), Severity::Down);
+    }
+
+    #[test]
+    fn recovery_after_two_hours() {
+        let change = StatusChange {
+            is_down: false,
+            response_time: Some(0.4321),
+            status_code: Some(200),
+            reason: None,
+            at: at(12, 0),
+            consecutive_failures: 0,
+            ..outage()
+        };
+        assert_status_golden("recovery", &change, Severity::Up);
+    }
+
+    #[test]
+    fn degraded() {
+        let health = PersistenceHealth {
+            degraded: true,
+            last_error: Some(
+                "metrics/incidents.json: No space left on device (os error 28)".to_string(),
+            ),
+            backlog: 4,
+        };
+        assert_message_golden("degraded", &persistence_message(&health));
+        assert_message_golden(
+            "degraded_recovered",
+            &persistence_message(&PersistenceHealth::default()),
+        );
+    }
+
+    /// A down `--critical` endpoint, alerted with the critical style.
+    #[test]
+    fn escalation() {
+        let change = StatusChange {
+            name: None,
+            status_code: None,
+            reason: Some("connection refused"),
+            consecutive_failures: 12,
+            ..outage()
+        };
+        assert_status_golden("escalation", &change, Severity::Critical);
+    }
+
+    #[test]
+    fn digest() {
+        let entries = [
+            DigestEntry {
+                endpoint: ENDPOINT.to_string(),
+                is_down: true,
+                response_time: 0.0,
+                reason: Some("HTTP 502 Bad Gateway".to_string()),
+                at: at(10, 0),
+            },
+            DigestEntry {
+                endpoint: "https://docs.example.com".to_string(),
+                is_down: true,
+                response_time: 0.0,
+                reason: None,
+                at: at(10, 5),
+            },
+            DigestEntry {
+                endpoint: ENDPOINT.to_string(),
+                is_down: false,
+                response_time: 0.4321,
+                reason: None,
+                at: at(12, 0),
+            },
+        ];
+        let budgets = [(ENDPOINT, 42.4)];
+        assert_message_golden(
+            "digest",
+            &digest_message(&entries, Duration::from_secs(3 * 3600), &styles(), &budgets),
+        );
+        assert_message_golden(
+            "after_hours_digest",
+            &after_hours_digest_message(&entries, &styles()),
+        );
+    }
+
+    #[test]
+    fn long_messages_are_cut_for_discord() {
+        let message = "x".repeat(DISCORD_MAX_CONTENT + 10);
+        let payload = discord_payload(&message);
+        assert_eq!(
+            payload["content"].as_str().unwrap().chars().count(),
+            DISCORD_MAX_CONTENT
+        );
+    }
+}
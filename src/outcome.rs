@@ -0,0 +1,160 @@
+use reqwest::StatusCode;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// Raw result category of a check, before any per-endpoint mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// A 2xx response (or a completed WebSocket exchange)
+    Ok2xx,
+    HttpStatus(u16),
+    Timeout,
+    ConnectReset,
+    TlsError,
+    DnsError,
+    Other,
+}
+
+impl Outcome {
+    pub fn from_status(status: StatusCode) -> Self {
+        if status.is_success() {
+            Outcome::Ok2xx
+        } else {
+            Outcome::HttpStatus(status.as_u16())
+        }
+    }
+
+    /// Classifies a transport error by walking its source chain. Resets are
+    /// looked for first since a TLS decoy resets during the handshake.
+    pub fn from_error(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return Outcome::Timeout;
+        }
+
+        let chain =
+            || std::iter::successors(Some(error as &dyn std::error::Error), |err| err.source());
+        let reset = chain().any(|err| {
+            err.downcast_ref::<std::io::Error>().is_some_and(|io| {
+                matches!(
+                    io.kind(),
+                    std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted
+                )
+            })
+        });
+        if reset {
+            return Outcome::ConnectReset;
+        }
+
+        let messages: Vec<String> = chain().map(|err| err.to_string().to_lowercase()).collect();
+        if messages.iter().any(|m| m.contains("dns error")) {
+            Outcome::DnsError
+        } else if messages
+            .iter()
+            .any(|m| m.contains("tls") || m.contains("ssl") || m.contains("certificate"))
+        {
+            Outcome::TlsError
+        } else {
+            Outcome::Other
+        }
+    }
+
+    /// Whether this outcome counts as up without any mapping.
+    pub fn default_up(self) -> bool {
+        self == Outcome::Ok2xx
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok2xx => write!(f, "ok-2xx"),
+            Outcome::HttpStatus(code) => write!(f, "http-{}", code),
+            Outcome::Timeout => write!(f, "timeout"),
+            Outcome::ConnectReset => write!(f, "connect-reset"),
+            Outcome::TlsError => write!(f, "tls-error"),
+            Outcome::DnsError => write!(f, "dns-error"),
+            Outcome::Other => write!(f, "other"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ok-2xx" => Ok(Outcome::Ok2xx),
+            "timeout" => Ok(Outcome::Timeout),
+            "connect-reset" => Ok(Outcome::ConnectReset),
+            "tls-error" => Ok(Outcome::TlsError),
+            "dns-error" => Ok(Outcome::DnsError),
+            "other" => Ok(Outcome::Other),
+            _ => {
+                let code = value
+                    .strip_prefix("http-")
+                    .and_then(|code| code.parse::<u16>().ok())
+                    .filter(|code| (100..600).contains(code))
+                    .ok_or_else(|| format!("unknown outcome '{}'", value))?;
+                if (200..300).contains(&code) {
+                    return Err(format!("'{}' is covered by ok-2xx", value));
+                }
+                Ok(Outcome::HttpStatus(code))
+            }
+        }
+    }
+}
+
+/// One `--map-outcome ENDPOINT,OUTCOME=up|down` entry.
+#[derive(Debug, Clone)]
+pub struct OutcomeMapping {
+    pub endpoint: String,
+    pub outcome: Outcome,
+    pub up: bool,
+}
+
+pub fn parse_mapping(value: &str) -> Result<OutcomeMapping, String> {
+    let (endpoint, rule) = value
+        .rsplit_once(',')
+        .ok_or("expected ENDPOINT,OUTCOME=up|down")?;
+    let (outcome, state) = rule
+        .split_once('=')
+        .ok_or("expected ENDPOINT,OUTCOME=up|down")?;
+    let up = match state {
+        "up" => true,
+        "down" => false,
+        _ => return Err(format!("state must be 'up' or 'down', got '{}'", state)),
+    };
+    Ok(OutcomeMapping {
+        endpoint: endpoint.to_string(),
+        outcome: outcome.parse()?,
+        up,
+    })
+}
+
+/// Per-endpoint overrides of which outcomes count as up. Outcomes without an
+/// entry keep their default, so an empty map reproduces the normal behavior.
+#[derive(Debug, Default)]
+pub struct OutcomeMap {
+    entries: HashMap<(String, Outcome), bool>,
+}
+
+impl OutcomeMap {
+    pub fn new(mappings: Vec<OutcomeMapping>) -> Self {
+        Self {
+            entries: mappings
+                .into_iter()
+                .map(|m| ((m.endpoint, m.outcome), m.up))
+                .collect(),
+        }
+    }
+
+    pub fn endpoints(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|(endpoint, _)| endpoint.as_str())
+    }
+
+    pub fn is_up(&self, endpoint: &str, outcome: Outcome) -> bool {
+        self.entries
+            .get(&(endpoint.to_string(), outcome))
+            .copied()
+            .unwrap_or_else(|| outcome.default_up())
+    }
+}
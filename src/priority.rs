@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Order in which due checks are dispatched when they can't all start at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Endpoints listed with `--critical`; also never throttled by the daily byte cap
+    Critical,
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::Critical => write!(f, "critical"),
+            Priority::High => write!(f, "high"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// Parses a `--check-priority URL=LEVEL` override.
+pub fn parse_check_priority(value: &str) -> Result<(String, Priority), String> {
+    let (endpoint, level) = value.rsplit_once('=').ok_or("expected URL=LEVEL")?;
+    Ok((endpoint.to_string(), parse_priority(level)?))
+}
+
+pub fn parse_priority(level: &str) -> Result<Priority, String> {
+    match level {
+        "critical" => Ok(Priority::Critical),
+        "high" => Ok(Priority::High),
+        "normal" => Ok(Priority::Normal),
+        "low" => Ok(Priority::Low),
+        _ => Err(format!(
+            "unknown priority '{}', expected critical, high, normal or low",
+            level
+        )),
+    }
+}
@@ -0,0 +1,517 @@
+use crate::monitor::Metrics;
+use std::{collections::HashMap, fmt::Write};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Prometheus,
+    OpenMetrics,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+type Extract = fn(&Metrics) -> f64;
+
+enum Value {
+    Single(Extract),
+    /// One sample per label value, e.g. `direction="sent"` and `direction="received"`
+    ByLabel(&'static str, &'static [(&'static str, Extract)]),
+    /// One sample with an extra label taken from the metrics, e.g. `priority="critical"`
+    WithLabel(&'static str, fn(&Metrics) -> String, Extract),
+}
+
+struct Family {
+    name: &'static str,
+    kind: Kind,
+    unit: Option<&'static str>,
+    help: &'static str,
+    value: Value,
+}
+
+// Counter names omit the `_total` suffix, which is added when rendering samples.
+const FAMILIES: &[Family] = &[
+    Family {
+        name: "uptime_checks",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Total number of checks performed.",
+        value: Value::Single(|m| m.total_checks as f64),
+    },
+    Family {
+        name: "uptime_checks_successful",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Number of successful checks.",
+        value: Value::Single(|m| m.successful_checks as f64),
+    },
+    Family {
+        name: "uptime_checks_failed",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Number of failed checks.",
+        value: Value::Single(|m| m.failed_checks as f64),
+    },
+    Family {
+        name: "uptime_downtime_seconds",
+        kind: Kind::Counter,
+        unit: Some("seconds"),
+        help: "Accumulated downtime in seconds.",
+        value: Value::Single(|m| m.total_downtime as f64),
+    },
+    Family {
+        name: "uptime_response_seconds_avg",
+        kind: Kind::Gauge,
+        unit: None,
+        help: "Average response time of successful checks in seconds.",
+        value: Value::Single(|m| m.average_response_time),
+    },
+    Family {
+        name: "uptime_last_status",
+        kind: Kind::Gauge,
+        unit: None,
+        help: "Result of the last check (1 = up, 0 = down).",
+        value: Value::Single(|m| match m.last_status.as_deref() {
+            Some("up") => 1.0,
+            _ => 0.0,
+        }),
+    },
+    Family {
+        name: "uptime_up",
+        kind: Kind::Gauge,
+        unit: None,
+        help: "Whether the last check succeeded (1 = up, 0 = down).",
+        value: Value::Single(|m| f64::from(u8::from(m.last_status.as_deref() == Some("up")))),
+    },
+    Family {
+        name: "uptime_check",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Checks performed, by result.",
+        value: Value::ByLabel(
+            "result",
+            &[
+                ("success", |m| m.successful_checks as f64),
+                ("failure", |m| m.failed_checks as f64),
+            ],
+        ),
+    },
+    Family {
+        name: "uptime_response_seconds",
+        kind: Kind::Gauge,
+        unit: Some("seconds"),
+        help: "Response time quantiles over recent successful checks.",
+        value: Value::ByLabel(
+            "quantile",
+            &[
+                ("0.5", |m| response_quantile(m, "0.5")),
+                ("0.9", |m| response_quantile(m, "0.9")),
+                ("0.99", |m| response_quantile(m, "0.99")),
+            ],
+        ),
+    },
+    Family {
+        name: "uptime_health_score",
+        kind: Kind::Gauge,
+        unit: None,
+        help: "Composite health score from 0 to 100.",
+        value: Value::Single(|m| m.health_score.unwrap_or(0.0)),
+    },
+    Family {
+        name: "uptime_last_check_timestamp_seconds",
+        kind: Kind::Gauge,
+        unit: Some("seconds"),
+        help: "Unix timestamp of the last check.",
+        value: Value::Single(|m| m.last_check.map_or(0.0, |t| t.timestamp() as f64)),
+    },
+    Family {
+        name: "uptime_request_bytes",
+        kind: Kind::Counter,
+        unit: Some("bytes"),
+        help: "Bytes sent by the monitor to the endpoint.",
+        value: Value::Single(|m| m.request_bytes as f64),
+    },
+    Family {
+        name: "uptime_response_bytes",
+        kind: Kind::Counter,
+        unit: Some("bytes"),
+        help: "Bytes received by the monitor from the endpoint.",
+        value: Value::Single(|m| m.response_bytes as f64),
+    },
+    Family {
+        name: "uptime_transport_retries",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Checks retried after the server closed a pooled connection.",
+        value: Value::Single(|m| m.transport_retries as f64),
+    },
+    Family {
+        name: "uptime_probe_requests",
+        kind: Kind::Counter,
+        unit: None,
+        help: "Requests sent to the endpoint, including retries.",
+        value: Value::Single(|m| (m.total_checks + m.transport_retries) as f64),
+    },
+    Family {
+        name: "uptime_probe_bytes",
+        kind: Kind::Counter,
+        unit: Some("bytes"),
+        help: "Probe traffic exchanged with the endpoint.",
+        value: Value::ByLabel(
+            "direction",
+            &[
+                ("sent", |m| m.request_bytes as f64),
+                ("received", |m| m.response_bytes as f64),
+            ],
+        ),
+    },
+    Family {
+        name: "uptime_window_percent",
+        kind: Kind::Gauge,
+        unit: None,
+        help: "Uptime percentage over a sliding window.",
+        value: Value::ByLabel(
+            "window",
+            &[
+                ("1h", |m| window_uptime(m, "1h")),
+                ("24h", |m| window_uptime(m, "24h")),
+                ("7d", |m| window_uptime(m, "7d")),
+                ("30d", |m| window_uptime(m, "30d")),
+            ],
+        ),
+    },
+    Family {
+        name: "uptime_clock_skew_seconds",
+        kind: Kind::Gauge,
+        unit: Some("seconds"),
+        help: "How far the endpoint's Date header was ahead of the monitor's clock.",
+        value: Value::Single(|m| m.clock_skew.unwrap_or(0.0)),
+    },
+    Family {
+        name: "uptime_scheduling_delay_seconds",
+        kind: Kind::Gauge,
+        unit: Some("seconds"),
+        help: "Time the last check waited past its due time before starting.",
+        value: Value::WithLabel(
+            "priority",
+            |m| m.priority.to_string(),
+            |m| m.scheduling_delay.unwrap_or(0.0),
+        ),
+    },
+];
+
+fn window_uptime(metrics: &Metrics, window: &str) -> f64 {
+    metrics.uptime_windows.get(window).copied().unwrap_or(0.0)
+}
+
+// NaN, as for a summary without observations, until a check has succeeded
+fn response_quantile(metrics: &Metrics, quantile: &str) -> f64 {
+    metrics
+        .response_quantiles
+        .get(quantile)
+        .copied()
+        .unwrap_or(f64::NAN)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_with(metrics: &HashMap<String, Metrics>, format: Format) -> String {
+    let mut entries: Vec<&Metrics> = metrics.values().collect();
+    entries.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+
+    let mut out = String::new();
+    for family in FAMILIES {
+        let (kind, sample_name) = match family.kind {
+            Kind::Counter => ("counter", format!("{}_total", family.name)),
+            Kind::Gauge => ("gauge", family.name.to_string()),
+        };
+        // The Prometheus text format names counter metadata after the sample,
+        // OpenMetrics names it after the family.
+        let metadata_name = match format {
+            Format::Prometheus => sample_name.as_str(),
+            Format::OpenMetrics => family.name,
+        };
+
+        let _ = writeln!(out, "# HELP {} {}", metadata_name, family.help);
+        let _ = writeln!(out, "# TYPE {} {}", metadata_name, kind);
+        if let (Format::OpenMetrics, Some(unit)) = (format, family.unit) {
+            let _ = writeln!(out, "# UNIT {} {}", metadata_name, unit);
+        }
+
+        for metrics in &entries {
+            let endpoint = escape_label(&metrics.endpoint);
+            match &family.value {
+                Value::Single(value) => {
+                    let _ = writeln!(
+                        out,
+                        "{}{{endpoint=\"{}\"}} {}",
+                        sample_name,
+                        endpoint,
+                        value(metrics)
+                    );
+                }
+                Value::ByLabel(label, values) => {
+                    for (label_value, value) in values.iter() {
+                        let _ = writeln!(
+                            out,
+                            "{}{{endpoint=\"{}\",{}=\"{}\"}} {}",
+                            sample_name,
+                            endpoint,
+                            label,
+                            label_value,
+                            value(metrics)
+                        );
+                    }
+                }
+                Value::WithLabel(label, label_value, value) => {
+                    let _ = writeln!(
+                        out,
+                        "{}{{endpoint=\"{}\",{}=\"{}\"}} {}",
+                        sample_name,
+                        endpoint,
+                        label,
+                        label_value(metrics),
+                        value(metrics)
+                    );
+                }
+            }
+        }
+    }
+
+    if format == Format::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+/// Renders the metrics map in the Prometheus text exposition format.
+pub fn render(metrics: &HashMap<String, Metrics>) -> String {
+    render_with(metrics, Format::Prometheus)
+}
+
+/// Renders the metrics map in the OpenMetrics text format, including the
+/// `# EOF` terminator.
+pub fn render_openmetrics(metrics: &HashMap<String, Metrics>) -> String {
+    render_with(metrics, Format::OpenMetrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::priority::Priority;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashSet;
+
+    fn fixture() -> HashMap<String, Metrics> {
+        let mut up = Metrics::new("https://example.com/health".to_string());
+        up.total_checks = 10;
+        up.successful_checks = 9;
+        up.failed_checks = 1;
+        up.total_downtime = 60;
+        up.last_status = Some("up".to_string());
+        up.last_check = Some(Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap());
+        up.average_response_time = 0.125;
+        up.request_bytes = 2048;
+        up.response_bytes = 65536;
+        up.priority = Priority::Critical;
+        up.response_quantiles = [("0.5", 0.1), ("0.9", 0.2), ("0.99", 0.4)]
+            .into_iter()
+            .map(|(q, v)| (q.to_string(), v))
+            .collect();
+        up.uptime_windows.insert("1h".to_string(), 90.0);
+
+        // Never checked successfully, so its quantiles render as NaN; the
+        // quote and backslash must come out escaped
+        let mut down = Metrics::new("https://example.com/a\"b\\c".to_string());
+        down.total_checks = 3;
+        down.failed_checks = 3;
+        down.last_status = Some("down".to_string());
+
+        [up, down]
+            .into_iter()
+            .map(|m| (m.endpoint.clone(), m))
+            .collect()
+    }
+
+    /// Family metadata as read back from an OpenMetrics exposition.
+    #[derive(Debug, Default)]
+    struct ParsedFamily {
+        kind: String,
+        unit: Option<String>,
+        help: Option<String>,
+        samples: Vec<String>,
+    }
+
+    /// Reads an OpenMetrics exposition, enforcing the parts of the format the
+    /// Prometheus text parser doesn't know about: the `# EOF` terminator,
+    /// metadata named after the family and preceding its samples, families
+    /// never split, unit suffixes and counter `_total` samples.
+    fn parse_openmetrics(text: &str) -> Vec<(String, ParsedFamily)> {
+        let body = text
+            .strip_suffix("# EOF\n")
+            .expect("exposition must end with `# EOF` and a newline");
+        assert!(!body.contains("# EOF"), "`# EOF` must appear only once");
+
+        let mut families: Vec<(String, ParsedFamily)> = Vec::new();
+        let mut seen = HashSet::new();
+        for line in body.lines() {
+            assert!(!line.is_empty(), "blank lines aren't allowed");
+            if let Some(metadata) = line.strip_prefix("# ") {
+                let mut parts = metadata.splitn(3, ' ');
+                let (keyword, name, value) = (
+                    parts.next().unwrap(),
+                    parts.next().expect("metadata names a family"),
+                    parts.next().expect("metadata has a value"),
+                );
+                let current = match families.last_mut() {
+                    Some((family, parsed)) if family == name && parsed.samples.is_empty() => parsed,
+                    _ => {
+                        assert!(seen.insert(name.to_string()), "family {} is split", name);
+                        families.push((name.to_string(), ParsedFamily::default()));
+                        &mut families.last_mut().unwrap().1
+                    }
+                };
+                match keyword {
+                    "HELP" => current.help = Some(value.to_string()),
+                    "TYPE" => current.kind = value.to_string(),
+                    "UNIT" => {
+                        assert!(
+                            name.ends_with(&format!("_{}", value)),
+                            "family {} must end with its unit {}",
+                            name,
+                            value
+                        );
+                        current.unit = Some(value.to_string());
+                    }
+                    other => panic!("unknown metadata {}", other),
+                }
+                continue;
+            }
+
+            let name = line.split(['{', ' ']).next().expect("sample has a name");
+            let (family, parsed) = families
+                .last_mut()
+                .unwrap_or_else(|| panic!("sample {} precedes any metadata", name));
+            let expected = match parsed.kind.as_str() {
+                "counter" => format!("{}_total", family),
+                "gauge" => family.clone(),
+                other => panic!("unexpected type {} of {}", other, family),
+            };
+            assert_eq!(name, expected, "sample outside its family");
+            let value = line.rsplit(' ').next().unwrap();
+            assert!(
+                value.parse::<f64>().is_ok(),
+                "{} has an invalid value {}",
+                name,
+                value
+            );
+            parsed.samples.push(line.to_string());
+        }
+        families
+    }
+
+    #[test]
+    fn openmetrics_output_parses() {
+        let metrics = fixture();
+        let text = render_openmetrics(&metrics);
+        let families = parse_openmetrics(&text);
+
+        let names: Vec<&str> = families.iter().map(|(name, _)| name.as_str()).collect();
+        let expected: Vec<&str> = FAMILIES.iter().map(|family| family.name).collect();
+        assert_eq!(names, expected);
+        for ((name, parsed), family) in families.iter().zip(FAMILIES) {
+            assert_eq!(parsed.help.as_deref(), Some(family.help), "{}", name);
+            assert_eq!(parsed.unit.as_deref(), family.unit, "{}", name);
+            assert!(!parsed.samples.is_empty(), "{} has no samples", name);
+        }
+
+        let (_, downtime) = families
+            .iter()
+            .find(|(name, _)| name == "uptime_downtime_seconds")
+            .unwrap();
+        assert_eq!(downtime.kind, "counter");
+        assert_eq!(downtime.unit.as_deref(), Some("seconds"));
+        assert!(downtime.samples[0].starts_with("uptime_downtime_seconds_total{"));
+        let (_, bytes) = families
+            .iter()
+            .find(|(name, _)| name == "uptime_probe_bytes")
+            .unwrap();
+        assert_eq!(bytes.unit.as_deref(), Some("bytes"));
+        assert_eq!(bytes.samples.len(), 2 * metrics.len());
+
+        // Every sample line also has to make it through a Prometheus parser,
+        // which checks the label syntax; it keeps label values escaped
+        let scrape =
+            prometheus_parse::Scrape::parse(text.lines().map(|line| Ok(line.to_string()))).unwrap();
+        let sample_lines = text.lines().filter(|line| !line.starts_with('#')).count();
+        assert_eq!(scrape.samples.len(), sample_lines);
+        let endpoints: HashSet<String> = metrics.keys().map(|e| escape_label(e)).collect();
+        for sample in &scrape.samples {
+            let endpoint = sample.labels.get("endpoint").unwrap();
+            assert!(
+                endpoints.contains(endpoint),
+                "unexpected endpoint {}",
+                endpoint
+            );
+        }
+
+        let value = |metric: &str, endpoint: &str, label: Option<(&str, &str)>| {
+            scrape
+                .samples
+                .iter()
+                .find(|s| {
+                    s.metric == metric
+                        && s.labels.get("endpoint") == Some(endpoint)
+                        && label.is_none_or(|(k, v)| s.labels.get(k) == Some(v))
+                })
+                .map(|s| match s.value {
+                    prometheus_parse::Value::Counter(v)
+                    | prometheus_parse::Value::Gauge(v)
+                    | prometheus_parse::Value::Untyped(v) => v,
+                    ref other => panic!("unexpected value {:?}", other),
+                })
+                .unwrap_or_else(|| panic!("no {} sample for {}", metric, endpoint))
+        };
+        let up = "https://example.com/health";
+        let down = r#"https://example.com/a\"b\\c"#;
+        assert_eq!(value("uptime_checks_total", up, None), 10.0);
+        assert_eq!(value("uptime_downtime_seconds_total", up, None), 60.0);
+        assert_eq!(
+            value(
+                "uptime_probe_bytes_total",
+                up,
+                Some(("direction", "received"))
+            ),
+            65536.0
+        );
+        assert_eq!(
+            value("uptime_response_seconds", up, Some(("quantile", "0.9"))),
+            0.2
+        );
+        assert!(value("uptime_response_seconds", down, Some(("quantile", "0.9"))).is_nan());
+        assert_eq!(
+            value(
+                "uptime_scheduling_delay_seconds",
+                up,
+                Some(("priority", "critical"))
+            ),
+            0.0
+        );
+        assert_eq!(value("uptime_up", down, None), 0.0);
+    }
+
+    #[test]
+    fn prometheus_output_has_no_openmetrics_markers() {
+        let text = render(&fixture());
+        assert!(!text.contains("# EOF"));
+        assert!(!text.contains("# UNIT"));
+        assert!(text.contains("# TYPE uptime_downtime_seconds_total counter\n"));
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::warn;
+
+/// One instance's view of every endpoint it monitors.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceReport {
+    pub instance_id: String,
+    pub updated_at: DateTime<Utc>,
+    /// Endpoint URL -> whether this instance currently sees it as up
+    pub statuses: HashMap<String, bool>,
+}
+
+/// Shared state backed by a directory every instance can read and write
+/// (e.g. an NFS mount), holding one report file per instance.
+#[derive(Debug)]
+pub struct QuorumStore {
+    dir: PathBuf,
+    instance_id: String,
+    /// Number of instances that must see an endpoint down before alerting
+    pub required: usize,
+    max_age: Duration,
+}
+
+impl QuorumStore {
+    pub fn new(dir: PathBuf, instance_id: String, required: usize, max_age: Duration) -> Self {
+        Self {
+            dir,
+            instance_id,
+            required,
+            max_age,
+        }
+    }
+
+    fn report_path(&self, instance_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", instance_id))
+    }
+
+    pub fn publish(&self, statuses: HashMap<String, bool>) -> io::Result<()> {
+        let report = InstanceReport {
+            instance_id: self.instance_id.clone(),
+            updated_at: Utc::now(),
+            statuses,
+        };
+        let json = serde_json::to_string_pretty(&report)?;
+        crate::monitor::write_atomic(&self.report_path(&self.instance_id), &json)
+    }
+
+    /// Reads every instance's report, skipping ones too old to trust.
+    pub fn read_reports(&self) -> io::Result<Vec<InstanceReport>> {
+        let mut reports = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            match read_report(&path) {
+                Ok(report) => {
+                    let age = (Utc::now() - report.updated_at)
+                        .to_std()
+                        .unwrap_or_default();
+                    if age <= self.max_age {
+                        reports.push(report);
+                    }
+                }
+                Err(e) => warn!(
+                    "Ignoring unreadable quorum report {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        Ok(reports)
+    }
+}
+
+fn read_report(path: &Path) -> io::Result<InstanceReport> {
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Counts how many reports see `endpoint` as down.
+pub fn down_votes(reports: &[InstanceReport], endpoint: &str) -> usize {
+    reports
+        .iter()
+        .filter(|report| report.statuses.get(endpoint) == Some(&false))
+        .count()
+}
@@ -0,0 +1,75 @@
+use ipnet::IpNet;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Clients tracked before idle ones are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-IP token bucket for the HTTP server: each client may burst up to one
+/// second's worth of requests and is then held to `per_second`.
+pub struct RateLimiter {
+    per_second: f64,
+    trusted: Vec<IpNet>,
+    clients: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32, trusted: Vec<IpNet>) -> Self {
+        Self {
+            per_second: per_second as f64,
+            trusted,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for `ip`, or returns how long until one is available.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        // IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6 addresses
+        let ip = ip.to_canonical();
+        if self.trusted.iter().any(|net| net.contains(&ip)) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&ip) {
+            let per_second = self.per_second;
+            clients.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < per_second
+            });
+        }
+        let bucket = clients.entry(ip).or_insert(Bucket {
+            tokens: self.per_second,
+            updated: now,
+        });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * self.per_second;
+        bucket.tokens = (bucket.tokens + refill).min(self.per_second);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+}
+
+/// Parses a `--trusted-ips` entry: a CIDR block or a single address.
+pub fn parse_trusted_ip(value: &str) -> Result<IpNet, String> {
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("'{}' is not an IP address or CIDR block", value))
+}
@@ -0,0 +1,95 @@
+use crate::writer::{WriteKind, Writer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+pub const RECENT_CHECKS_PATH: &str = "metrics/recent_checks.json";
+
+/// One individual check, as kept in [`RecentChecks`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckRecord {
+    pub(crate) timestamp: DateTime<Utc>,
+    pub(crate) success: bool,
+    pub(crate) response_time_ms: u64,
+    /// Response headers on the endpoint's `capture_headers` allowlist
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) headers: BTreeMap<String, String>,
+}
+
+/// The last `--recent-checks` checks of each endpoint, oldest first, for
+/// reconstructing exactly when an endpoint went down and came back. Kept out
+/// of the metrics, which are saved after every check, and written at most
+/// once per round.
+pub struct RecentChecks {
+    path: PathBuf,
+    capacity: usize,
+    endpoints: HashMap<String, VecDeque<CheckRecord>>,
+    dirty: bool,
+}
+
+impl RecentChecks {
+    pub fn open(path: &Path, capacity: usize) -> Self {
+        let mut endpoints: HashMap<String, VecDeque<CheckRecord>> = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                error!(
+                    "Ignoring unreadable recent checks {}: {}",
+                    path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                error!("Failed to read recent checks {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        // --recent-checks may have been lowered since the file was written
+        for checks in endpoints.values_mut() {
+            while checks.len() > capacity {
+                checks.pop_front();
+            }
+        }
+        endpoints.retain(|_, checks| !checks.is_empty());
+        Self {
+            path: path.to_path_buf(),
+            capacity,
+            endpoints,
+            dirty: false,
+        }
+    }
+
+    pub fn record(&mut self, endpoint: &str, check: CheckRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let checks = self.endpoints.entry(endpoint.to_string()).or_default();
+        if checks.len() == self.capacity {
+            checks.pop_front();
+        }
+        checks.push_back(check);
+        self.dirty = true;
+    }
+
+    /// The endpoint's kept checks, oldest first.
+    pub fn get(&self, endpoint: &str) -> impl DoubleEndedIterator<Item = &CheckRecord> {
+        self.endpoints.get(endpoint).into_iter().flatten()
+    }
+
+    /// Writes the checks if any were recorded since the last write.
+    pub fn flush(&mut self, writer: &Writer) {
+        if !self.dirty {
+            return;
+        }
+        match serde_json::to_string(&self.endpoints) {
+            // Stays dirty when the write is dropped, so the next round retries it
+            Ok(json) => self.dirty = !writer.write(&self.path, json, WriteKind::Routine),
+            Err(e) => error!("Failed to save recent checks: {}", e),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Only this much of each capture is compared, bounding the cost of a diff.
+const MAX_DIFF_BYTES: usize = 64 * 1024;
+
+/// A check's raw HTTP exchange, saved by `--record` and re-evaluated by `replay`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub endpoint: String,
+    pub recorded_at: DateTime<Utc>,
+    pub response_time: f64,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+    /// Similarity (0.0-1.0) to the endpoint's previous consecutive failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_failure_similarity: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Body decoded as UTF-8, with invalid sequences replaced
+    pub body: String,
+}
+
+pub fn headers_to_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+fn pairs_to_headers(pairs: &[(String, String)]) -> io::Result<HeaderMap> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(e.to_string()))?,
+            HeaderValue::from_str(value).map_err(|e| invalid(e.to_string()))?,
+        );
+    }
+    Ok(headers)
+}
+
+impl RecordedResponse {
+    pub fn status_code(&self) -> io::Result<StatusCode> {
+        StatusCode::from_u16(self.status).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn header_map(&self) -> io::Result<HeaderMap> {
+        pairs_to_headers(&self.headers)
+    }
+}
+
+// Keeps file names readable and filesystem-safe: "https://a.com/x" -> "https___a.com_x".
+fn sanitize(endpoint: &str) -> String {
+    endpoint
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub fn save(dir: &Path, exchange: &RecordedExchange) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "{}-{}.json",
+        sanitize(&exchange.endpoint),
+        exchange.recorded_at.format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    fs::write(&path, serde_json::to_string_pretty(exchange)?)?;
+    Ok(path)
+}
+
+pub fn load(path: &Path) -> io::Result<RecordedExchange> {
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Status line plus body with every match of `patterns` removed, so volatile
+/// parts like timestamps and request IDs don't count as differences.
+pub fn normalize_capture(status: u16, body: &str, patterns: &[Regex]) -> String {
+    let mut text = format!("{}\n{}", status, truncate(body, MAX_DIFF_BYTES));
+    for pattern in patterns {
+        text = pattern.replace_all(&text, "").into_owned();
+    }
+    text
+}
+
+fn truncate(text: &str, max: usize) -> &str {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Dice coefficient over character bigrams: 1.0 for identical text, 0.0 for
+/// text sharing no bigram. Inputs are capped at [`MAX_DIFF_BYTES`].
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (truncate(a, MAX_DIFF_BYTES), truncate(b, MAX_DIFF_BYTES));
+    if a == b {
+        return 1.0;
+    }
+    let bigrams = |text: &str| {
+        let chars: Vec<char> = text.chars().collect();
+        let mut counts: HashMap<(char, char), usize> = HashMap::new();
+        for pair in chars.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        (counts, chars.len().saturating_sub(1))
+    };
+    let ((a, a_total), (b, b_total)) = (bigrams(a), bigrams(b));
+    if a_total + b_total == 0 {
+        return 0.0;
+    }
+    let shared: usize = a
+        .iter()
+        .map(|(pair, count)| (*count).min(b.get(pair).copied().unwrap_or(0)))
+        .sum();
+    2.0 * shared as f64 / (a_total + b_total) as f64
+}
+
+/// Describes how a failure compares to the previous one, for the check record.
+pub fn describe_similarity(similarity: f64) -> String {
+    if similarity >= 1.0 {
+        "identical to previous failure".to_string()
+    } else {
+        format!(
+            "differs from previous failure, {:.0}% similar",
+            (similarity * 100.0).min(99.0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn identical_text_is_fully_similar() {
+        assert_eq!(similarity("502 Bad Gateway", "502 Bad Gateway"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+    }
+
+    #[test]
+    fn text_sharing_no_bigram_is_not_similar() {
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        // Too short to have a bigram
+        assert_eq!(similarity("a", "b"), 0.0);
+        assert_eq!(similarity("", "abc"), 0.0);
+    }
+
+    #[test]
+    fn similarity_is_the_dice_coefficient_over_bigrams() {
+        // ni ig gh ht against na ac ch ht: one of eight bigrams shared per side
+        assert_eq!(similarity("night", "nacht"), 0.25);
+        // Repeated bigrams count as often as both sides have them
+        assert_eq!(similarity("aaaa", "aa"), 0.5);
+        assert_eq!(similarity("nacht", "night"), similarity("night", "nacht"));
+        let close = similarity("502 Bad Gateway from edge-1", "502 Bad Gateway from edge-2");
+        let far = similarity("502 Bad Gateway from edge-1", "504 Gateway Timeout");
+        assert!(close > 0.9 && close < 1.0, "{}", close);
+        assert!(far < close, "{}", far);
+    }
+
+    #[test]
+    fn only_the_first_64_kib_are_compared() {
+        let prefix = "x".repeat(MAX_DIFF_BYTES);
+        assert_eq!(
+            similarity(&format!("{}a", prefix), &format!("{}b", prefix)),
+            1.0
+        );
+        let short = "<html>error</html>";
+        assert!(similarity(&format!("{}{}", short, prefix), short) < 1.0);
+    }
+
+    #[test]
+    fn huge_captures_are_diffed_quickly() {
+        let a: String = (0..4_000_000)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+        let b: String = (0..4_000_000)
+            .map(|i| char::from(b'a' + (i % 25) as u8))
+            .collect();
+        let started = Instant::now();
+        let score = similarity(&a, &b);
+        assert!((0.0..1.0).contains(&score));
+        assert!(started.elapsed().as_secs() < 5);
+    }
+
+    #[test]
+    fn truncation_keeps_whole_characters() {
+        assert_eq!(truncate("aé", 2), "a");
+        assert_eq!(truncate("aé", 3), "aé");
+        assert_eq!(truncate("abc", 10), "abc");
+        // A two-byte character straddling the limit
+        let text = format!("a{}", "é".repeat(MAX_DIFF_BYTES));
+        assert_eq!(truncate(&text, MAX_DIFF_BYTES).len(), MAX_DIFF_BYTES - 1);
+        assert_eq!(similarity(&text, &text[..MAX_DIFF_BYTES - 1]), 1.0);
+    }
+
+    #[test]
+    fn normalizing_strips_volatile_parts() {
+        let patterns = [
+            Regex::new(r"\d{4}-\d\d-\d\dT[\d:.]+Z").unwrap(),
+            Regex::new(r"request-id: \w+").unwrap(),
+        ];
+        let first = normalize_capture(
+            502,
+            "Bad Gateway at 2026-10-16T12:00:00.123Z, request-id: abc123",
+            &patterns,
+        );
+        let second = normalize_capture(
+            502,
+            "Bad Gateway at 2026-10-16T12:00:05.456Z, request-id: def456",
+            &patterns,
+        );
+        assert_eq!(first, "502\nBad Gateway at , ");
+        assert_eq!(similarity(&first, &second), 1.0);
+        // The status is part of what's compared
+        let other = normalize_capture(504, "Bad Gateway at , ", &[]);
+        assert!(similarity(&first, &other) < 1.0);
+    }
+
+    #[test]
+    fn normalizing_caps_the_body() {
+        let body = "y".repeat(2 * MAX_DIFF_BYTES);
+        assert_eq!(
+            normalize_capture(500, &body, &[]).len(),
+            "500\n".len() + MAX_DIFF_BYTES
+        );
+    }
+
+    #[test]
+    fn descriptions_never_round_up_to_identical() {
+        assert_eq!(describe_similarity(1.0), "identical to previous failure");
+        assert_eq!(
+            describe_similarity(0.61),
+            "differs from previous failure, 61% similar"
+        );
+        assert_eq!(
+            describe_similarity(0.999),
+            "differs from previous failure, 99% similar"
+        );
+        assert_eq!(
+            describe_similarity(0.0),
+            "differs from previous failure, 0% similar"
+        );
+    }
+}
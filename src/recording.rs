@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::error;
+
+/// An active `ctl record-events` session. Every notifier checks this file when
+/// it dispatches, so recording starts and stops without restarting the monitor.
+pub const EVENT_RECORDING_PATH: &str = "metrics/event_recording.json";
+
+/// Keys whose values are replaced before a payload is written to a recording.
+const SECRET_KEYS: [&str; 6] = [
+    "authorization",
+    "password",
+    "secret",
+    "token",
+    "api_key",
+    "webhook",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventRecording {
+    pub out: PathBuf,
+    pub until: DateTime<Utc>,
+}
+
+/// One dispatched notification, a line of the NDJSON recording.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub at: DateTime<Utc>,
+    /// `slack`, `discord`, `webhook` or `sns`
+    pub notifier: String,
+    /// Event name, e.g. `transition` or `message` for free-form Slack messages
+    pub event: String,
+    /// The body as rendered for the notifier
+    pub payload: Value,
+}
+
+pub fn start(path: &Path, out: &Path, duration: Duration) -> io::Result<EventRecording> {
+    let recording = EventRecording {
+        // The monitor may run from another directory
+        out: std::path::absolute(out)?,
+        until: Utc::now()
+            + chrono::Duration::from_std(duration)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+    };
+    let json = serde_json::to_string_pretty(&recording)?;
+    crate::monitor::write_atomic(path, &json)?;
+    Ok(recording)
+}
+
+fn active_recording(path: &Path, now: DateTime<Utc>) -> io::Result<Option<EventRecording>> {
+    let recording: EventRecording = match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok((recording.until > now).then_some(recording))
+}
+
+/// Appends a dispatched notification to the active recording, if any.
+/// Failures are logged rather than returned so recording never gets in the
+/// way of the notification itself.
+pub fn record(notifier: &str, event: &str, payload: &Value) {
+    let now = Utc::now();
+    let result = active_recording(Path::new(EVENT_RECORDING_PATH), now).and_then(|recording| {
+        let Some(recording) = recording else {
+            return Ok(());
+        };
+        let line = serde_json::to_string(&RecordedEvent {
+            at: now,
+            notifier: notifier.to_string(),
+            event: event.to_string(),
+            payload: redact(payload.clone()),
+        })?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&recording.out)?;
+        writeln!(file, "{}", line)
+    });
+    if let Err(e) = result {
+        error!("Failed to record {} {} event: {}", notifier, event, e);
+    }
+}
+
+/// Whether a key or header name looks like it holds a credential.
+pub(crate) fn is_secret_key(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    SECRET_KEYS.iter().any(|secret| lower.contains(secret))
+}
+
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    if is_secret_key(&key) {
+                        (key, Value::String("[redacted]".into()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<RecordedEvent>> {
+    BufReader::new(fs::File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// Parses a replay speed such as `10x`, `0.5x` or `2`.
+pub fn parse_speed(value: &str) -> Result<f64, String> {
+    value
+        .trim_end_matches('x')
+        .parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+        .ok_or_else(|| format!("'{}' is not a speed like 10x or 0.5x", value))
+}
+
+/// POSTs each recorded payload to `url` as JSON, keeping the recorded gaps
+/// between events divided by `speed`. Failed deliveries are retried up to
+/// `retries` times, one second apart.
+pub async fn replay(
+    events: &[RecordedEvent],
+    url: &str,
+    speed: f64,
+    retries: u32,
+) -> Result<(), String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut failures = 0;
+    for (index, event) in events.iter().enumerate() {
+        if let Some(previous) = index.checked_sub(1).map(|i| &events[i]) {
+            let gap = (event.at - previous.at).to_std().unwrap_or_default();
+            tokio::time::sleep(gap.div_f64(speed)).await;
+        }
+
+        let mut attempt = 0;
+        let delivered = loop {
+            let result = client.post(url).json(&event.payload).send().await;
+            match result {
+                Ok(response) if response.status().is_success() => break true,
+                Ok(response) => error!(
+                    "Delivering {} {} event returned HTTP {}",
+                    event.notifier,
+                    event.event,
+                    response.status()
+                ),
+                Err(e) => error!(
+                    "Delivering {} {} event failed: {}",
+                    event.notifier, event.event, e
+                ),
+            }
+            if attempt == retries {
+                break false;
+            }
+            attempt += 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+        println!(
+            "[{}/{}] {} {} {}",
+            index + 1,
+            events.len(),
+            event.notifier,
+            event.event,
+            if delivered { "delivered" } else { "FAILED" }
+        );
+        failures += u32::from(!delivered);
+    }
+    match failures {
+        0 => Ok(()),
+        n => Err(format!(
+            "{} of {} events failed to deliver",
+            n,
+            events.len()
+        )),
+    }
+}
@@ -0,0 +1,213 @@
+use crate::check;
+use std::{fs, future::Future, io, net::SocketAddr, path::Path, time::Duration};
+use tokio::net::{lookup_host, TcpListener, TcpStream, UnixListener, UnixStream};
+
+/// One line of the `--selftest` table.
+pub struct Probe {
+    pub name: String,
+    /// What was found on success, or why it failed
+    pub outcome: Result<String, String>,
+}
+
+/// Runs `check` as a probe, failing it once `timeout` passes so one hung
+/// probe can't stall the others.
+pub async fn probe(
+    name: String,
+    timeout: Duration,
+    check: impl Future<Output = Result<String, String>>,
+) -> Probe {
+    let outcome = match tokio::time::timeout(timeout, check).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(format!(
+            "timed out after {}",
+            humantime::format_duration(timeout)
+        )),
+    };
+    Probe { name, outcome }
+}
+
+/// Resolves the endpoint's host and opens a TCP connection to it, without
+/// sending a request.
+pub async fn connect(endpoint: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(endpoint).map_err(|e| format!("invalid URL: {}", e))?;
+    let host = url
+        .host_str()
+        .ok_or("no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url
+        .port_or_known_default()
+        .or(check::is_mqtt(endpoint).then_some(1883))
+        .ok_or("no port")?;
+    let addr = lookup_host((host, port))
+        .await
+        .map_err(|e| format!("DNS lookup failed: {}", e))?
+        .next()
+        .ok_or("DNS lookup found no addresses")?;
+    TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("connecting to {} failed: {}", addr, e))?;
+    Ok(format!("connected to {}", addr))
+}
+
+/// Writes and removes a file in the data directory. A directory that had to
+/// be created for this is removed again.
+pub fn data_dir(dir: &Path) -> Result<String, String> {
+    let created = !dir.exists();
+    let file = dir.join(format!(".selftest-{}", std::process::id()));
+    let result = fs::create_dir_all(dir)
+        .and_then(|()| fs::write(&file, b"selftest"))
+        .and_then(|()| fs::remove_file(&file));
+    if created {
+        let _ = fs::remove_dir(dir);
+    }
+    result
+        .map(|()| format!("{} is writable", dir.display()))
+        .map_err(|e| format!("{}: {}", dir.display(), e))
+}
+
+/// Binds the listener and releases it straight away.
+pub async fn bind(addr: SocketAddr) -> Result<String, String> {
+    let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
+    let bound = listener.local_addr().map_err(|e| e.to_string())?;
+    Ok(format!("bound {}", bound))
+}
+
+/// Binds the Unix socket and removes it again. A socket left at the path is
+/// only reported, since it may belong to a running monitor.
+pub async fn bind_unix(path: &Path) -> Result<String, String> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => {
+            return match UnixStream::connect(path).await {
+                Ok(_) => Err(format!("{} is in use by a running process", path.display())),
+                Err(_) => Ok(format!("{} is stale and will be replaced", path.display())),
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    }
+    UnixListener::bind(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    fs::remove_file(path).map_err(|e| format!("removing {}: {}", path.display(), e))?;
+    Ok(format!("bound {}", path.display()))
+}
+
+/// Prints the results as a table and returns how many probes failed.
+pub fn print_table(probes: &[Probe]) -> usize {
+    print!("{}", table(probes));
+    probes.iter().filter(|p| p.outcome.is_err()).count()
+}
+
+fn table(probes: &[Probe]) -> String {
+    let width = probes.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    let mut table = format!("{:<6}  {:<width$}  DETAIL\n", "RESULT", "PROBE");
+    for probe in probes {
+        let (result, detail) = match &probe.outcome {
+            Ok(detail) => ("pass", detail),
+            Err(reason) => ("FAIL", reason),
+        };
+        table += &format!("{:<6}  {:<width$}  {}\n", result, probe.name, detail);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_result(name: &str, outcome: Result<&str, &str>) -> Probe {
+        Probe {
+            name: name.to_string(),
+            outcome: outcome.map(str::to_string).map_err(str::to_string),
+        }
+    }
+
+    #[test]
+    fn table_aligns_probe_names_and_marks_failures() {
+        let probes = [
+            probe_result("config", Ok("2 endpoints, 0 schedule warnings")),
+            probe_result("notify slack", Err("HTTP 404 Not Found")),
+            probe_result("data directory", Ok("metrics is writable")),
+        ];
+        assert_eq!(
+            table(&probes),
+            "RESULT  PROBE           DETAIL\n\
+             pass    config          2 endpoints, 0 schedule warnings\n\
+             FAIL    notify slack    HTTP 404 Not Found\n\
+             pass    data directory  metrics is writable\n"
+        );
+        assert_eq!(table(&[]), "RESULT  PROBE  DETAIL\n");
+    }
+
+    #[tokio::test]
+    async fn hung_probe_fails_after_the_timeout() {
+        let hung = probe(
+            "notify webhook".into(),
+            Duration::from_millis(10),
+            std::future::pending(),
+        )
+        .await;
+        assert_eq!(hung.outcome, Err("timed out after 10ms".to_string()));
+
+        let quick = probe("config".into(), Duration::from_secs(1), async {
+            Ok("fine".to_string())
+        })
+        .await;
+        assert_eq!(quick.outcome, Ok("fine".to_string()));
+    }
+
+    #[tokio::test]
+    async fn connect_reports_unusable_endpoints() {
+        assert!(connect("not a url")
+            .await
+            .unwrap_err()
+            .starts_with("invalid URL"));
+        assert_eq!(
+            connect("unix:/run/app.sock").await,
+            Err("no host".to_string())
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(
+            connect(&format!("http://{}/health", addr)).await,
+            Ok(format!("connected to {}", addr))
+        );
+    }
+
+    #[test]
+    fn data_dir_probe_leaves_nothing_behind() {
+        let root = tempfile::tempdir().unwrap();
+        let existing = root.path();
+        assert_eq!(
+            data_dir(existing),
+            Ok(format!("{} is writable", existing.display()))
+        );
+        assert_eq!(fs::read_dir(existing).unwrap().count(), 0);
+
+        let missing = root.path().join("metrics");
+        assert!(data_dir(&missing).is_ok());
+        assert!(!missing.exists());
+    }
+
+    #[tokio::test]
+    async fn unix_socket_probe_tells_stale_from_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("uptime.sock");
+        assert_eq!(
+            bind_unix(&path).await,
+            Ok(format!("bound {}", path.display()))
+        );
+        assert!(!path.exists());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert_eq!(
+            bind_unix(&path).await,
+            Err(format!("{} is in use by a running process", path.display()))
+        );
+        drop(listener);
+        assert_eq!(
+            bind_unix(&path).await,
+            Ok(format!("{} is stale and will be replaced", path.display()))
+        );
+    }
+}
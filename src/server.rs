@@ -0,0 +1,301 @@
+use crate::{
+    control, incident,
+    monitor::{MetricsSnapshot, MonitorEvent},
+    prometheus,
+    ratelimit::RateLimiter,
+    snapshot,
+};
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::stream::{Stream, StreamExt};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::{
+    net::{TcpListener, UnixListener},
+    sync::broadcast,
+};
+use tokio_stream::wrappers::BroadcastStream;
+use tracing::{error, info, warn};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub events: broadcast::Sender<MonitorEvent>,
+    pub metrics: MetricsSnapshot,
+    /// Expected `user:password` for HTTP Basic Authentication on every route
+    pub basic_auth: Option<String>,
+    /// Per-IP request limit; Unix socket clients have no IP and are not limited
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+fn router(state: AppState) -> Router {
+    let mut routes = Router::new()
+        .route("/events", get(events))
+        .route("/api/metrics", get(metrics))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/api/incidents", get(incidents));
+    // Opening a deploy window silences alerts, so it is never left open to anyone who can connect
+    if state.basic_auth.is_some() {
+        routes = routes
+            .route("/api/deploy-window", post(start_deploy))
+            .route("/api/deploy-window/{id}/end", post(end_deploy));
+    } else {
+        info!("Deploy window routes are disabled; they require --serve-auth");
+    }
+    protect(routes, state)
+}
+
+/// Only `/metrics`, for `--metrics-addr`.
+fn metrics_router(state: AppState) -> Router {
+    let routes = Router::new().route("/metrics", get(prometheus_metrics));
+    protect(routes, state)
+}
+
+fn protect(routes: Router<AppState>, state: AppState) -> Router {
+    routes
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_basic_auth,
+        ))
+        // Outermost, so failed authentication attempts are limited too
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .with_state(state)
+}
+
+// Compares in constant time so response timing doesn't leak how much of the secret matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let limited = state.rate_limiter.as_ref().zip(
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip()),
+    );
+    if let Some((limiter, ip)) = limited {
+        if let Err(wait) = limiter.check(ip) {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                "rate limit exceeded\n",
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn require_basic_auth(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = &state.basic_auth else {
+        return next.run(request).await;
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok());
+
+    match provided {
+        Some(credentials) if constant_time_eq(&credentials, expected.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"uptime\"")],
+        )
+            .into_response(),
+    }
+}
+
+async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Each client gets its own receiver, which is dropped when the client disconnects
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(|event| async move {
+        // Clients that fall behind skip the events they missed rather than being disconnected
+        let event = event.ok()?;
+        Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .ok()
+            .map(Ok)
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn metrics(State(state): State<AppState>) -> Response {
+    match snapshot::to_json(&state.metrics.load()) {
+        Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// The live metrics in the Prometheus text exposition format, for scraping.
+async fn prometheus_metrics(State(state): State<AppState>) -> Response {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        prometheus::render(&state.metrics.load()),
+    )
+        .into_response()
+}
+
+async fn incidents() -> Response {
+    match incident::load(std::path::Path::new(incident::INCIDENTS_PATH)) {
+        Ok(incidents) => Json(incidents).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct DeployRequest {
+    endpoint: String,
+    /// Humantime duration, e.g. "30m"
+    max_duration: String,
+}
+
+fn deploy_windows_path() -> &'static std::path::Path {
+    std::path::Path::new(control::DEPLOY_WINDOWS_PATH)
+}
+
+async fn start_deploy(Json(request): Json<DeployRequest>) -> Response {
+    let max_duration = match humantime::parse_duration(&request.max_duration) {
+        Ok(duration) => duration,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("max_duration: {}", e)).into_response(),
+    };
+    match control::start_deploy(deploy_windows_path(), &request.endpoint, max_duration) {
+        Ok(window) => Json(window).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn end_deploy(Path(id): Path<String>) -> Response {
+    match control::end_deploy(deploy_windows_path(), &id) {
+        Ok(Some(window)) => Json(window).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("no open deploy window {}", id),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+fn warn_if_reachable(server: &str, addr: SocketAddr, state: &AppState) {
+    if addr.ip().is_unspecified() {
+        warn!(
+            "{} is bound to {} and reachable from other hosts{}",
+            server,
+            addr,
+            if state.basic_auth.is_some() {
+                ""
+            } else {
+                " without authentication; consider --serve-auth"
+            }
+        );
+    }
+}
+
+pub async fn serve(listener: TcpListener, state: AppState) {
+    if let Ok(addr) = listener.local_addr() {
+        info!("HTTP server listening on {}", addr);
+        warn_if_reachable("HTTP server", addr, &state);
+    }
+
+    let app = router(state).into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("HTTP server failed: {}", e);
+    }
+}
+
+/// Serves only the Prometheus metrics, so a scrape port can be opened to
+/// other hosts without exposing the rest of the API.
+pub async fn serve_metrics(listener: TcpListener, state: AppState) {
+    if let Ok(addr) = listener.local_addr() {
+        info!("Prometheus metrics listening on {}", addr);
+        warn_if_reachable("Metrics server", addr, &state);
+    }
+
+    let app = metrics_router(state).into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("Metrics server failed: {}", e);
+    }
+}
+
+/// Serves the same routes on a Unix domain socket, for local collectors or a
+/// reverse proxy on hosts where no TCP port should be opened.
+pub async fn serve_unix(listener: UnixListener, state: AppState) {
+    if let Some(path) = listener
+        .local_addr()
+        .ok()
+        .and_then(|a| a.as_pathname().map(|p| p.display().to_string()))
+    {
+        info!("HTTP server listening on unix:{}", path);
+    }
+
+    if let Err(e) = axum::serve(listener, router(state)).await {
+        error!("HTTP server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    async fn start(basic_auth: Option<&str>) -> SocketAddr {
+        let state = AppState {
+            events: broadcast::channel(1).0,
+            metrics: Arc::new(arc_swap::ArcSwap::from_pointee(HashMap::new())),
+            basic_auth: basic_auth.map(str::to_string),
+            rate_limiter: None,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state));
+        addr
+    }
+
+    async fn open_window(addr: SocketAddr) -> StatusCode {
+        let status = reqwest::Client::new()
+            .post(format!("http://{}/api/deploy-window", addr))
+            .json(&serde_json::json!({"endpoint": "https://a.example", "max_duration": "5m"}))
+            .send()
+            .await
+            .unwrap()
+            .status();
+        StatusCode::from_u16(status.as_u16()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn deploy_windows_are_not_served_without_auth() {
+        let addr = start(None).await;
+        assert_eq!(open_window(addr).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn deploy_windows_require_credentials() {
+        let addr = start(Some("ci:secret")).await;
+        assert_eq!(open_window(addr).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A multi-window burn-rate rule: it fires when both the long and the short
+/// window burn the error budget faster than `burn_rate`.
+pub struct BurnRateRule {
+    pub name: &'static str,
+    pub long_window: Duration,
+    pub short_window: Duration,
+    pub burn_rate: f64,
+}
+
+/// The standard fast-burn (page) and slow-burn (ticket) rules from the SRE workbook.
+pub fn default_rules() -> Vec<BurnRateRule> {
+    vec![
+        BurnRateRule {
+            name: "fast burn",
+            long_window: Duration::hours(1),
+            short_window: Duration::minutes(5),
+            burn_rate: 14.4,
+        },
+        BurnRateRule {
+            name: "slow burn",
+            long_window: Duration::hours(6),
+            short_window: Duration::minutes(30),
+            burn_rate: 6.0,
+        },
+    ]
+}
+
+struct Sample {
+    at: DateTime<Utc>,
+    good: bool,
+}
+
+pub enum SloAlert {
+    Firing {
+        endpoint: String,
+        rule: &'static str,
+        long_window: Duration,
+        burn_rate: f64,
+        threshold: f64,
+    },
+    Resolved {
+        endpoint: String,
+        rule: &'static str,
+    },
+}
+
+pub struct SloTracker {
+    /// Target fraction of good checks, e.g. 0.999
+    target: f64,
+    /// Successful checks slower than this many seconds also count against the budget
+    latency_threshold: Option<f64>,
+    rules: Vec<BurnRateRule>,
+    samples: HashMap<String, VecDeque<Sample>>,
+    firing: HashSet<(String, &'static str)>,
+}
+
+impl SloTracker {
+    pub fn new(target_percent: f64, latency_threshold: Option<f64>) -> Self {
+        Self {
+            target: target_percent / 100.0,
+            latency_threshold,
+            rules: default_rules(),
+            samples: HashMap::new(),
+            firing: HashSet::new(),
+        }
+    }
+
+    fn burn_rate(samples: &VecDeque<Sample>, since: DateTime<Utc>, budget: f64) -> Option<f64> {
+        let (total, bad) = samples
+            .iter()
+            .rev()
+            .take_while(|s| s.at >= since)
+            .fold((0u64, 0u64), |(total, bad), s| {
+                (total + 1, bad + u64::from(!s.good))
+            });
+        if total == 0 {
+            return None;
+        }
+        Some((bad as f64 / total as f64) / budget)
+    }
+
+    /// Records a check and returns the alerts whose state changed because of it.
+    pub fn record(
+        &mut self,
+        endpoint: &str,
+        at: DateTime<Utc>,
+        success: bool,
+        response_time: f64,
+    ) -> Vec<SloAlert> {
+        let good = success
+            && self
+                .latency_threshold
+                .is_none_or(|threshold| response_time <= threshold);
+
+        let longest = self
+            .rules
+            .iter()
+            .map(|rule| rule.long_window)
+            .max()
+            .unwrap_or_else(Duration::zero);
+        let samples = self.samples.entry(endpoint.to_string()).or_default();
+        samples.push_back(Sample { at, good });
+        while samples.front().is_some_and(|s| s.at < at - longest) {
+            samples.pop_front();
+        }
+
+        let budget = (1.0 - self.target).max(f64::EPSILON);
+        let mut alerts = Vec::new();
+        for rule in &self.rules {
+            let long = Self::burn_rate(samples, at - rule.long_window, budget).unwrap_or(0.0);
+            let short = Self::burn_rate(samples, at - rule.short_window, budget).unwrap_or(0.0);
+            let breached = long >= rule.burn_rate && short >= rule.burn_rate;
+
+            let key = (endpoint.to_string(), rule.name);
+            if breached && self.firing.insert(key.clone()) {
+                alerts.push(SloAlert::Firing {
+                    endpoint: endpoint.to_string(),
+                    rule: rule.name,
+                    long_window: rule.long_window,
+                    burn_rate: long,
+                    threshold: rule.burn_rate,
+                });
+            } else if !breached && self.firing.remove(&key) {
+                alerts.push(SloAlert::Resolved {
+                    endpoint: endpoint.to_string(),
+                    rule: rule.name,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+impl SloAlert {
+    pub fn message(&self) -> String {
+        match self {
+            SloAlert::Firing {
+                endpoint,
+                rule,
+                long_window,
+                burn_rate,
+                threshold,
+            } => format!(
+                "🔥 SLO {} alert for {}: error budget burning at {:.1}x over the last {}h (threshold {:.1}x)",
+                rule,
+                endpoint,
+                burn_rate,
+                long_window.num_hours(),
+                threshold
+            ),
+            SloAlert::Resolved { endpoint, rule } => {
+                format!("✅ SLO {} alert for {} resolved", rule, endpoint)
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::monitor::Metrics;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Version of the on-disk metrics format written by this binary.
+///
+/// - v1: a bare `{ endpoint: Metrics }` map
+/// - v2: `{ "schema_version": 2, "endpoints": { endpoint: Metrics } }`
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    schema_version: u32,
+    endpoints: &'a HashMap<String, Metrics>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    endpoints: HashMap<String, Metrics>,
+}
+
+pub fn to_json(metrics: &HashMap<String, Metrics>) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&SnapshotRef {
+        schema_version: SCHEMA_VERSION,
+        endpoints: metrics,
+    })
+}
+
+fn schema_version(json: &Value) -> u32 {
+    // Files written before versioning have no wrapper at all
+    json.get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or(1, |v| v as u32)
+}
+
+pub fn migrate_v1_to_v2(json: Value) -> Value {
+    serde_json::json!({
+        "schema_version": 2,
+        "endpoints": json,
+    })
+}
+
+/// Upgrades a snapshot of any known version to [`SCHEMA_VERSION`].
+pub fn migrate(mut json: Value) -> io::Result<Value> {
+    let mut version = schema_version(&json);
+    if version > SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "metrics schema version {} is newer than supported version {}",
+                version, SCHEMA_VERSION
+            ),
+        ));
+    }
+
+    while version < SCHEMA_VERSION {
+        json = match version {
+            1 => migrate_v1_to_v2(json),
+            _ => unreachable!("no migration from schema version {}", version),
+        };
+        version += 1;
+    }
+    Ok(json)
+}
+
+pub fn read_metrics_file(path: &Path) -> io::Result<HashMap<String, Metrics>> {
+    let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let snapshot: Snapshot = serde_json::from_value(migrate(json)?)?;
+    Ok(snapshot.endpoints)
+}
@@ -0,0 +1,409 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Semaphore,
+};
+use tracing::{error, info, warn};
+
+use crate::{monitor::MonitorEvent, recording};
+
+const SNS_API_VERSION: &str = "2010-03-31";
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+/// Publishes transitions and annotations to an SNS topic as JSON, with
+/// `endpoint`, `status` and `severity` message attributes for subscription
+/// filter policies. Requests go to the SNS Query API signed with SigV4.
+pub struct SnsPublisher {
+    client: Client,
+    topic_arn: String,
+    region: String,
+    /// `https://sns.<region>.amazonaws.com/` unless overridden, e.g. for LocalStack
+    url: String,
+    credentials: Credentials,
+}
+
+impl SnsPublisher {
+    /// Reads `SNS_TOPIC_ARN` and the standard AWS credential variables.
+    /// Returns `Ok(None)` when no topic is configured.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(topic_arn) = std::env::var("SNS_TOPIC_ARN") else {
+            return Ok(None);
+        };
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        // arn:aws:sns:<region>:<account>:<topic>
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .or_else(|| topic_arn.split(':').nth(3).map(str::to_string))
+            .filter(|region| !region.is_empty())
+            .ok_or("SNS_TOPIC_ARN has no region and AWS_REGION is not set")?;
+        let credentials = Credentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID").ok_or("AWS_ACCESS_KEY_ID is not set")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")
+                .ok_or("AWS_SECRET_ACCESS_KEY is not set")?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        };
+        let url = var("AWS_ENDPOINT_URL_SNS")
+            .or_else(|| var("AWS_ENDPOINT_URL"))
+            .unwrap_or_else(|| format!("https://sns.{}.amazonaws.com/", region));
+
+        Ok(Some(Self {
+            client: Client::new(),
+            topic_arn,
+            region,
+            url,
+            credentials,
+        }))
+    }
+
+    /// Publishes events until the monitor goes away, holding one of the
+    /// shared notification slots per request.
+    pub async fn run(self, mut events: broadcast::Receiver<MonitorEvent>, slots: Arc<Semaphore>) {
+        info!("Publishing status changes to SNS topic {}", self.topic_arn);
+        loop {
+            match events.recv().await {
+                // Every check would be a message per endpoint per interval
+                Ok(MonitorEvent::Check { .. }) => {}
+                Ok(event) => {
+                    let Ok(_slot) = slots.acquire().await else {
+                        break;
+                    };
+                    if let Err(e) = self.publish(&event).await {
+                        error!("Failed to publish {} event to SNS: {}", event.name(), e);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "SNS publisher fell behind, {} events not published",
+                        skipped
+                    )
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    // Form body of an SNS Publish call carrying the event.
+    fn publish_body(&self, event: &MonitorEvent) -> serde_json::Result<String> {
+        let message = serde_json::to_string(event)?;
+        let mut body = form_urlencoded::Serializer::new(String::new());
+        body.append_pair("Action", "Publish")
+            .append_pair("Version", SNS_API_VERSION)
+            .append_pair("TopicArn", &self.topic_arn)
+            .append_pair("Message", &message);
+        for (index, (name, value)) in message_attributes(event).iter().enumerate() {
+            let prefix = format!("MessageAttributes.entry.{}", index + 1);
+            body.append_pair(&format!("{}.Name", prefix), name)
+                .append_pair(&format!("{}.Value.DataType", prefix), "String")
+                .append_pair(&format!("{}.Value.StringValue", prefix), value);
+        }
+        Ok(body.finish())
+    }
+
+    pub async fn publish(&self, event: &MonitorEvent) -> Result<(), String> {
+        let body = self.publish_body(event).map_err(|e| e.to_string())?;
+        let attributes: serde_json::Map<String, serde_json::Value> = message_attributes(event)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .collect();
+        recording::record(
+            "sns",
+            event.name(),
+            &serde_json::json!({ "message": event, "attributes": attributes }),
+        );
+        let url = reqwest::Url::parse(&self.url).map_err(|e| e.to_string())?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(format!("invalid SNS endpoint {}", self.url)),
+        };
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut request = self
+            .client
+            .post(url.clone())
+            .header("Content-Type", CONTENT_TYPE)
+            .header("X-Amz-Date", &amz_date)
+            .header(
+                "Authorization",
+                self.authorization(url.path(), &host, &amz_date, &body),
+            );
+        if let Some(token) = &self.credentials.session_token {
+            request = request.header("X-Amz-Security-Token", token);
+        }
+
+        let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("HTTP {}: {}", status, text));
+        }
+        Ok(())
+    }
+
+    // AWS Signature Version 4 for a POST with the given form body.
+    fn authorization(&self, path: &str, host: &str, amz_date: &str, body: &str) -> String {
+        let mut headers = vec![
+            ("content-type", CONTENT_TYPE),
+            ("host", host),
+            ("x-amz-date", amz_date),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token));
+        }
+        sigv4_authorization(
+            &self.credentials,
+            &self.region,
+            "sns",
+            amz_date,
+            &SignedRequest {
+                method: "POST",
+                path,
+                query: "",
+                headers: &headers,
+                body,
+            },
+        )
+    }
+}
+
+/// The parts of a request covered by its signature. `query` is already in
+/// canonical form and `headers` are lowercase and sorted by name.
+struct SignedRequest<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: &'a str,
+    headers: &'a [(&'a str, &'a str)],
+    body: &'a str,
+}
+
+/// `Authorization` header value signing `request` with AWS Signature Version 4.
+fn sigv4_authorization(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    amz_date: &str,
+    request: &SignedRequest,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+
+    let canonical_headers: String = request
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = request
+        .headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{:x}",
+        request.method,
+        if request.path.is_empty() {
+            "/"
+        } else {
+            request.path
+        },
+        request.query,
+        canonical_headers,
+        signed_headers,
+        Sha256::digest(request.body.as_bytes())
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let key = signing_key(&credentials.secret_access_key, date, region, service);
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", secret_access_key).into_bytes(),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Attributes subscribers can filter on, e.g. `{"severity": ["critical"]}`.
+fn message_attributes(event: &MonitorEvent) -> Vec<(&'static str, String)> {
+    match event {
+        MonitorEvent::Transition {
+            endpoint,
+            status,
+            severity,
+            ..
+        } => vec![
+            ("endpoint", endpoint.clone()),
+            ("status", status.clone()),
+            ("severity", severity.to_string()),
+        ],
+        MonitorEvent::Annotation { endpoint, .. } => vec![
+            ("endpoint", endpoint.clone()),
+            ("severity", "info".to_string()),
+        ],
+        MonitorEvent::Check { endpoint, .. } => vec![("endpoint", endpoint.clone())],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Time of the AWS SigV4 test suite, whose example credentials are below
+    const AMZ_DATE: &str = "20150830T123600Z";
+    const PUBLISH_BODY: &str = "Action=Publish&Version=2010-03-31&TopicArn=arn%3Aaws%3Asns%3Aus-east-1%3A123456789012%3Auptime&Message=%7B%22event%22%3A%22transition%22%7D";
+
+    fn credentials(session_token: Option<&str>) -> Credentials {
+        Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: session_token.map(str::to_string),
+        }
+    }
+
+    fn publisher(session_token: Option<&str>) -> SnsPublisher {
+        SnsPublisher {
+            client: Client::new(),
+            topic_arn: "arn:aws:sns:us-east-1:123456789012:uptime".to_string(),
+            region: "us-east-1".to_string(),
+            url: "https://sns.us-east-1.amazonaws.com/".to_string(),
+            credentials: credentials(session_token),
+        }
+    }
+
+    /// `get-vanilla` from the AWS SigV4 test suite.
+    #[test]
+    fn signs_get_vanilla() {
+        let authorization = sigv4_authorization(
+            &credentials(None),
+            "us-east-1",
+            "service",
+            AMZ_DATE,
+            &SignedRequest {
+                method: "GET",
+                path: "/",
+                query: "",
+                headers: &[("host", "example.amazonaws.com"), ("x-amz-date", AMZ_DATE)],
+                body: "",
+            },
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    /// The IAM `ListUsers` example of the AWS SigV4 documentation.
+    #[test]
+    fn signs_the_iam_list_users_example() {
+        assert_eq!(
+            hex(&signing_key(
+                &credentials(None).secret_access_key,
+                "20150830",
+                "us-east-1",
+                "iam"
+            )),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+        let authorization = sigv4_authorization(
+            &credentials(None),
+            "us-east-1",
+            "iam",
+            AMZ_DATE,
+            &SignedRequest {
+                method: "GET",
+                path: "/",
+                query: "Action=ListUsers&Version=2010-05-08",
+                headers: &[
+                    ("content-type", CONTENT_TYPE),
+                    ("host", "iam.amazonaws.com"),
+                    ("x-amz-date", AMZ_DATE),
+                ],
+                body: "",
+            },
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    // The publish signatures below match botocore's SigV4Auth for the same
+    // request, credentials and time.
+
+    #[test]
+    fn signs_a_publish_request() {
+        assert_eq!(
+            publisher(None).authorization("/", "sns.us-east-1.amazonaws.com", AMZ_DATE, PUBLISH_BODY),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/sns/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=c9a1faf9f1f3a71cb08a81fa206a505574a7cefcfa2df3d486d0f41743c31330"
+        );
+        // An empty path is signed as the root
+        assert_eq!(
+            publisher(None).authorization(
+                "",
+                "sns.us-east-1.amazonaws.com",
+                AMZ_DATE,
+                PUBLISH_BODY
+            ),
+            publisher(None).authorization(
+                "/",
+                "sns.us-east-1.amazonaws.com",
+                AMZ_DATE,
+                PUBLISH_BODY
+            )
+        );
+    }
+
+    #[test]
+    fn signs_the_session_token() {
+        assert_eq!(
+            publisher(Some("session-token-example")).authorization(
+                "/",
+                "sns.us-east-1.amazonaws.com",
+                AMZ_DATE,
+                PUBLISH_BODY
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/sns/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, Signature=9d557db156dc84a812e6d4b2c6bb3446d1223f62329d0f2bf63d637854399876"
+        );
+    }
+
+    #[test]
+    fn signs_the_port_of_an_overridden_endpoint() {
+        assert_eq!(
+            publisher(None).authorization("/", "localhost:4566", AMZ_DATE, PUBLISH_BODY),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/sns/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=01554cc7a599486352b9fc01c514ad1a5f0585d4f6412bc826a9addae8baf53e"
+        );
+    }
+}
@@ -0,0 +1,639 @@
+use crate::{
+    monitor::{Metrics, LEGACY_METRICS_PATH},
+    snapshot,
+    writer::{PersistenceHealth, DEGRADED_AFTER_FAILURES, STORAGE_LOG_TARGET},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Row, Sqlite, Transaction,
+};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot, Notify,
+};
+use tracing::{debug, error, info, warn};
+
+pub const DEFAULT_DB_PATH: &str = "metrics/uptime.db";
+
+/// Check rows older than this are deleted, like the hourly check history.
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 3600);
+
+const PRUNE_EVERY: Duration = Duration::from_secs(3600);
+
+/// One check, as a row of `check_history`.
+#[derive(Debug)]
+pub struct CheckRow {
+    pub endpoint: String,
+    pub checked_at: DateTime<Utc>,
+    pub success: bool,
+    pub response_time_ms: u64,
+    pub status_code: Option<u16>,
+    pub failure_reason: Option<String>,
+}
+
+enum Op {
+    Check(CheckRow),
+    /// Answered once everything saved before it was committed
+    Barrier(oneshot::Sender<()>),
+}
+
+/// Latest unsaved metrics of each stored key; `None` deletes the endpoint
+type Pending = HashMap<String, Option<Metrics>>;
+
+/// The SQLite database the monitor persists its metrics in: each endpoint's
+/// aggregates as a row of `endpoint_aggregates` and every check in
+/// `check_history`. A save only writes the endpoint that changed. Writes go
+/// through a task of their own, like the [`Writer`](crate::writer::Writer)'s,
+/// so the check loop never waits on the database.
+#[derive(Debug)]
+pub struct Store {
+    pool: SqlitePool,
+    queue: mpsc::Sender<Op>,
+    /// Saves not committed yet; a newer save of an endpoint replaces the
+    /// older one instead of queueing behind it
+    pending: Arc<Mutex<Pending>>,
+    wake: Arc<Notify>,
+    /// Check rows dropped since the queue last had room
+    dropped: AtomicU64,
+    health: Arc<Mutex<PersistenceHealth>>,
+}
+
+impl Store {
+    /// Opens or creates the database, bringing its schema up to date, and
+    /// starts the task writing to it. A metrics file left by an earlier
+    /// version at `legacy` is imported into a database without metrics.
+    pub async fn open(path: &Path, legacy: &Path, capacity: usize) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+        }
+        let pool = connect(path, legacy, true).await?;
+        info!("Storing metrics in {}", path.display());
+
+        let (queue, ops) = mpsc::channel(capacity);
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let wake = Arc::new(Notify::new());
+        let health = Arc::new(Mutex::new(PersistenceHealth::default()));
+        tokio::spawn(run(
+            pool.clone(),
+            ops,
+            pending.clone(),
+            wake.clone(),
+            health.clone(),
+            capacity,
+        ));
+        Ok(Self {
+            pool,
+            queue,
+            pending,
+            wake,
+            dropped: AtomicU64::new(0),
+            health,
+        })
+    }
+
+    /// Every endpoint's saved metrics, keyed as they were stored.
+    pub async fn load_metrics(&self) -> Result<HashMap<String, Metrics>, String> {
+        load_metrics(&self.pool)
+            .await
+            .map_err(|e| format!("failed to read saved metrics: {}", e))
+    }
+
+    /// Queues the metrics of the endpoint stored under `key`.
+    pub fn save(&self, key: &str, metrics: &Metrics) {
+        self.set_pending(key, Some(metrics.clone()));
+    }
+
+    /// Queues deleting the aggregates stored under `key`; its checks are kept
+    /// until they expire.
+    pub fn remove(&self, key: &str) {
+        self.set_pending(key, None);
+    }
+
+    pub fn record_check(&self, row: CheckRow) {
+        match self.queue.try_send(Op::Check(row)) {
+            Ok(()) => {
+                let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    info!("Database caught up after {} checks were dropped", dropped);
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("Database is falling behind; dropping check rows until it catches up");
+                }
+            }
+            // The task logged why it stopped
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Waits until everything saved so far has been committed, or failed to,
+    /// e.g. before exiting.
+    pub async fn flush(&self) {
+        let (done, committed) = oneshot::channel();
+        if self.queue.send(Op::Barrier(done)).await.is_ok() {
+            let _ = committed.await;
+        }
+    }
+
+    pub fn health(&self) -> PersistenceHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set_pending(&self, key: &str, metrics: Option<Metrics>) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), metrics);
+        self.wake.notify_one();
+    }
+}
+
+/// Opens the database and brings its schema up to date, importing the metrics
+/// file at `legacy` the first time.
+async fn connect(path: &Path, legacy: &Path, create: bool) -> Result<SqlitePool, String> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(create)
+        .journal_mode(SqliteJournalMode::Wal);
+    // A single connection: every write comes from the one task
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .map_err(|e| format!("failed to migrate {}: {}", path.display(), e))?;
+    import_metrics_file(&pool, legacy)
+        .await
+        .map_err(|e| format!("failed to import {}: {}", legacy.display(), e))?;
+    Ok(pool)
+}
+
+/// Moves the metrics file written by earlier versions into a database that
+/// has no metrics yet, then renames the file so it is only imported once. An
+/// unreadable file is left alone, to be fixed and imported on the next start.
+async fn import_metrics_file(pool: &SqlitePool, path: &Path) -> sqlx::Result<()> {
+    let metrics = match snapshot::read_metrics_file(path) {
+        Ok(metrics) => metrics,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            warn!(
+                "Not importing unreadable metrics file {}, starting fresh: {}",
+                path.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+    let saved: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM endpoint_aggregates")
+        .fetch_one(pool)
+        .await?;
+    if saved > 0 {
+        warn!(
+            "Ignoring {}: the database already has metrics; remove the file once it is no longer needed",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    let now = Utc::now();
+    for (key, m) in &metrics {
+        upsert_aggregates(&mut tx, key, m, now).await?;
+    }
+    tx.commit().await?;
+
+    let imported = imported_path(path);
+    fs::rename(path, &imported).map_err(sqlx::Error::Io)?;
+    info!(
+        "Imported metrics for {} endpoints from {} (renamed to {})",
+        metrics.len(),
+        path.display(),
+        imported.display()
+    );
+    Ok(())
+}
+
+fn imported_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".imported");
+    PathBuf::from(name)
+}
+
+async fn run(
+    pool: SqlitePool,
+    mut ops: mpsc::Receiver<Op>,
+    pending: Arc<Mutex<Pending>>,
+    wake: Arc<Notify>,
+    health: Arc<Mutex<PersistenceHealth>>,
+    capacity: usize,
+) {
+    // Check rows not committed yet, retried with the next commit
+    let mut checks: Vec<CheckRow> = Vec::new();
+    let mut failures = 0;
+    let mut pruned = Instant::now() - PRUNE_EVERY;
+    loop {
+        let mut barriers = Vec::new();
+        let mut collect = |op| match op {
+            Op::Check(row) => checks.push(row),
+            Op::Barrier(done) => barriers.push(done),
+        };
+        tokio::select! {
+            op = ops.recv() => match op {
+                Some(op) => collect(op),
+                None => break,
+            },
+            () = wake.notified() => {}
+        }
+        // Whatever queued up meanwhile is committed in the same transaction
+        while let Ok(op) = ops.try_recv() {
+            collect(op);
+        }
+        let aggregates = std::mem::take(&mut *pending.lock().unwrap_or_else(|e| e.into_inner()));
+
+        let started = Instant::now();
+        match commit(&pool, &aggregates, &checks).await {
+            Ok(()) => {
+                if !aggregates.is_empty() {
+                    debug!(
+                        target: STORAGE_LOG_TARGET,
+                        endpoints = aggregates.len(),
+                        checks = checks.len(),
+                        commit_ms = started.elapsed().as_secs_f64() * 1000.0,
+                        "metrics saved"
+                    );
+                }
+                checks.clear();
+                failures = 0;
+                let mut health = health.lock().unwrap_or_else(|e| e.into_inner());
+                health.backlog = 0;
+                if health.degraded {
+                    health.degraded = false;
+                    info!("Database writes are succeeding again; saved the unsaved metrics");
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+                // Saves made meanwhile are newer than the ones that failed
+                for (key, metrics) in aggregates {
+                    pending.entry(key).or_insert(metrics);
+                }
+                if checks.len() > capacity {
+                    let excess = checks.len() - capacity;
+                    checks.drain(..excess);
+                }
+                let mut health = health.lock().unwrap_or_else(|e| e.into_inner());
+                if health.degraded {
+                    debug!(target: STORAGE_LOG_TARGET, error = %e, "failed to save metrics");
+                } else if failures >= DEGRADED_AFTER_FAILURES {
+                    health.degraded = true;
+                    error!(
+                        "Database writes keep failing ({}); monitoring continues from memory and unsaved metrics are kept until writes succeed",
+                        e
+                    );
+                } else {
+                    error!(target: STORAGE_LOG_TARGET, error = %e, "failed to save metrics");
+                }
+                health.last_error = Some(e.to_string());
+                health.backlog = pending.len() + checks.len();
+            }
+        }
+        for done in barriers {
+            let _ = done.send(());
+        }
+
+        if pruned.elapsed() >= PRUNE_EVERY {
+            pruned = Instant::now();
+            if let Err(e) = prune(&pool).await {
+                error!("Failed to prune old checks from the database: {}", e);
+            }
+        }
+    }
+}
+
+async fn commit(pool: &SqlitePool, aggregates: &Pending, checks: &[CheckRow]) -> sqlx::Result<()> {
+    if aggregates.is_empty() && checks.is_empty() {
+        return Ok(());
+    }
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    for (key, metrics) in aggregates {
+        match metrics {
+            Some(metrics) => upsert_aggregates(&mut tx, key, metrics, now).await?,
+            None => {
+                sqlx::query("DELETE FROM endpoint_aggregates WHERE endpoint = ?")
+                    .bind(key)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
+    for row in checks {
+        insert_check(&mut tx, row).await?;
+    }
+    tx.commit().await
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> sqlx::Result<String> {
+    serde_json::to_string(value).map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> sqlx::Result<T> {
+    serde_json::from_str(json).map_err(|e| sqlx::Error::Decode(e.into()))
+}
+
+async fn upsert_aggregates(
+    tx: &mut Transaction<'_, Sqlite>,
+    key: &str,
+    m: &Metrics,
+    now: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO endpoint_aggregates (endpoint, url, total_checks, \
+         successful_checks, failed_checks, total_downtime, last_check, last_status, \
+         average_response_time, request_bytes, response_bytes, transport_retries, \
+         ipv4_connect_time, ipv6_connect_time, last_outcome, last_status_code, draining_checks, \
+         last_response_time, recent_error_rate, health_score, deploy_downtime, \
+         maintenance_checks, maintenance_downtime, bytes_today, traffic_day, range_supported, \
+         priority, consecutive_failures, consecutive_successes, clock_skew, cert_expiry, \
+         scheduling_delay, latency_budget, uptime_windows, response_quantiles, cluster_health, \
+         uptime_percentage, updated_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+         ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(key)
+    .bind(&m.endpoint)
+    .bind(m.total_checks as i64)
+    .bind(m.successful_checks as i64)
+    .bind(m.failed_checks as i64)
+    .bind(m.total_downtime as i64)
+    .bind(m.last_check)
+    .bind(m.last_status.as_deref())
+    .bind(m.average_response_time)
+    .bind(m.request_bytes as i64)
+    .bind(m.response_bytes as i64)
+    .bind(m.transport_retries as i64)
+    .bind(m.ipv4_connect_time)
+    .bind(m.ipv6_connect_time)
+    .bind(m.last_outcome.as_deref())
+    .bind(m.last_status_code)
+    .bind(m.draining_checks as i64)
+    .bind(m.last_response_time)
+    .bind(m.recent_error_rate)
+    .bind(m.health_score)
+    .bind(m.deploy_downtime as i64)
+    .bind(m.maintenance_checks as i64)
+    .bind(m.maintenance_downtime as i64)
+    .bind(m.bytes_today as i64)
+    .bind(m.traffic_day)
+    .bind(m.range_supported)
+    .bind(m.priority.to_string())
+    .bind(m.consecutive_failures as i64)
+    .bind(m.consecutive_successes as i64)
+    .bind(m.clock_skew)
+    .bind(m.cert_expiry)
+    .bind(m.scheduling_delay)
+    .bind(m.latency_budget.as_ref().map(to_json).transpose()?)
+    .bind(to_json(&m.uptime_windows)?)
+    .bind(to_json(&m.response_quantiles)?)
+    .bind(m.cluster_health.as_ref().map(to_json).transpose()?)
+    .bind(m.uptime_percentage())
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+fn metrics_from_row(row: &SqliteRow) -> sqlx::Result<(String, Metrics)> {
+    let count = |column: &str| row.try_get::<i64, _>(column).map(|n| n as u64);
+    let json = |column: &str| -> sqlx::Result<Option<String>> { row.try_get(column) };
+    let priority: String = row.try_get("priority")?;
+    let metrics = Metrics {
+        endpoint: row.try_get("url")?,
+        total_checks: count("total_checks")?,
+        successful_checks: count("successful_checks")?,
+        failed_checks: count("failed_checks")?,
+        total_downtime: count("total_downtime")?,
+        last_check: row.try_get("last_check")?,
+        last_status: row.try_get("last_status")?,
+        average_response_time: row.try_get("average_response_time")?,
+        request_bytes: count("request_bytes")?,
+        response_bytes: count("response_bytes")?,
+        transport_retries: count("transport_retries")?,
+        ipv4_connect_time: row.try_get("ipv4_connect_time")?,
+        ipv6_connect_time: row.try_get("ipv6_connect_time")?,
+        last_outcome: row.try_get("last_outcome")?,
+        last_status_code: row.try_get("last_status_code")?,
+        draining_checks: count("draining_checks")?,
+        last_response_time: row.try_get("last_response_time")?,
+        recent_error_rate: row.try_get("recent_error_rate")?,
+        health_score: row.try_get("health_score")?,
+        deploy_downtime: count("deploy_downtime")?,
+        maintenance_checks: count("maintenance_checks")?,
+        maintenance_downtime: count("maintenance_downtime")?,
+        bytes_today: count("bytes_today")?,
+        traffic_day: row.try_get("traffic_day")?,
+        range_supported: row.try_get("range_supported")?,
+        latency_budget: json("latency_budget")?
+            .as_deref()
+            .map(from_json)
+            .transpose()?,
+        priority: crate::priority::parse_priority(&priority)
+            .map_err(|e| sqlx::Error::Decode(e.into()))?,
+        consecutive_failures: count("consecutive_failures")?,
+        consecutive_successes: count("consecutive_successes")?,
+        clock_skew: row.try_get("clock_skew")?,
+        cert_expiry: row.try_get("cert_expiry")?,
+        scheduling_delay: row.try_get("scheduling_delay")?,
+        uptime_windows: from_json(&row.try_get::<String, _>("uptime_windows")?)?,
+        response_quantiles: from_json(&row.try_get::<String, _>("response_quantiles")?)?,
+        cluster_health: json("cluster_health")?
+            .as_deref()
+            .map(from_json)
+            .transpose()?,
+    };
+    Ok((row.try_get("endpoint")?, metrics))
+}
+
+async fn load_metrics(pool: &SqlitePool) -> sqlx::Result<HashMap<String, Metrics>> {
+    sqlx::query("SELECT * FROM endpoint_aggregates")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(metrics_from_row)
+        .collect()
+}
+
+async fn insert_check(tx: &mut Transaction<'_, Sqlite>, row: &CheckRow) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO check_history (endpoint, checked_at, success, response_time_ms, \
+         status_code, failure_reason) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&row.endpoint)
+    .bind(row.checked_at)
+    .bind(row.success)
+    .bind(row.response_time_ms as i64)
+    .bind(row.status_code)
+    .bind(row.failure_reason.as_deref())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn prune(pool: &SqlitePool) -> sqlx::Result<()> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(RETENTION).unwrap_or_default();
+    let deleted = sqlx::query("DELETE FROM check_history WHERE checked_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if deleted > 0 {
+        info!(
+            "Pruned {} checks older than 30 days from the database",
+            deleted
+        );
+    }
+    Ok(())
+}
+
+/// Whether anything is stored under `key`, aggregates or checks.
+pub(crate) async fn has_endpoint(pool: &SqlitePool, key: &str) -> sqlx::Result<bool> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM endpoint_aggregates WHERE endpoint = ?1) \
+         OR EXISTS (SELECT 1 FROM check_history WHERE endpoint = ?1)",
+    )
+    .bind(key)
+    .fetch_one(pool)
+    .await
+}
+
+/// Moves the aggregates and checks stored under `old` to `new`. Returns
+/// whether there were any.
+pub(crate) async fn rename_endpoint(pool: &SqlitePool, old: &str, new: &str) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let mut renamed = 0;
+    for table in ["endpoint_aggregates", "check_history"] {
+        renamed += sqlx::query(&format!(
+            "UPDATE {} SET endpoint = ? WHERE endpoint = ?",
+            table
+        ))
+        .bind(new)
+        .bind(old)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(renamed > 0)
+}
+
+/// The saved metrics of the endpoint stored under `key`.
+pub(crate) async fn load_endpoint(pool: &SqlitePool, key: &str) -> sqlx::Result<Option<Metrics>> {
+    sqlx::query("SELECT * FROM endpoint_aggregates WHERE endpoint = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| metrics_from_row(&row).map(|(_, metrics)| metrics))
+        .transpose()
+}
+
+pub(crate) async fn save_endpoint(
+    pool: &SqlitePool,
+    key: &str,
+    metrics: &Metrics,
+) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    upsert_aggregates(&mut tx, key, metrics, Utc::now()).await?;
+    tx.commit().await
+}
+
+/// When the failed checks of the endpoint stored under `key` between `from`
+/// and `to` ran.
+pub(crate) async fn failed_checks(
+    pool: &SqlitePool,
+    key: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> sqlx::Result<Vec<DateTime<Utc>>> {
+    sqlx::query_scalar(
+        "SELECT checked_at FROM check_history \
+         WHERE endpoint = ? AND NOT success AND checked_at BETWEEN ? AND ?",
+    )
+    .bind(key)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+}
+
+/// The endpoint's oldest check still kept.
+pub(crate) async fn oldest_check(
+    pool: &SqlitePool,
+    key: &str,
+) -> sqlx::Result<Option<DateTime<Utc>>> {
+    sqlx::query_scalar("SELECT MIN(checked_at) FROM check_history WHERE endpoint = ?")
+        .bind(key)
+        .fetch_one(pool)
+        .await
+}
+
+/// Runs `f` on the database at `path` from synchronous code, such as a `ctl`
+/// command. Returns `None` without creating anything when there is no
+/// database yet.
+pub fn with_database<T>(
+    path: &Path,
+    f: impl AsyncFnOnce(&SqlitePool) -> sqlx::Result<T>,
+) -> io::Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let pool = connect(path, Path::new(LEGACY_METRICS_PATH), false)
+            .await
+            .map_err(io::Error::other)?;
+        let result = f(&pool).await.map_err(io::Error::other);
+        pool.close().await;
+        result.map(Some)
+    })
+}
+
+/// The saved metrics, for `ctl` commands: from the database, or from the
+/// metrics file of an earlier version when the monitor hasn't imported it yet.
+pub fn read_metrics(db: &Path, legacy: &Path) -> io::Result<HashMap<String, Metrics>> {
+    match with_database(db, async |pool| load_metrics(pool).await)? {
+        Some(metrics) => Ok(metrics),
+        None => snapshot::read_metrics_file(legacy).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no saved metrics: neither {} nor {} exists",
+                    db.display(),
+                    legacy.display()
+                ),
+            ),
+            _ => e,
+        }),
+    }
+}
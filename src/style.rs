@@ -0,0 +1,126 @@
+use colored::{Color, ColoredString, Colorize};
+use serde::Serialize;
+use std::fmt;
+
+/// Severity a check result is presented with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Up,
+    Down,
+    /// Down on an endpoint listed with `--critical`
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Up => write!(f, "up"),
+            Severity::Down => write!(f, "down"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// Emoji, label and console color for one severity.
+#[derive(Debug, Clone)]
+pub struct StatusStyle {
+    pub emoji: String,
+    pub label: String,
+    pub color: Color,
+}
+
+impl StatusStyle {
+    fn new(emoji: &str, label: &str, color: Color) -> Self {
+        Self {
+            emoji: emoji.to_string(),
+            label: label.to_string(),
+            color,
+        }
+    }
+
+    pub fn colored_label(&self) -> ColoredString {
+        self.label.as_str().color(self.color).bold()
+    }
+}
+
+/// Presentation used by the console, Slack notifications and digests.
+#[derive(Debug, Clone)]
+pub struct StatusStyles {
+    pub up: StatusStyle,
+    pub down: StatusStyle,
+    /// Falls back to `down` when not configured
+    pub critical: Option<StatusStyle>,
+}
+
+impl Default for StatusStyles {
+    fn default() -> Self {
+        Self {
+            up: StatusStyle::new("🟢", "UP", Color::Green),
+            down: StatusStyle::new("🔴", "DOWN", Color::Red),
+            critical: None,
+        }
+    }
+}
+
+impl StatusStyles {
+    pub fn get(&self, severity: Severity) -> &StatusStyle {
+        match severity {
+            Severity::Up => &self.up,
+            Severity::Down => &self.down,
+            Severity::Critical => self.critical.as_ref().unwrap_or(&self.down),
+        }
+    }
+
+    pub fn set(&mut self, severity: Severity, style: StatusStyle) {
+        match severity {
+            Severity::Up => self.up = style,
+            Severity::Down => self.down = style,
+            Severity::Critical => self.critical = Some(style),
+        }
+    }
+}
+
+/// Parses `SEVERITY=EMOJI:LABEL[:COLOR]`, e.g. `critical=🚨:CRITICAL:magenta`.
+pub fn parse_style(value: &str) -> Result<(Severity, StatusStyle), String> {
+    let (severity, style) = value
+        .split_once('=')
+        .ok_or("expected SEVERITY=EMOJI:LABEL[:COLOR]")?;
+    let severity = match severity {
+        "up" => Severity::Up,
+        "down" => Severity::Down,
+        "critical" => Severity::Critical,
+        _ => {
+            return Err(format!(
+                "severity must be up, down or critical, got '{}'",
+                severity
+            ))
+        }
+    };
+
+    let mut parts = style.splitn(3, ':');
+    let emoji = parts.next().unwrap_or_default();
+    let label = parts
+        .next()
+        .filter(|label| !label.is_empty())
+        .ok_or("expected SEVERITY=EMOJI:LABEL[:COLOR]")?;
+    let color = match parts.next() {
+        Some(color) => color
+            .parse()
+            .map_err(|_| format!("unknown color '{}'", color))?,
+        None => match severity {
+            Severity::Up => Color::Green,
+            Severity::Down => Color::Red,
+            Severity::Critical => Color::Magenta,
+        },
+    };
+
+    Ok((
+        severity,
+        StatusStyle {
+            emoji: emoji.to_string(),
+            label: label.to_string(),
+            color,
+        },
+    ))
+}
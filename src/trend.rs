@@ -0,0 +1,349 @@
+use crate::writer::{WriteKind, Writer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+pub const TREND_PATH: &str = "metrics/trend.json";
+
+/// Long enough to compare the last 7 days against the 7 before.
+const HISTORY_HOURS: i64 = 14 * 24;
+
+/// Compared windows as (name, length in hours).
+const WINDOWS: [(&str, i64); 2] = [("24h", 24), ("7d", 7 * 24)];
+
+/// Check counts for one UTC hour.
+#[derive(Debug, Serialize, Deserialize)]
+struct Bucket {
+    /// Hours since the Unix epoch
+    hour: i64,
+    checks: u64,
+    successes: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EndpointTrend {
+    buckets: VecDeque<Bucket>,
+    /// Set when an alert fires; cleared once the drop falls below half the delta
+    firing: bool,
+    last_alert: Option<DateTime<Utc>>,
+}
+
+impl EndpointTrend {
+    /// Uptime percentage over the hours in `(from, to]`.
+    fn uptime(&self, from: i64, to: i64) -> Option<f64> {
+        let (checks, successes) = self
+            .buckets
+            .iter()
+            .filter(|b| b.hour > from && b.hour <= to)
+            .fold((0, 0), |(c, s), b| (c + b.checks, s + b.successes));
+        (checks > 0).then(|| successes as f64 / checks as f64 * 100.0)
+    }
+
+    /// The largest drop across the windows whose history covers both halves.
+    fn worst_drop(&self, hour: i64) -> Option<TrendAlertWindow> {
+        let oldest = self.buckets.front()?.hour;
+        WINDOWS
+            .iter()
+            .filter(|(_, length)| oldest <= hour - 2 * length + 1)
+            .filter_map(|&(window, length)| {
+                Some(TrendAlertWindow {
+                    window,
+                    previous: self.uptime(hour - 2 * length, hour - length)?,
+                    current: self.uptime(hour - length, hour)?,
+                })
+            })
+            .max_by(|a, b| a.drop().total_cmp(&b.drop()))
+    }
+}
+
+struct TrendAlertWindow {
+    window: &'static str,
+    previous: f64,
+    current: f64,
+}
+
+impl TrendAlertWindow {
+    fn drop(&self) -> f64 {
+        self.previous - self.current
+    }
+}
+
+/// A "reliability trending down" notification for one endpoint.
+pub struct TrendAlert {
+    pub endpoint: String,
+    pub window: &'static str,
+    pub previous: f64,
+    pub current: f64,
+}
+
+impl TrendAlert {
+    pub fn message(&self) -> String {
+        format!(
+            "📉 Reliability trending down for {}: {:.2}% uptime over the last {} vs {:.2}% the {} before",
+            self.endpoint, self.current, self.window, self.previous, self.window
+        )
+    }
+}
+
+/// Compares each endpoint's rolling 24h and 7d uptime against the window
+/// before it to catch slow degradation that never trips a threshold. Hourly
+/// counts are persisted so the comparison survives restarts.
+pub struct TrendTracker {
+    path: PathBuf,
+    /// Drop in percentage points that raises an alert
+    delta: f64,
+    endpoints: HashMap<String, EndpointTrend>,
+    dirty: bool,
+}
+
+impl TrendTracker {
+    pub fn open(path: &Path, delta: f64) -> Self {
+        let endpoints = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                error!(
+                    "Ignoring unreadable trend history {}: {}",
+                    path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                error!("Failed to read trend history {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            delta,
+            endpoints,
+            dirty: false,
+        }
+    }
+
+    /// Records a check and returns an alert when the endpoint's uptime has
+    /// dropped by more than the delta, at most once a day per endpoint.
+    pub fn record(
+        &mut self,
+        endpoint: &str,
+        at: DateTime<Utc>,
+        success: bool,
+    ) -> Option<TrendAlert> {
+        let hour = at.timestamp().div_euclid(3600);
+        let trend = self.endpoints.entry(endpoint.to_string()).or_default();
+        match trend.buckets.back_mut() {
+            Some(bucket) if bucket.hour == hour => {
+                bucket.checks += 1;
+                bucket.successes += u64::from(success);
+            }
+            _ => {
+                trend.buckets.push_back(Bucket {
+                    hour,
+                    checks: 1,
+                    successes: u64::from(success),
+                });
+                while trend
+                    .buckets
+                    .front()
+                    .is_some_and(|b| b.hour <= hour - HISTORY_HOURS)
+                {
+                    trend.buckets.pop_front();
+                }
+                // Saving on each new hour loses at most the current hour on a restart
+                self.dirty = true;
+            }
+        }
+
+        let worst = trend.worst_drop(hour)?;
+        if trend.firing {
+            if worst.drop() < self.delta / 2.0 {
+                trend.firing = false;
+                self.dirty = true;
+            }
+            return None;
+        }
+        let alerted_today = trend
+            .last_alert
+            .is_some_and(|last| at - last < chrono::Duration::days(1));
+        if worst.drop() <= self.delta || alerted_today {
+            return None;
+        }
+
+        trend.firing = true;
+        trend.last_alert = Some(at);
+        self.dirty = true;
+        Some(TrendAlert {
+            endpoint: endpoint.to_string(),
+            window: worst.window,
+            previous: worst.previous,
+            current: worst.current,
+        })
+    }
+
+    /// Writes the history if it changed since the last save.
+    pub fn flush(&mut self, writer: &Writer) {
+        if !self.dirty {
+            return;
+        }
+        match serde_json::to_string(&self.endpoints) {
+            // Stays dirty when the write is dropped, so the next round retries it
+            Ok(json) => self.dirty = !writer.write(&self.path, json, WriteKind::Routine),
+            Err(e) => error!("Failed to save trend history: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const URL: &str = "https://api.example.com/health";
+    /// 2026-10-01T00:00:00Z in hours since the epoch
+    const START: i64 = 1_790_812_800 / 3600;
+
+    fn tracker(delta: f64) -> TrendTracker {
+        let dir = tempfile::tempdir().unwrap();
+        TrendTracker::open(&dir.path().join("trend.json"), delta)
+    }
+
+    fn at(hour: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(hour * 3600 + 60, 0).unwrap()
+    }
+
+    /// Replaces the endpoint's history with two windows of `length` hours
+    /// ending at `hour`, of 100 checks an hour with the given successes.
+    fn set_history(
+        tracker: &mut TrendTracker,
+        hour: i64,
+        length: i64,
+        previous: u64,
+        current: u64,
+    ) {
+        let trend = tracker.endpoints.entry(URL.to_string()).or_default();
+        trend.buckets = (hour - 2 * length + 1..=hour)
+            .map(|h| Bucket {
+                hour: h,
+                checks: 100,
+                successes: if h <= hour - length {
+                    previous
+                } else {
+                    current
+                },
+            })
+            .collect();
+    }
+
+    /// Feeds 8 checks an hour, `successes` of them succeeding and the rest
+    /// failing at the end of the hour, returning each alert with its hour
+    /// and the check within it.
+    fn feed(
+        tracker: &mut TrendTracker,
+        hours: std::ops::Range<i64>,
+        successes: u64,
+    ) -> Vec<(i64, u64, TrendAlert)> {
+        let mut alerts = Vec::new();
+        for hour in hours {
+            for check in 0..8 {
+                if let Some(alert) = tracker.record(URL, at(START + hour), check < successes) {
+                    alerts.push((hour, check, alert));
+                }
+            }
+        }
+        alerts
+    }
+
+    #[test]
+    fn first_alert_comes_once_both_24h_windows_have_history() {
+        let mut tracker = tracker(12.45);
+        assert!(feed(&mut tracker, 0..24, 8).is_empty());
+        let alerts = feed(&mut tracker, 24..72, 7);
+
+        // Hour 47 completes the second window at 87.5% against 100%: a
+        // 12.5 point drop, reached on the failed check ending the hour. Its
+        // first check already makes it 12.43, under the delta
+        assert_eq!(alerts.len(), 1);
+        let (hour, check, alert) = &alerts[0];
+        assert_eq!((*hour, *check), (47, 7));
+        assert_eq!(alert.window, "24h");
+        assert_eq!(alert.previous, 100.0);
+        assert_eq!(alert.current, 87.5);
+        assert_eq!(
+            alert.message(),
+            format!(
+                "📉 Reliability trending down for {}: 87.50% uptime over the last 24h vs 100.00% the 24h before",
+                URL
+            )
+        );
+    }
+
+    #[test]
+    fn drop_of_exactly_the_delta_does_not_fire() {
+        let mut tracker = tracker(12.5);
+        feed(&mut tracker, 0..24, 8);
+        assert!(feed(&mut tracker, 24..72, 7).is_empty());
+    }
+
+    #[test]
+    fn steady_uptime_never_fires() {
+        let mut tracker = tracker(0.1);
+        assert!(feed(&mut tracker, 0..400, 7).is_empty());
+    }
+
+    #[test]
+    fn slow_weekly_decline_fires_on_the_7d_window() {
+        // 95% every day of the last week, so day over day nothing changes
+        let mut tracker = tracker(3.0);
+        let hour = START + 14 * 24;
+        set_history(&mut tracker, hour, 7 * 24, 100, 95);
+        let alert = tracker.record(URL, at(hour), true).unwrap();
+        assert_eq!(alert.window, "7d");
+        assert_eq!(alert.previous, 100.0);
+        assert!((alert.current - 95.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn weekly_window_needs_two_full_weeks_of_history() {
+        let mut tracker = tracker(3.0);
+        let hour = START + 14 * 24;
+        set_history(&mut tracker, hour, 7 * 24, 100, 95);
+        tracker.endpoints.get_mut(URL).unwrap().buckets.pop_front();
+        assert!(tracker.record(URL, at(hour), true).is_none());
+    }
+
+    #[test]
+    fn hysteresis_and_daily_limit() {
+        let mut tracker = tracker(10.0);
+        let mut step = |hour: i64, current: u64| {
+            set_history(&mut tracker, START + hour, 24, 100, current);
+            let alert = tracker.record(URL, at(START + hour), true);
+            (alert.map(|a| a.window), tracker.endpoints[URL].firing)
+        };
+
+        assert_eq!(step(0, 85), (Some("24h"), true));
+        // Between half the delta and the delta: still firing, so silent
+        assert_eq!(step(1, 93), (None, true));
+        assert_eq!(step(2, 85), (None, true));
+        // Under half the delta clears it
+        assert_eq!(step(3, 96), (None, false));
+        // Past the delta again, but already alerted today
+        assert_eq!(step(4, 85), (None, false));
+        assert_eq!(step(23, 85), (None, false));
+        assert_eq!(step(24, 85), (Some("24h"), true));
+    }
+
+    #[test]
+    fn history_outside_14_days_is_dropped() {
+        let mut tracker = tracker(10.0);
+        feed(&mut tracker, 0..1, 8);
+        feed(&mut tracker, HISTORY_HOURS..HISTORY_HOURS + 1, 8);
+        let buckets = &tracker.endpoints[URL].buckets;
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].hour, START + HISTORY_HOURS);
+    }
+}
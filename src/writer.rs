@@ -0,0 +1,528 @@
+use crate::monitor::write_atomic;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+use tracing::{debug, error, info, warn};
+
+/// Target of the storage logs of both the writer and the database, e.g. the
+/// DEBUG "metrics saved" events enabled by `--debug-storage`.
+pub const STORAGE_LOG_TARGET: &str = "uptime::writer";
+
+/// Failed writes in a row before persistence counts as degraded.
+pub(crate) const DEGRADED_AFTER_FAILURES: u32 = 3;
+
+/// Files whose unsaved contents are kept in memory while writes fail.
+const MAX_BACKLOG: usize = 64;
+
+/// How much a write matters when storage can't keep up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteKind {
+    /// Superseded by the next write of the same file, so dropped when the queue is full
+    Routine,
+    /// A status change or an explicit dump; queued past the limit instead of dropped
+    Required,
+}
+
+enum Contents {
+    Text(String),
+    /// Not a write: answered once every write queued before it was attempted
+    Barrier(oneshot::Sender<()>),
+}
+
+/// Whether writes are reaching storage, shared with the monitor so it can
+/// alert when persistence degrades and recovers.
+#[derive(Debug, Clone, Default)]
+pub struct PersistenceHealth {
+    /// Set after repeated failed writes, cleared once the backlog is saved
+    pub degraded: bool,
+    pub last_error: Option<String>,
+    /// Files, endpoints or checks waiting to be saved
+    pub backlog: usize,
+}
+
+impl PersistenceHealth {
+    /// Health of two stores taken together: degraded when either is.
+    pub fn merge(self, other: Self) -> Self {
+        let last_error = match (self.degraded, other.degraded) {
+            (false, true) => other.last_error.or(self.last_error),
+            _ => self.last_error.or(other.last_error),
+        };
+        Self {
+            degraded: self.degraded || other.degraded,
+            last_error,
+            backlog: self.backlog + other.backlog,
+        }
+    }
+}
+
+struct Job {
+    /// Submission order; a job older than the last one written to the same
+    /// path is skipped, since required writes can overtake routine ones
+    seq: u64,
+    path: PathBuf,
+    contents: Contents,
+}
+
+/// Where the writer's files end up: the filesystem, except in tests.
+trait Backend: Send + 'static {
+    fn write(&mut self, path: &Path, contents: &str) -> io::Result<()>;
+}
+
+struct Filesystem;
+
+impl Backend for Filesystem {
+    fn write(&mut self, path: &Path, contents: &str) -> io::Result<()> {
+        write_atomic(path, contents)
+    }
+}
+
+/// Hands writes to a dedicated task so the check loop never waits on storage.
+/// Slow storage fills the bounded queue, after which routine writes are
+/// dropped until it drains; required writes always go through.
+pub struct Writer {
+    queue: mpsc::Sender<Job>,
+    overflow: mpsc::UnboundedSender<Job>,
+    seq: AtomicU64,
+    /// Routine writes dropped since the queue last had room
+    dropped: AtomicU64,
+    health: Arc<Mutex<PersistenceHealth>>,
+}
+
+impl Writer {
+    /// Starts the writer task with room for `capacity` pending writes.
+    pub fn spawn(capacity: usize) -> Self {
+        Self::spawn_with(capacity, Filesystem)
+    }
+
+    fn spawn_with(capacity: usize, backend: impl Backend) -> Self {
+        let (queue, jobs) = mpsc::channel(capacity);
+        let (overflow, required) = mpsc::unbounded_channel();
+        let health = Arc::new(Mutex::new(PersistenceHealth::default()));
+        tokio::spawn(run(jobs, required, health.clone(), backend));
+        Self {
+            queue,
+            overflow,
+            seq: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            health,
+        }
+    }
+
+    pub fn health(&self) -> PersistenceHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Queues `contents` to replace the file at `path`. Returns whether the
+    /// write was queued.
+    pub fn write(&self, path: &Path, contents: String, kind: WriteKind) -> bool {
+        self.submit(path, Contents::Text(contents), kind)
+    }
+
+    /// Waits until every write queued so far has been attempted, e.g. before
+    /// exiting.
+    pub async fn flush(&self) {
+        let (done, attempted) = oneshot::channel();
+        let job = Job {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed),
+            path: PathBuf::new(),
+            contents: Contents::Barrier(done),
+        };
+        // Behind the routine writes; required ones are always taken first
+        if self.queue.send(job).await.is_ok() {
+            let _ = attempted.await;
+        }
+    }
+
+    fn submit(&self, path: &Path, contents: Contents, kind: WriteKind) -> bool {
+        let job = Job {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed),
+            path: path.to_path_buf(),
+            contents,
+        };
+        match self.queue.try_send(job) {
+            Ok(()) => {
+                let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    info!(
+                        "Storage caught up after {} routine writes were dropped",
+                        dropped
+                    );
+                }
+                true
+            }
+            Err(TrySendError::Full(job)) if kind == WriteKind::Required => {
+                self.overflow.send(job).is_ok()
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("Storage is falling behind; dropping routine writes until it catches up");
+                }
+                false
+            }
+            // The writer task logged why it stopped
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+async fn run<B: Backend>(
+    mut jobs: mpsc::Receiver<Job>,
+    mut required: mpsc::UnboundedReceiver<Job>,
+    health: Arc<Mutex<PersistenceHealth>>,
+    backend: B,
+) {
+    let mut storage = Storage::new(health, backend);
+    loop {
+        let job = tokio::select! {
+            biased;
+            Some(job) = required.recv() => job,
+            Some(job) = jobs.recv() => job,
+            else => break,
+        };
+        // Writing blocks, so it runs off the async workers
+        storage = match tokio::task::spawn_blocking(move || {
+            storage.apply(job);
+            storage
+        })
+        .await
+        {
+            Ok(storage) => storage,
+            Err(e) => {
+                error!("Storage writer stopped; nothing more will be saved: {}", e);
+                break;
+            }
+        };
+    }
+}
+
+struct Storage<B> {
+    backend: B,
+    /// Sequence number of the last job written to each path
+    written: HashMap<PathBuf, u64>,
+    /// Latest unsaved contents of each path whose write failed, retried
+    /// once a write succeeds again
+    backlog: HashMap<PathBuf, Job>,
+    /// Failed writes in a row
+    failures: u32,
+    health: Arc<Mutex<PersistenceHealth>>,
+}
+
+impl<B: Backend> Storage<B> {
+    fn new(health: Arc<Mutex<PersistenceHealth>>, backend: B) -> Self {
+        Self {
+            backend,
+            written: HashMap::new(),
+            backlog: HashMap::new(),
+            failures: 0,
+            health,
+        }
+    }
+
+    fn is_stale(&self, job: &Job) -> bool {
+        self.written
+            .get(&job.path)
+            .is_some_and(|&seq| seq > job.seq)
+    }
+
+    fn apply(&mut self, job: Job) {
+        if let Contents::Barrier(done) = job.contents {
+            let _ = done.send(());
+            return;
+        }
+        if self.is_stale(&job) {
+            return;
+        }
+        match self.write(&job) {
+            Ok(()) => {
+                self.backlog.remove(&job.path);
+                self.written.insert(job.path, job.seq);
+                self.failures = 0;
+                self.flush_backlog();
+            }
+            Err(e) => self.failed(job, e),
+        }
+    }
+
+    fn write(&mut self, job: &Job) -> io::Result<()> {
+        match &job.contents {
+            Contents::Text(text) => self.backend.write(&job.path, text),
+            Contents::Barrier(_) => Ok(()),
+        }
+    }
+
+    fn failed(&mut self, job: Job, e: io::Error) {
+        self.failures += 1;
+        let mut health = self.health.lock().unwrap_or_else(|e| e.into_inner());
+        if health.degraded {
+            debug!(path = %job.path.display(), error = %e, "failed to write file");
+        } else if self.failures >= DEGRADED_AFTER_FAILURES {
+            health.degraded = true;
+            error!(
+                "Storage writes keep failing ({}); monitoring continues from memory and unsaved files are kept until writes succeed",
+                e
+            );
+        } else {
+            error!(path = %job.path.display(), error = %e, "failed to write file");
+        }
+        health.last_error = Some(format!("{}: {}", job.path.display(), e));
+
+        if self
+            .backlog
+            .get(&job.path)
+            .is_none_or(|queued| queued.seq < job.seq)
+        {
+            self.backlog.insert(job.path.clone(), job);
+        }
+        if self.backlog.len() > MAX_BACKLOG {
+            let oldest = self
+                .backlog
+                .values()
+                .min_by_key(|job| job.seq)
+                .map(|job| job.path.clone());
+            if let Some(path) = oldest {
+                warn!("Dropping unsaved {}, the backlog is full", path.display());
+                self.backlog.remove(&path);
+            }
+        }
+        health.backlog = self.backlog.len();
+    }
+
+    /// Retries the backlog oldest first, stopping at the first failure.
+    fn flush_backlog(&mut self) {
+        let mut pending: Vec<Job> = self.backlog.drain().map(|(_, job)| job).collect();
+        pending.sort_by_key(|job| job.seq);
+        let mut flushed = 0;
+        let mut pending = pending.into_iter();
+        for job in pending.by_ref() {
+            if self.is_stale(&job) {
+                continue;
+            }
+            match self.write(&job) {
+                Ok(()) => {
+                    self.written.insert(job.path, job.seq);
+                    flushed += 1;
+                }
+                Err(e) => {
+                    self.failed(job, e);
+                    break;
+                }
+            }
+        }
+        self.backlog
+            .extend(pending.map(|job| (job.path.clone(), job)));
+
+        let mut health = self.health.lock().unwrap_or_else(|e| e.into_inner());
+        health.backlog = self.backlog.len();
+        if health.degraded && self.backlog.is_empty() {
+            health.degraded = false;
+            info!(
+                "Storage writes are succeeding again; wrote {} files from the backlog",
+                flushed
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// In-memory files behind a switch that makes every write fail as if the
+    /// disk were full.
+    #[derive(Clone, Default)]
+    struct MockDisk(Arc<Mutex<Disk>>);
+
+    #[derive(Default)]
+    struct Disk {
+        full: bool,
+        files: HashMap<PathBuf, String>,
+    }
+
+    impl MockDisk {
+        fn set_full(&self, full: bool) {
+            self.0.lock().unwrap().full = full;
+        }
+
+        fn file(&self, path: &str) -> Option<String> {
+            self.0.lock().unwrap().files.get(Path::new(path)).cloned()
+        }
+    }
+
+    impl Backend for MockDisk {
+        fn write(&mut self, path: &Path, contents: &str) -> io::Result<()> {
+            let mut disk = self.0.lock().unwrap();
+            if disk.full {
+                // ENOSPC
+                return Err(io::Error::from_raw_os_error(28));
+            }
+            disk.files.insert(path.to_path_buf(), contents.to_string());
+            Ok(())
+        }
+    }
+
+    fn storage(disk: &MockDisk) -> Storage<MockDisk> {
+        Storage::new(Arc::default(), disk.clone())
+    }
+
+    fn job(seq: u64, path: &str, text: &str) -> Job {
+        Job {
+            seq,
+            path: PathBuf::from(path),
+            contents: Contents::Text(text.to_string()),
+        }
+    }
+
+    fn health(storage: &Storage<MockDisk>) -> PersistenceHealth {
+        storage.health.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn degrades_after_repeated_failures() {
+        let disk = MockDisk::default();
+        disk.set_full(true);
+        let mut storage = storage(&disk);
+
+        storage.apply(job(1, "a.json", "a"));
+        storage.apply(job(2, "b.json", "b"));
+        assert!(!health(&storage).degraded);
+        assert_eq!(health(&storage).backlog, 2);
+
+        storage.apply(job(3, "c.json", "c"));
+        let health = health(&storage);
+        assert_eq!(storage.failures, DEGRADED_AFTER_FAILURES);
+        assert!(health.degraded);
+        assert_eq!(health.backlog, 3);
+        let error = health.last_error.unwrap();
+        assert!(error.starts_with("c.json: "), "{}", error);
+        assert!(error.contains("os error 28"), "{}", error);
+    }
+
+    #[test]
+    fn success_resets_the_failure_streak() {
+        let disk = MockDisk::default();
+        let mut storage = storage(&disk);
+        for seq in 0..10 {
+            disk.set_full(seq % 2 == 0);
+            storage.apply(job(seq, "a.json", &seq.to_string()));
+        }
+        assert!(!health(&storage).degraded);
+        assert_eq!(disk.file("a.json").as_deref(), Some("9"));
+    }
+
+    #[test]
+    fn backlog_keeps_the_latest_contents_of_each_file_up_to_a_limit() {
+        let disk = MockDisk::default();
+        disk.set_full(true);
+        let mut storage = storage(&disk);
+
+        storage.apply(job(1, "a.json", "old"));
+        storage.apply(job(2, "a.json", "new"));
+        assert_eq!(health(&storage).backlog, 1);
+
+        for seq in 3..3 + MAX_BACKLOG as u64 {
+            storage.apply(job(seq, &format!("{}.json", seq), "x"));
+        }
+        assert_eq!(health(&storage).backlog, MAX_BACKLOG);
+        // The oldest file made room
+        assert!(!storage.backlog.contains_key(Path::new("a.json")));
+
+        disk.set_full(false);
+        storage.apply(job(100, "b.json", "b"));
+        assert_eq!(disk.file("a.json"), None);
+        assert_eq!(disk.file("3.json").as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn recovers_by_writing_the_backlog() {
+        let disk = MockDisk::default();
+        disk.set_full(true);
+        let mut storage = storage(&disk);
+        storage.apply(job(1, "a.json", "a1"));
+        storage.apply(job(2, "b.json", "b1"));
+        storage.apply(job(3, "a.json", "a2"));
+        storage.apply(job(4, "c.json", "c1"));
+        assert!(health(&storage).degraded);
+
+        disk.set_full(false);
+        storage.apply(job(5, "d.json", "d1"));
+
+        let health = health(&storage);
+        assert!(!health.degraded);
+        assert_eq!(health.backlog, 0);
+        assert_eq!(storage.failures, 0);
+        for (path, contents) in [
+            ("a.json", "a2"),
+            ("b.json", "b1"),
+            ("c.json", "c1"),
+            ("d.json", "d1"),
+        ] {
+            assert_eq!(disk.file(path).as_deref(), Some(contents), "{}", path);
+        }
+    }
+
+    #[test]
+    fn backlog_never_overwrites_newer_contents() {
+        let disk = MockDisk::default();
+        disk.set_full(true);
+        let mut storage = storage(&disk);
+        storage.apply(job(1, "a.json", "stale"));
+
+        disk.set_full(false);
+        storage.apply(job(2, "a.json", "fresh"));
+        assert_eq!(disk.file("a.json").as_deref(), Some("fresh"));
+        assert_eq!(health(&storage).backlog, 0);
+
+        // A job overtaken by a newer write of the same file is skipped
+        storage.apply(job(1, "a.json", "stale"));
+        assert_eq!(disk.file("a.json").as_deref(), Some("fresh"));
+    }
+
+    #[tokio::test]
+    async fn full_disk_never_stalls_the_caller() {
+        let disk = MockDisk::default();
+        disk.set_full(true);
+        let writer = Writer::spawn_with(4, disk.clone());
+
+        let started = Instant::now();
+        for i in 0..1000 {
+            writer.write(
+                Path::new(&format!("{}.json", i % 10)),
+                i.to_string(),
+                WriteKind::Routine,
+            );
+        }
+        assert!(writer.write(
+            Path::new("incidents.json"),
+            "open".into(),
+            WriteKind::Required
+        ));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        writer.flush().await;
+        let health = writer.health();
+        assert!(health.degraded);
+        assert!(health.backlog > 0);
+
+        disk.set_full(false);
+        writer.write(Path::new("0.json"), "latest".into(), WriteKind::Required);
+        writer.flush().await;
+        let health = writer.health();
+        assert!(!health.degraded);
+        assert_eq!(health.backlog, 0);
+        assert_eq!(disk.file("0.json").as_deref(), Some("latest"));
+        assert_eq!(disk.file("incidents.json").as_deref(), Some("open"));
+    }
+}